@@ -0,0 +1,35 @@
+//! LZSS decompression throughput for each mode, on texture-like data with runs and
+//! repeated patterns.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use retrotool::util::lzss::{compress, decompress};
+
+/// 1 MiB of compressible data, varying between rows like a texture.
+fn sample() -> Vec<u8> {
+    (0..0x100000usize).map(|i| ((i / 24) ^ (i % 7) ^ ((i >> 10) * 3)) as u8).collect()
+}
+
+fn bench_decompress(c: &mut Criterion) {
+    let input = sample();
+    let blobs = [
+        (1, compress::<1>(&input).unwrap()),
+        (2, compress::<2>(&input).unwrap()),
+        (3, compress::<3>(&input).unwrap()),
+    ];
+    let mut output = vec![0u8; input.len()];
+
+    let mut group = c.benchmark_group("decompress");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    for (mode, blob) in &blobs {
+        group.bench_with_input(BenchmarkId::from_parameter(mode), blob, |b, blob| {
+            b.iter(|| match mode {
+                1 => decompress::<1>(blob, &mut output),
+                2 => decompress::<2>(blob, &mut output),
+                _ => decompress::<3>(blob, &mut output),
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decompress);
+criterion_main!(benches);
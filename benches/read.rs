@@ -0,0 +1,59 @@
+//! Compares decompressing every asset one at a time with an eager read
+//! (`ReadOptions::eager`), which decompresses across threads with the `rayon` feature, and
+//! measures reading the committed fixture package end to end.
+use std::io::{Cursor, Write};
+
+use binrw::Endian;
+use criterion::{criterion_group, criterion_main, Criterion};
+use retrotool::format::{
+    pack::{Package, PackageBuilder, ReadOptions},
+    rfrm::FormDescriptor,
+    FourCC,
+};
+
+/// Package of 256 64 KiB assets compressed with mode 2.
+fn compressed_package() -> Vec<u8> {
+    const K_FORM_TXTR: FourCC = FourCC(*b"TXTR");
+    let mut builder = PackageBuilder::new();
+    for i in 0..256u32 {
+        let payload: Vec<u8> =
+            (0..0x10000u32).map(|n| (i + n / 24) as u8 ^ (n % 7) as u8).collect();
+        let mut cursor = Cursor::new(Vec::new());
+        FormDescriptor { size: 0, unk: 0, id: K_FORM_TXTR, version_a: 1, version_b: 0 }
+            .write(&mut cursor, Endian::Little, |w| {
+                w.write_all(&payload)?;
+                Ok(())
+            })
+            .unwrap();
+        builder.add_asset(K_FORM_TXTR, cursor.into_inner()).with_compression(2);
+    }
+    builder.build().to_vec(Endian::Little).unwrap()
+}
+
+fn bench_read(c: &mut Criterion) {
+    let data = compressed_package();
+    let eager = ReadOptions { eager: true, ..Default::default() };
+
+    let mut group = c.benchmark_group("read");
+    group.sample_size(20);
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            let package = Package::read(&data, Endian::Little).unwrap();
+            for asset in &package.assets {
+                asset.data().unwrap();
+            }
+            package
+        })
+    });
+    group.bench_function("eager", |b| {
+        b.iter(|| Package::read_with_options(&data, Endian::Little, &eager).unwrap())
+    });
+    let fixture = include_bytes!("../fixtures/mixed-le.pak");
+    group.bench_function("fixture", |b| {
+        b.iter(|| Package::read_with_options(fixture, Endian::Little, &eager).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_read);
+criterion_main!(benches);
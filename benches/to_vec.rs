@@ -0,0 +1,84 @@
+//! Compares `Package::to_vec`, which allocates the final package size up front, with writing
+//! to a growing buffer. Allocation counts for each are printed before benchmarking.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::{Cursor, Write},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use binrw::Endian;
+use criterion::{criterion_group, criterion_main, Criterion};
+use retrotool::format::{
+    pack::{Package, PackageBuilder},
+    rfrm::FormDescriptor,
+    FourCC,
+};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) { System.dealloc(ptr, layout) }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Package of 512 stored 64 KiB assets.
+fn big_package() -> Package<'static> {
+    const K_FORM_TXTR: FourCC = FourCC(*b"TXTR");
+    let mut builder = PackageBuilder::new();
+    for i in 0..512u32 {
+        let mut cursor = Cursor::new(Vec::new());
+        FormDescriptor { size: 0, unk: 0, id: K_FORM_TXTR, version_a: 1, version_b: 0 }
+            .write(&mut cursor, Endian::Little, |w| {
+                w.write_all(&vec![i as u8; 0x10000])?;
+                Ok(())
+            })
+            .unwrap();
+        builder.add_asset(K_FORM_TXTR, cursor.into_inner()).with_name(format!("asset{}", i));
+    }
+    builder.build()
+}
+
+fn write_growing(package: &Package) -> Vec<u8> {
+    let mut cursor = Cursor::new(Vec::new());
+    package.write(&mut cursor, Endian::Little).unwrap();
+    cursor.into_inner()
+}
+
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let start = ALLOCATIONS.load(Ordering::Relaxed);
+    drop(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - start
+}
+
+fn bench_to_vec(c: &mut Criterion) {
+    let package = big_package();
+    assert_eq!(package.to_vec(Endian::Little).unwrap(), write_growing(&package));
+    println!(
+        "allocations: growing {}, to_vec {}",
+        allocations(|| write_growing(&package)),
+        allocations(|| package.to_vec(Endian::Little).unwrap())
+    );
+
+    let mut group = c.benchmark_group("write");
+    group.sample_size(20);
+    group.bench_function("growing", |b| b.iter(|| write_growing(&package)));
+    group.bench_function("to_vec", |b| b.iter(|| package.to_vec(Endian::Little).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_to_vec);
+criterion_main!(benches);
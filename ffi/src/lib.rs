@@ -0,0 +1,9 @@
+//! C and WebAssembly builds of the `retrotool` library, as a `cdylib` and `staticlib`.
+//!
+//! The functions are defined in `retrotool::ffi` and `retrotool::wasm`; this crate only links
+//! them into libraries, so the `retrotool` crate itself stays an `rlib` that builds without
+//! `std`.
+#[cfg(feature = "ffi")]
+pub use retrotool::ffi::*;
+#[cfg(feature = "wasm")]
+pub use retrotool::wasm::*;
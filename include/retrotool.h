@@ -0,0 +1,44 @@
+/* C API for reading packages, built as a library by the retrotool-ffi crate (ffi/). See src/ffi.rs. */
+#ifndef RETROTOOL_H
+#define RETROTOOL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Success */
+#define RETROTOOL_OK 0
+/* Null pointer argument */
+#define RETROTOOL_ERR_NULL (-1)
+/* Failed to read the package */
+#define RETROTOOL_ERR_READ (-2)
+/* Asset index out of range */
+#define RETROTOOL_ERR_INDEX (-3)
+
+/* Opaque package handle */
+typedef struct PackageHandle PackageHandle;
+
+/* Reads a little-endian package from `len` bytes at `data`. The data is copied, and can be
+ * freed once this returns. Returns NULL on failure. */
+PackageHandle *retrotool_package_open(const uint8_t *data, size_t len);
+
+/* Returns the number of assets in the package, or a negative error code. */
+ptrdiff_t retrotool_package_asset_count(const PackageHandle *handle);
+
+/* Retrieves the decompressed data of the asset at `index`, decompressing it on first access.
+ * The data remains valid until the package is closed. Returns RETROTOOL_OK or a negative
+ * error code. */
+int32_t retrotool_package_asset_data(const PackageHandle *handle, size_t index,
+                                     const uint8_t **out_ptr, size_t *out_len);
+
+/* Frees a package handle. NULL is ignored. */
+void retrotool_package_close(PackageHandle *handle);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RETROTOOL_H */
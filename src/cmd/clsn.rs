@@ -58,6 +58,7 @@ pub struct Vertices {
 
 #[binrw]
 #[derive(Clone, Debug)]
+#[allow(unused)]
 pub struct Materials {
     pub count: u32,
     #[br(count = count)]
@@ -66,6 +67,7 @@ pub struct Materials {
 
 #[binrw]
 #[derive(Clone, Debug)]
+#[allow(unused)]
 pub struct CCollisionMaterial {
     orientation: u32,
     material_type: u32,
@@ -94,6 +96,7 @@ pub struct Triangles {
 
 #[binrw]
 #[derive(Clone, Debug)]
+#[allow(unused)]
 pub struct AABoxTreeNode {
     bounds: CAABox,
     start: u32,
@@ -106,6 +109,7 @@ pub struct AABoxTreeNode {
 
 #[binrw]
 #[derive(Clone, Debug)]
+#[allow(unused)]
 pub struct OBBoxTreeNode {
     bounds: COBBox,
     start: u32,
@@ -118,6 +122,7 @@ pub struct OBBoxTreeNode {
 
 #[binrw]
 #[derive(Clone, Debug)]
+#[allow(unused)]
 pub struct AABoxCollisionTree {
     count: u32,
     #[br(count = count)]
@@ -126,6 +131,7 @@ pub struct AABoxCollisionTree {
 
 #[binrw]
 #[derive(Clone, Debug)]
+#[allow(unused)]
 pub struct OBBoxCollisionTree {
     count: u32,
     #[br(count = count)]
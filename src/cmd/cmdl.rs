@@ -836,7 +836,7 @@ impl EMaterialDataId {
 #[binrw]
 #[repr(u32)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-#[allow(clippy::upper_case_acronyms)]
+#[allow(clippy::upper_case_acronyms, unused)]
 pub enum EMaterialFlag {
     #[brw(magic(b"MFTR"))]
     MFTR = 0,
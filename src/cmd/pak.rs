@@ -1,29 +1,29 @@
 use std::{
-    borrow::Cow,
     fmt::Debug,
-    fs,
-    fs::File,
+    fs::{DirBuilder, File},
     io::{BufWriter, Cursor, Write},
     path::PathBuf,
+    str::FromStr,
 };
-use std::fs::DirBuilder;
 
-use anyhow::{bail, ensure, Context, Result};
+use anyhow::{Context, Result};
 use argh::FromArgs;
-use binrw::{BinReaderExt, BinWriterExt, Endian};
+use binrw::{BinReaderExt, Endian};
 
 use crate::{
+    cmd::txtr::decode as decode_texture,
     format::{
-        chunk::ChunkDescriptor,
-        pack::{Asset, AssetInfo, Package, K_CHUNK_AINF, K_CHUNK_META, K_CHUNK_NAME, K_FORM_FOOT},
+        pack::{AssetFilter, AssetOrdering, Package, WriteOptions},
         rfrm::FormDescriptor,
+        txtr::{STextureHeader, STextureMetaData, K_FORM_TXTR},
+        FourCC,
     },
-    util::file::map_file,
+    util::{dds::write_dds, file::map_file, png},
 };
 
 #[derive(FromArgs, PartialEq, Debug)]
 /// process PAK files
-#[argh(subcommand, name = "pak")]
+#[argh(subcommand, name = "pack")]
 pub struct Args {
     #[argh(subcommand)]
     command: SubCommand,
@@ -34,6 +34,7 @@ pub struct Args {
 enum SubCommand {
     Extract(ExtractArgs),
     Package(PackageArgs),
+    Textures(TexturesArgs),
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
@@ -46,6 +47,12 @@ pub struct ExtractArgs {
     #[argh(positional)]
     /// output directory
     output: PathBuf,
+    #[argh(option, long = "type")]
+    /// only extract assets of this type
+    kind: Option<FourCC>,
+    #[argh(option)]
+    /// only extract assets with names matching this pattern (* and ? as wildcards)
+    name: Option<String>,
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
@@ -60,17 +67,55 @@ pub struct PackageArgs {
     output: PathBuf,
 }
 
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// extract textures from a PAK file
+#[argh(subcommand, name = "textures")]
+pub struct TexturesArgs {
+    #[argh(positional)]
+    /// input file
+    input: PathBuf,
+    #[argh(positional)]
+    /// output directory
+    output: PathBuf,
+    #[argh(option, default = "ImageFormat::Png")]
+    /// output format (png, dds)
+    format: ImageFormat,
+    #[argh(switch)]
+    /// write all mip levels
+    mips: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum ImageFormat {
+    Png,
+    Dds,
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(Self::Png),
+            "dds" => Ok(Self::Dds),
+            _ => Err(format!("Invalid image format '{s}'")),
+        }
+    }
+}
+
 pub fn run(args: Args) -> Result<()> {
     match args.command {
         SubCommand::Extract(c_args) => extract(c_args),
         SubCommand::Package(c_args) => package(c_args),
+        SubCommand::Textures(c_args) => textures(c_args),
     }
 }
 
 fn extract(args: ExtractArgs) -> Result<()> {
     let data = map_file(args.input)?;
     let package = Package::read(&data, Endian::Little)?;
-    for asset in &package.assets {
+    let filter = AssetFilter { kind: args.kind, name: args.name, ..Default::default() };
+    for asset in package.select(&filter) {
         let name = asset
             .name
             .as_ref()
@@ -80,124 +125,273 @@ fn extract(args: ExtractArgs) -> Result<()> {
             "Asset {} {} size {:#X} (compressed {}, meta size {:#X})",
             asset.kind,
             name,
-            asset.data.len(),
+            asset.decompressed_size(),
             asset.info.compression_mode != 0,
-            asset.meta.as_ref().map(|m| m.len()).unwrap_or_default()
-        );
-        let file_name = asset
-            .name
-            .as_ref()
-            .map(|name| format!("{}.{}", name, asset.kind))
-            .unwrap_or_else(|| format!("{}.{}", asset.id, asset.kind));
-        let path = args.output.join(&file_name);
-        if let Some(parent) = path.parent() {
-            DirBuilder::new().recursive(true).create(parent)?;
-        }
-
-        let mut file = BufWriter::new(
-            File::create(&path)
-                .with_context(|| format!("Failed to create file '{}'", path.display()))?,
+            asset.meta_bytes().map_or(0, |m| m.len())
         );
-        file.write_all(&asset.data)?;
-
-        // Write custom footer
-        FormDescriptor { size: 0, unk: 0, id: K_FORM_FOOT, version_a: 1, version_b: 1 }.write(
-            &mut file,
-            Endian::Little,
-            |w| {
-                ChunkDescriptor { id: K_CHUNK_AINF, size: 0, unk: 0, skip: 0 }.write(
-                    w,
-                    Endian::Little,
-                    |w| {
-                        w.write_le(&asset.info)?;
-                        Ok(())
-                    },
-                )?;
-                if let Some(meta) = &asset.meta {
-                    let meta_chunk = ChunkDescriptor {
-                        id: K_CHUNK_META,
-                        size: meta.len() as u64,
-                        unk: 0,
-                        skip: 0,
-                    };
-                    w.write_le(&meta_chunk)?;
-                    w.write_all(meta)?;
-                }
-                if let Some(name) = &asset.name {
-                    let bytes = name.as_bytes();
-                    let name_chunk = ChunkDescriptor {
-                        id: K_CHUNK_NAME,
-                        size: bytes.len() as u64,
-                        unk: 0,
-                        skip: 0,
-                    };
-                    w.write_le(&name_chunk)?;
-                    w.write_all(bytes)?;
-                }
-                Ok(())
-            },
-        )?;
-        file.flush()?;
     }
+    let paths = package.extract_selected_to_dir(&args.output, &filter)?;
+    log::info!("Extracted {} assets", paths.len());
     Ok(())
 }
 
 fn package(args: PackageArgs) -> Result<()> {
-    let files = fs::read_dir(&args.input)?;
-    let mut package = Package { assets: vec![] };
-    for result in files {
-        let entry = match result {
-            Ok(e) => e,
-            Err(e) => bail!("Failed to read directory entry: {:?}", e),
+    let package = Package::from_dir(&args.input, Endian::Little)?;
+    log::info!("Loaded {} assets", package.assets.len());
+    let mut file =
+        BufWriter::new(File::create(&args.output).with_context(|| {
+            format!("Failed to create output file '{}'", args.output.display())
+        })?);
+    // Keep the data layout recovered from extracted asset footers
+    let options = WriteOptions { ordering: AssetOrdering::PreserveOriginal, ..Default::default() };
+    package.write_with_options(&mut file, Endian::Little, &options)?;
+    file.flush()?;
+    Ok(())
+}
+
+fn textures(args: TexturesArgs) -> Result<()> {
+    let data = map_file(args.input)?;
+    let package = Package::read(&data, Endian::Little)?;
+    for asset in package.assets.iter().filter(|a| a.kind == K_FORM_TXTR) {
+        let Some(meta) = &asset.meta else {
+            log::warn!("Texture {} has no metadata, skipping", asset.id);
+            continue;
         };
+        let meta: STextureMetaData = Cursor::new(meta).read_type(Endian::Little)?;
+        let asset_data = asset.data()?;
+        let (_, txtr_data, _) = FormDescriptor::slice(asset_data, Endian::Little)?;
+        let (head, texture) = decode_texture(asset_data, txtr_data, &meta)
+            .with_context(|| format!("Failed to decode texture {}", asset.id))?;
 
-        let path = entry.path();
-        log::info!("Processing {}", path.display());
-        let data = map_file(&path)?;
-        let (form, _, remain) = FormDescriptor::slice(&data, Endian::Little)?;
-        // log::info!("Found type {} version {}, {}", form.id, form.version, form.other_version);
-        let (foot, mut foot_data, _) = FormDescriptor::slice(remain, Endian::Little)?;
-        ensure!(foot.id == K_FORM_FOOT);
-        ensure!(foot.version_a == 1);
-        let mut ainfo: Option<AssetInfo> = None;
-        let mut meta: Option<&[u8]> = None;
-        let mut name: Option<String> = None;
-        while !foot_data.is_empty() {
-            let (chunk, chunk_data, remain) = ChunkDescriptor::slice(foot_data, Endian::Little)?;
-            match chunk.id {
-                K_CHUNK_AINF => {
-                    ainfo = Some(Cursor::new(chunk_data).read_type(Endian::Little)?);
+        let name = asset.file_stem();
+        let base_path = args.output.join(&name);
+        if let Some(parent) = base_path.parent() {
+            DirBuilder::new().recursive(true).create(parent)?;
+        }
+        match args.format {
+            ImageFormat::Png => {
+                if !png::supports_format(head.format) {
+                    log::warn!(
+                        "Texture {} format {:?} unsupported for PNG, skipping",
+                        name,
+                        head.format
+                    );
+                    continue;
                 }
-                K_CHUNK_META => {
-                    meta = Some(chunk_data);
+                if head.layers != 1 {
+                    log::warn!("Texture {} has {} layers, skipping", name, head.layers);
+                    continue;
                 }
-                K_CHUNK_NAME => {
-                    name = Some(String::from_utf8(chunk_data.to_vec())?);
+                let levels = if args.mips { head.mip_sizes.len() as u32 } else { 1 };
+                for level in 0..levels {
+                    let path = if level == 0 {
+                        args.output.join(format!("{name}.png"))
+                    } else {
+                        args.output.join(format!("{name}_mip{level}.png"))
+                    };
+                    let mut file =
+                        BufWriter::new(File::create(&path).with_context(|| {
+                            format!("Failed to create file '{}'", path.display())
+                        })?);
+                    log::info!("Writing {}", path.display());
+                    png::write_png(&mut file, &head, &texture, level)?;
+                    file.flush()?;
                 }
-                _ => {}
             }
-            foot_data = remain;
+            ImageFormat::Dds => {
+                let (head, texture) =
+                    if args.mips { (head, texture) } else { first_mips(head, &texture) };
+                let path = args.output.join(format!("{name}.dds"));
+                let mut file = BufWriter::new(
+                    File::create(&path)
+                        .with_context(|| format!("Failed to create file '{}'", path.display()))?,
+                );
+                log::info!("Writing {}", path.display());
+                write_dds(&mut file, &head, texture)?;
+                file.flush()?;
+            }
         }
-        let Some(ainfo) = ainfo else {
-            bail!("Failed to locate asset info footer");
-        };
-        package.assets.push(Asset {
-            id: ainfo.id,
-            kind: form.id,
-            name,
-            data: Cow::Owned(data[..data.len() - remain.len()].to_vec()),
-            meta: meta.map(|data| Cow::Owned(data.to_vec())),
-            info: ainfo,
-            version: form.version_a,
-            other_version: form.version_b,
-        });
     }
-    package.assets.sort_by_key(|a| a.id);
-    let mut file =
-        BufWriter::new(File::create(&args.output).with_context(|| {
-            format!("Failed to create output file '{}'", args.output.display())
-        })?);
-    package.write(&mut file, Endian::Little)?;
-    file.flush()?;
     Ok(())
 }
+
+/// Strips all but the first mip level of each layer from deswizzled texture data.
+fn first_mips(mut head: STextureHeader, data: &[u8]) -> (STextureHeader, Vec<u8>) {
+    let layer_size: usize = (0..head.mip_sizes.len() as u32).map(|l| head.mip_size(l)).sum();
+    let mip_size = head.mip_size(0);
+    let mut out = Vec::with_capacity(mip_size * head.layers as usize);
+    for layer in data.chunks(layer_size.max(1)).take(head.layers as usize) {
+        out.extend_from_slice(&layer[..mip_size.min(layer.len())]);
+    }
+    head.mip_sizes.truncate(1);
+    (head, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, num::NonZeroUsize};
+
+    use binrw::BinWriterExt;
+    use retrotool::format::{
+        chunk::ChunkDescriptor,
+        pack::PackageBuilder,
+        txtr::{
+            ETextureAnisotropicRatio, ETextureFilter, ETextureFormat, ETextureMipFilter,
+            ETextureType, ETextureWrap, STextureCompressedBufferInfo, STextureReadInfo,
+            STextureSamplerData,
+        },
+    };
+    use tegra_swizzle::surface::BlockDim;
+
+    use super::*;
+    use crate::cmd::txtr::{K_CHUNK_GPU, K_CHUNK_HEAD};
+
+    /// TXTR asset data and metadata for a single-mip 2D texture, given its linear data.
+    fn texture_asset(
+        format: ETextureFormat,
+        width: u32,
+        height: u32,
+        linear: &[u8],
+    ) -> (Vec<u8>, Vec<u8>) {
+        let (bw, bh, _) = format.block_size();
+        let block_dim = BlockDim {
+            width: NonZeroUsize::new(bw as usize).unwrap(),
+            height: NonZeroUsize::new(bh as usize).unwrap(),
+            depth: NonZeroUsize::new(1).unwrap(),
+        };
+        let bpp = format.bytes_per_pixel() as usize;
+        let swizzled = tegra_swizzle::surface::swizzle_surface(
+            width as usize,
+            height as usize,
+            1,
+            linear,
+            block_dim,
+            None,
+            bpp,
+            1,
+            1,
+        )
+        .unwrap();
+        // Stored with a mode 0 compression header
+        let buffer = [&[0u8; 4][..], &swizzled].concat();
+        let head = STextureHeader {
+            kind: ETextureType::_2D,
+            format,
+            width,
+            height,
+            layers: 1,
+            tile_mode: 0,
+            swizzle: 0,
+            mip_sizes: vec![linear.len() as u32],
+            sampler_data: STextureSamplerData {
+                unk: 0,
+                filter: ETextureFilter::Linear,
+                mip_filter: ETextureMipFilter::Linear,
+                wrap_x: ETextureWrap::Repeat,
+                wrap_y: ETextureWrap::Repeat,
+                wrap_z: ETextureWrap::Repeat,
+                aniso: ETextureAnisotropicRatio::None,
+            },
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        FormDescriptor { size: 0, unk: 0, id: K_FORM_TXTR, version_a: 1, version_b: 0 }
+            .write(&mut cursor, Endian::Little, |w| {
+                ChunkDescriptor { id: K_CHUNK_HEAD, size: 0, unk: 0, skip: 0 }.write(
+                    w,
+                    Endian::Little,
+                    |w| Ok(w.write_type(&head, Endian::Little)?),
+                )?;
+                ChunkDescriptor { id: K_CHUNK_GPU, size: 0, unk: 0, skip: 0 }.write(
+                    w,
+                    Endian::Little,
+                    |w| Ok(w.write_all(&buffer)?),
+                )
+            })
+            .unwrap();
+        let data = cursor.into_inner();
+        let meta = STextureMetaData {
+            unk1: 0,
+            unk2: 0,
+            alloc_category: 0,
+            gpu_offset: 0,
+            align: 0,
+            decompressed_size: swizzled.len() as u32,
+            info: vec![STextureReadInfo {
+                index: 0,
+                offset: (data.len() - buffer.len()) as u32,
+                size: buffer.len() as u32,
+            }],
+            buffers: vec![STextureCompressedBufferInfo {
+                index: 0,
+                offset: 0,
+                size: buffer.len() as u32,
+                dest_offset: 0,
+                dest_size: swizzled.len() as u32,
+            }],
+        };
+        let mut meta_data = Cursor::new(Vec::new());
+        meta_data.write_type(&meta, Endian::Little).unwrap();
+        (data, meta_data.into_inner())
+    }
+
+    #[test]
+    fn textures_writes_png_per_txtr() {
+        let dir = std::env::temp_dir().join(format!("retrotool-textures-{}", std::process::id()));
+        let input = dir.join("textures.pak");
+        let output = dir.join("out");
+        fs::create_dir_all(&dir).unwrap();
+
+        let rgba: Vec<u8> = (0..8 * 4 * 4).map(|i| i as u8).collect();
+        // One opaque white 4x4 BC1 block, repeated
+        let bc1 = [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0].repeat(4 * 2);
+        let cases = [
+            ("rgba", ETextureFormat::Rgba8Unorm, 8, 4, rgba.clone()),
+            ("../escape/bc1", ETextureFormat::RgbaBc1Unorm, 16, 8, bc1),
+        ];
+        let mut builder = PackageBuilder::new();
+        for (name, format, width, height, linear) in &cases {
+            let (data, meta) = texture_asset(*format, *width, *height, linear);
+            builder.add_asset(K_FORM_TXTR, data).with_name(*name).with_meta(meta);
+        }
+        let mut model = Cursor::new(Vec::new());
+        FormDescriptor { size: 0, unk: 0, id: FourCC(*b"CMDL"), version_a: 1, version_b: 0 }
+            .write(&mut model, Endian::Little, |_| Ok(()))
+            .unwrap();
+        builder.add_asset(FourCC(*b"CMDL"), model.into_inner());
+        fs::write(&input, builder.build().to_vec(Endian::Little).unwrap()).unwrap();
+
+        let args =
+            TexturesArgs { input, output: output.clone(), format: ImageFormat::Png, mips: false };
+        let result = textures(args);
+        let mut written = vec![];
+        for (name, _, width, height, _) in &cases {
+            let path = output.join(format!("{}.png", name.replace("..", "_")));
+            let decoder = ::png::Decoder::new(File::open(&path).unwrap());
+            let mut reader = decoder.read_info().unwrap();
+            let mut image = vec![0u8; reader.output_buffer_size()];
+            reader.next_frame(&mut image).unwrap();
+            assert_eq!((reader.info().width, reader.info().height), (*width, *height));
+            written.push(image);
+        }
+        let count = walk_files(&output);
+        fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        assert_eq!(count, cases.len());
+        assert_eq!(written[0], rgba);
+        assert!(written[1].iter().all(|&b| b == 0xFF));
+    }
+
+    fn walk_files(dir: &std::path::Path) -> usize {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    walk_files(&path)
+                } else {
+                    1
+                }
+            })
+            .sum()
+    }
+}
@@ -13,14 +13,12 @@ use crate::{
         chunk::ChunkDescriptor,
         pack::{K_CHUNK_META, K_FORM_FOOT},
         rfrm::FormDescriptor,
-        txtr::{deswizzle, STextureHeader, STextureMetaData},
+        txtr::{deswizzle, STextureHeader, STextureMetaData, K_FORM_TXTR},
         FourCC,
     },
     util::{astc::write_astc, dds::write_dds, file::map_file, lzss::decompress_into},
 };
 
-// Texture
-pub const K_FORM_TXTR: FourCC = FourCC(*b"TXTR");
 // Texture header
 pub const K_CHUNK_HEAD: FourCC = FourCC(*b"HEAD");
 // GPU data
@@ -85,6 +83,39 @@ fn convert(args: ConvertArgs) -> Result<()> {
         bail!("Failed to locate meta chunk");
     };
 
+    let (head, deswizzled) = decode(&data, txtr_data, &meta)?;
+    let path = if args.astc {
+        if !head.format.is_astc() {
+            bail!("Expected ASTC format, got {:?}", head.format);
+        }
+        args.input.with_extension("astc")
+    } else {
+        args.input.with_extension("dds")
+    };
+    let mut file = BufWriter::new(
+        File::create(&path)
+            .with_context(|| format!("Failed to create output file '{}'", path.display()))?,
+    );
+    log::info!("Writing {}", path.display());
+    if args.astc {
+        write_astc(&mut file, &head, &deswizzled)?;
+    } else {
+        write_dds(&mut file, &head, deswizzled)?;
+    }
+    file.flush()?;
+
+    Ok(())
+}
+
+/// Decodes a TXTR asset, returning the texture header and deswizzled texture data.
+///
+/// `data` is the full asset data, which the metadata's read offsets are relative to,
+/// and `txtr_data` is the contents of its TXTR form.
+pub fn decode(
+    data: &[u8],
+    txtr_data: &[u8],
+    meta: &STextureMetaData,
+) -> Result<(STextureHeader, Vec<u8>)> {
     let (head_desc, head_data, remain) = ChunkDescriptor::slice(txtr_data, Endian::Little)?;
     ensure!(head_desc.id == K_CHUNK_HEAD);
     let head: STextureHeader = Cursor::new(head_data).read_type(Endian::Little)?;
@@ -117,25 +148,5 @@ fn convert(args: ConvertArgs) -> Result<()> {
     }
 
     let deswizzled = deswizzle(&head, &buffer)?;
-    let path = if args.astc {
-        if !head.format.is_astc() {
-            bail!("Expected ASTC format, got {:?}", head.format);
-        }
-        args.input.with_extension("astc")
-    } else {
-        args.input.with_extension("dds")
-    };
-    let mut file = BufWriter::new(
-        File::create(&path)
-            .with_context(|| format!("Failed to create output file '{}'", path.display()))?,
-    );
-    log::info!("Writing {}", path.display());
-    if args.astc {
-        write_astc(&mut file, &head, &deswizzled)?;
-    } else {
-        write_dds(&mut file, &head, deswizzled)?;
-    }
-    file.flush()?;
-
-    Ok(())
+    Ok((head, deswizzled))
 }
@@ -0,0 +1,147 @@
+//! C API for reading packages.
+//!
+//! Functions returning `i32` or `isize` return a negative error code on failure.
+use std::{ptr, slice};
+
+use binrw::Endian;
+
+use crate::format::pack::Package;
+
+// Success
+pub const RETROTOOL_OK: i32 = 0;
+// Null pointer argument
+pub const RETROTOOL_ERR_NULL: i32 = -1;
+// Failed to read the package
+pub const RETROTOOL_ERR_READ: i32 = -2;
+// Asset index out of range
+pub const RETROTOOL_ERR_INDEX: i32 = -3;
+
+/// Opaque package handle
+pub struct PackageHandle {
+    package: Package<'static>,
+}
+
+/// Reads a little-endian package from `len` bytes at `data`. The data is copied, and can be
+/// freed once this returns. Returns null on failure.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retrotool_package_open(data: *const u8, len: usize) -> *mut PackageHandle {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+    let data = slice::from_raw_parts(data, len);
+    match Package::read(data, Endian::Little) {
+        Ok(package) => Box::into_raw(Box::new(PackageHandle { package: package.into_owned() })),
+        Err(e) => {
+            log::error!("Failed to read package: {:?}", e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the number of assets in the package.
+///
+/// # Safety
+/// `handle` must be null or a handle returned by [`retrotool_package_open`].
+#[no_mangle]
+pub unsafe extern "C" fn retrotool_package_asset_count(handle: *const PackageHandle) -> isize {
+    match handle.as_ref() {
+        Some(handle) => handle.package.assets.len() as isize,
+        None => RETROTOOL_ERR_NULL as isize,
+    }
+}
+
+/// Retrieves the decompressed data of the asset at `index`, decompressing it on first access.
+/// The data remains valid until the package is closed. Returns [`RETROTOOL_ERR_READ`] if the
+/// asset fails to decompress.
+///
+/// # Safety
+/// `handle` must be null or a handle returned by [`retrotool_package_open`], and `out_ptr`
+/// and `out_len` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn retrotool_package_asset_data(
+    handle: *const PackageHandle,
+    index: usize,
+    out_ptr: *mut *const u8,
+    out_len: *mut usize,
+) -> i32 {
+    let Some(handle) = handle.as_ref() else {
+        return RETROTOOL_ERR_NULL;
+    };
+    if out_ptr.is_null() || out_len.is_null() {
+        return RETROTOOL_ERR_NULL;
+    }
+    let Some(asset) = handle.package.assets.get(index) else {
+        return RETROTOOL_ERR_INDEX;
+    };
+    let Ok(data) = asset.data() else {
+        return RETROTOOL_ERR_READ;
+    };
+    *out_ptr = data.as_ptr();
+    *out_len = data.len();
+    RETROTOOL_OK
+}
+
+/// Frees a package handle.
+///
+/// # Safety
+/// `handle` must be null or a handle returned by [`retrotool_package_open`] that wasn't
+/// already closed.
+#[no_mangle]
+pub unsafe extern "C" fn retrotool_package_close(handle: *mut PackageHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use super::*;
+    use crate::format::{pack::PackageBuilder, rfrm::FormDescriptor, FourCC};
+
+    #[test]
+    fn read_package_through_c_api() {
+        let mut builder = PackageBuilder::new();
+        for i in 0..4u8 {
+            let mut cursor = Cursor::new(Vec::new());
+            FormDescriptor { size: 0, unk: 0, id: FourCC(*b"TXTR"), version_a: 1, version_b: 0 }
+                .write(&mut cursor, Endian::Little, |w| {
+                    w.write_all(&[i; 256])?;
+                    Ok(())
+                })
+                .unwrap();
+            builder.add_asset(FourCC(*b"TXTR"), cursor.into_inner()).with_compression(2);
+        }
+        let package = builder.build();
+        let data = package.to_vec(Endian::Little).unwrap();
+        unsafe {
+            let handle = retrotool_package_open(data.as_ptr(), data.len());
+            assert!(!handle.is_null());
+            assert_eq!(retrotool_package_asset_count(handle), 4);
+            for (index, asset) in package.assets.iter().enumerate() {
+                let (mut ptr, mut len) = (ptr::null(), 0);
+                assert_eq!(retrotool_package_asset_data(handle, index, &mut ptr, &mut len), 0);
+                assert_eq!(slice::from_raw_parts(ptr, len), asset.data().unwrap());
+            }
+            let (mut ptr, mut len) = (ptr::null(), 0);
+            assert_eq!(
+                retrotool_package_asset_data(handle, 4, &mut ptr, &mut len),
+                RETROTOOL_ERR_INDEX
+            );
+            assert_eq!(
+                retrotool_package_asset_data(handle, 0, ptr::null_mut(), &mut len),
+                RETROTOOL_ERR_NULL
+            );
+            retrotool_package_close(handle);
+
+            assert!(retrotool_package_open(data.as_ptr(), 8).is_null());
+            assert!(retrotool_package_open(ptr::null(), 0).is_null());
+            assert_eq!(retrotool_package_asset_count(ptr::null()), RETROTOOL_ERR_NULL as isize);
+            retrotool_package_close(ptr::null_mut());
+        }
+    }
+}
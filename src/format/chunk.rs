@@ -1,8 +1,18 @@
-use std::io::{Read, Seek, SeekFrom, Write};
+#[cfg(feature = "std")]
+use std::io::Write;
 
+#[cfg(feature = "std")]
 use anyhow::Result;
-use binrw::{binrw, io::Cursor, BinReaderExt, BinResult, BinWriterExt, Endian};
+#[cfg(feature = "std")]
+use binrw::BinWriterExt;
+use binrw::{
+    binrw,
+    io::{Cursor, Read, Seek, SeekFrom},
+    BinReaderExt, BinResult, Endian,
+};
 
+#[cfg(feature = "std")]
+use crate::format::pack::PackError;
 use crate::format::FourCC;
 
 #[binrw]
@@ -22,16 +32,23 @@ impl ChunkDescriptor {
         Ok(desc)
     }
 
+    /// Slices the chunk payload, returning the header, payload and remaining data.
+    ///
+    /// The payload starts `skip` bytes after the header, and is `size` bytes long. The next
+    /// chunk immediately follows the payload, so any padding between chunks must be declared
+    /// with `skip`.
     #[inline]
     pub fn slice(data: &[u8], e: Endian) -> BinResult<(Self, &[u8], &[u8])> {
         let mut reader = Cursor::new(data);
         let header = Self::read(&mut reader, e)?;
         let start = reader.position();
-        let slice = &data[start as usize..(start + header.size) as usize];
-        let remain = &data[(start + header.size) as usize..];
+        let end = check_bounds(data, start, header.size, header.id)?;
+        let slice = &data[start as usize..end];
+        let remain = &data[end..];
         Ok((header, slice, remain))
     }
 
+    #[cfg(feature = "std")]
     pub fn write<W, CB>(&mut self, w: &mut W, e: Endian, mut cb: CB) -> Result<()>
     where
         W: Write + Seek,
@@ -39,6 +56,7 @@ impl ChunkDescriptor {
     {
         let form_pos = w.stream_position()?;
         w.write_type(self, e)?;
+        w.write_all(&vec![0u8; self.skip as usize])?;
         let data_pos = w.stream_position()?;
         cb(w)?;
         let end_pos = w.stream_position()?;
@@ -49,3 +67,24 @@ impl ChunkDescriptor {
         Ok(())
     }
 }
+
+/// Checks that `size` bytes at `start` are within `data`, returning the end offset.
+pub fn check_bounds(data: &[u8], start: u64, size: u64, id: FourCC) -> BinResult<usize> {
+    match start.checked_add(size) {
+        Some(end) if end <= data.len() as u64 => Ok(end as usize),
+        _ => {
+            let remaining = (data.len() as u64).saturating_sub(start);
+            #[cfg(feature = "std")]
+            let err = Box::new(PackError::ChunkOutOfBounds { id, size, remaining });
+            #[cfg(not(feature = "std"))]
+            let err = alloc::boxed::Box::new(alloc::format!(
+                "{id} size {size:#X} exceeds remaining data size {remaining:#X}"
+            ));
+            Err(binrw::Error::Custom { pos: start, err })
+        }
+    }
+}
+
+/// Whether the remaining data in a form is zero padding rather than another chunk.
+#[inline]
+pub fn is_padding(data: &[u8]) -> bool { data.iter().all(|&b| b == 0) }
@@ -1,11 +1,19 @@
 pub mod chunk;
+#[cfg(feature = "std")]
 pub mod pack;
 pub mod rfrm;
+pub mod toc;
+#[cfg(feature = "std")]
 pub mod txtr;
 
-use std::fmt::{Debug, Display, Formatter, Write};
+use alloc::{format, string::String, vec::Vec};
+use core::{
+    fmt::{Debug, Display, Formatter, Write},
+    str::FromStr,
+};
 
-use binrw::binrw;
+use binrw::{binrw, BinReaderExt, BinResult, BinWriterExt, Endian};
+use uuid::Uuid;
 
 use crate::array_ref;
 
@@ -26,25 +34,88 @@ impl FourCC {
             | ((self.0[2] as u32) << 8)
             | (self.0[3] as u32)
     }
+
+    /// Whether all bytes are printable ASCII. Null or non-ASCII bytes usually indicate a read
+    /// from a bad offset.
+    #[inline]
+    pub fn is_printable(&self) -> bool { self.0.iter().all(|&c| c == b' ' || c.is_ascii_graphic()) }
+
+    /// Renders the four bytes for use in a file name. ASCII letters, digits, `_` and `-` are
+    /// kept, and any other byte (including spaces and non-ASCII bytes) is written as `%NN`.
+    pub fn to_file_token(&self) -> String {
+        let mut token = String::with_capacity(4);
+        for c in self.0 {
+            if c.is_ascii_alphanumeric() || c == b'_' || c == b'-' {
+                token.push(c as char);
+            } else {
+                write!(token, "%{c:02X}").unwrap();
+            }
+        }
+        token
+    }
 }
 
+/// Writes the four bytes as ASCII, escaping non-printable bytes and `\` as `\xNN`.
+/// [`FourCC::from_str`] parses the result back.
 impl Display for FourCC {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         for c in self.0 {
-            f.write_char(c as char)?;
+            if (c == b' ' || c.is_ascii_graphic()) && c != b'\\' {
+                f.write_char(c as char)?;
+            } else {
+                write!(f, "\\x{c:02X}")?;
+            }
         }
         Ok(())
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for FourCC {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FourCC {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl Debug for FourCC {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_char('"')?;
-        for c in self.0 {
-            f.write_char(c as char)?;
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result { write!(f, "\"{self}\"") }
+}
+
+/// Parses four ASCII characters or `\xNN` escapes, as written by [`Display`].
+impl FromStr for FourCC {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error =
+            || format!("Invalid FourCC '{s}', expected 4 ASCII characters or \\xNN escapes");
+        let mut bytes = Vec::with_capacity(4);
+        let mut rest = s.as_bytes();
+        while let Some((&c, next)) = rest.split_first() {
+            if !c.is_ascii() {
+                return Err(error());
+            }
+            if c == b'\\' {
+                let hex = next.strip_prefix(b"x").and_then(|hex| hex.get(..2)).ok_or_else(error)?;
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return Err(error());
+                }
+                // Two ASCII hex digits are valid UTF-8 and fit a byte.
+                bytes.push(u8::from_str_radix(core::str::from_utf8(hex).unwrap(), 16).unwrap());
+                rest = &next[3..];
+            } else {
+                bytes.push(c);
+                rest = next;
+            }
         }
-        f.write_char('"')?;
-        Ok(())
+        <[u8; 4]>::try_from(bytes).map(Self).map_err(|_| error())
     }
 }
 
@@ -54,3 +125,52 @@ impl PartialEq<[u8; 4]> for FourCC {
 
 #[inline]
 pub fn peek_four_cc(data: &[u8]) -> FourCC { FourCC(*array_ref!(data, 0, 4)) }
+
+/// Reads a UUID in the byte order of the file.
+///
+/// Little-endian files store the first three UUID fields little-endian, which is the
+/// convention used when displaying asset IDs. For big-endian files, the fields are
+/// byte-swapped, so the same asset reads as the same UUID from either.
+#[binrw::parser(reader, endian)]
+pub fn parse_uuid() -> BinResult<Uuid> {
+    let bytes: [u8; 16] = reader.read_type(endian)?;
+    Ok(match endian {
+        Endian::Little => Uuid::from_bytes_le(bytes),
+        Endian::Big => Uuid::from_bytes(bytes),
+    })
+}
+
+/// Writes a UUID in the byte order of the file. See [`parse_uuid`].
+#[binrw::writer(writer, endian)]
+pub fn write_uuid(uuid: &Uuid) -> BinResult<()> {
+    let bytes = match endian {
+        Endian::Little => uuid.to_bytes_le(),
+        Endian::Big => uuid.into_bytes(),
+    };
+    writer.write_type(&bytes, endian)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fourcc_display_parses_back() {
+        for bytes in [*b"TXTR", [0, b'A', 0xFF, b' '], *b"\\x41", [0x7F, b'\\', b'x', 0x0A]] {
+            let fourcc = FourCC(bytes);
+            assert_eq!(fourcc.to_string().parse::<FourCC>(), Ok(fourcc));
+        }
+        assert_eq!("\\x00AB\\x7f".parse::<FourCC>(), Ok(FourCC([0, b'A', b'B', 0x7F])));
+        for invalid in ["TXT", "TXTRS", "TX\\x4", "TX\\y41", "TX\\x+1", "TXT\u{e9}", "\\x41\\"] {
+            assert!(invalid.parse::<FourCC>().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn fourcc_serde_round_trip() {
+        let fourcc = FourCC([0, b'A', 0xFF, b'\\']);
+        let json = serde_json::to_string(&fourcc).unwrap();
+        assert_eq!(serde_json::from_str::<FourCC>(&json).unwrap(), fourcc);
+    }
+}
@@ -1,18 +1,68 @@
 use std::{
     borrow::Cow,
-    collections::HashMap,
-    io::{Cursor, Seek, SeekFrom, Write},
+    collections::{hash_map, HashMap, HashSet},
+    fs,
+    fs::{DirBuilder, File},
+    io::{BufWriter, Cursor, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
 };
 
-use anyhow::{bail, ensure, Result};
+use anyhow::{bail, ensure, Context, Result};
 use binrw::{binrw, BinReaderExt, BinWriterExt, Endian};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
+#[cfg(feature = "mmap")]
+pub use self::mapped::MappedPackage;
+pub use self::{
+    builder::{AssetBuilder, PackageBuilder},
+    diff::{AssetChange, AssetField, PackageDiff},
+    error::PackError,
+    journal::{MergePolicy, PackageEdit},
+    layout::{LayoutNode, PackageLayout},
+    lazy::{AssetHeader, EditablePackage, LazyPackage},
+    manifest::{ManifestDiff, ManifestEntry, PackageManifest, K_CHUNK_MENT, K_FORM_MANI},
+    merkle::{MerkleProof, MerkleSibling},
+    metadata::{Metadata, MetadataReference},
+    writer::PackageWriter,
+};
+pub use crate::format::toc::{
+    AssetDirectory, AssetDirectoryEntry, MetadataTable, MetadataTableEntry, NameEncoding,
+    StringTable, StringTableEntry,
+};
 use crate::{
-    format::{chunk::ChunkDescriptor, rfrm::FormDescriptor, FourCC},
-    util::lzss::decompress_buffer,
+    format::{
+        chunk::{is_padding, ChunkDescriptor},
+        parse_uuid, peek_four_cc,
+        rfrm::{FormDescriptor, K_CHUNK_RFRM},
+        write_uuid, FourCC,
+    },
+    util::{
+        glob,
+        lzss::{
+            compress_buffer, decompress_buffer, decompress_mode, group_len, is_supported_mode,
+            max_decompressed_size,
+        },
+    },
 };
 
+mod builder;
+mod deps;
+mod diff;
+mod error;
+mod index;
+mod journal;
+mod layout;
+mod lazy;
+mod manifest;
+#[cfg(feature = "mmap")]
+mod mapped;
+mod merkle;
+mod metadata;
+mod stream;
+mod writer;
+
 // Package file
 pub const K_FORM_PACK: FourCC = FourCC(*b"PACK");
 // Table of contents
@@ -24,6 +74,11 @@ pub const K_CHUNK_STRG: FourCC = FourCC(*b"STRG");
 // Asset directory
 pub const K_CHUNK_ADIR: FourCC = FourCC(*b"ADIR");
 
+// Size of a serialized ChunkDescriptor
+const CHUNK_DESCRIPTOR_SIZE: usize = 24;
+// Size of a serialized FormDescriptor
+const FORM_DESCRIPTOR_SIZE: usize = 32;
+
 // Custom footer for extracted files
 pub const K_FORM_FOOT: FourCC = FourCC(*b"FOOT");
 // Custom footer asset information
@@ -31,84 +86,67 @@ pub const K_CHUNK_AINF: FourCC = FourCC(*b"AINF");
 // Custom footer asset name
 pub const K_CHUNK_NAME: FourCC = FourCC(*b"NAME");
 
-/// PACK::TOCC::ADIR chunk
-#[binrw]
-#[derive(Clone, Debug, Default)]
-pub struct AssetDirectory {
-    #[bw(try_calc = entries.len().try_into())]
-    pub entry_count: u32,
-    #[br(count = entry_count)]
-    pub entries: Vec<AssetDirectoryEntry>,
-}
-
-/// PACK::TOCC::ADIR chunk entry
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct AssetDirectoryEntry {
-    pub asset_type: FourCC,
-    #[br(map = Uuid::from_bytes_le)]
-    #[bw(map = Uuid::to_bytes_le)]
-    pub asset_id: Uuid,
-    pub version: u32,
-    pub other_version: u32,
-    pub offset: u64,
-    pub decompressed_size: u64,
-    pub size: u64,
-}
-
-/// PACK::TOCC::META chunk
-#[binrw]
-#[derive(Clone, Debug, Default)]
-pub struct MetadataTable {
-    #[bw(try_calc = entries.len().try_into())]
-    pub entry_count: u32,
-    #[br(count = entry_count)]
-    pub entries: Vec<MetadataTableEntry>,
-}
+// Custom package index
+pub const K_FORM_PIDX: FourCC = FourCC(*b"PIDX");
+// Custom package index TOCC digest
+pub const K_CHUNK_DGST: FourCC = FourCC(*b"DGST");
 
-/// PACK::TOCC::META chunk entry
-#[binrw]
-#[derive(Clone, Debug)]
-pub struct MetadataTableEntry {
-    #[br(map = Uuid::from_bytes_le)]
-    #[bw(map = Uuid::to_bytes_le)]
-    pub asset_id: Uuid,
-    pub offset: u32,
-}
+impl StringTableEntry {
+    /// Decodes the asset name. Names are UTF-8, unless they start with a UTF-16 byte order mark.
+    pub fn decode_name(&self) -> Result<String> {
+        let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| -> Result<String> {
+            ensure!(bytes.len().is_multiple_of(2), "Invalid UTF-16 name length {}", bytes.len());
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|c| from_bytes([c[0], c[1]])).collect();
+            Ok(String::from_utf16(&units)?)
+        };
+        match self.name.as_slice() {
+            [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+            [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+            bytes => Ok(String::from_utf8(bytes.to_vec())?),
+        }
+    }
 
-/// PACK::TOCC::STRG chunk
-#[binrw]
-#[derive(Clone, Debug, Default)]
-pub struct StringTable {
-    #[bw(try_calc = entries.len().try_into())]
-    pub entry_count: u32,
-    #[br(count = entry_count)]
-    pub entries: Vec<StringTableEntry>,
-}
+    /// Decodes the asset name like [`decode_name`](Self::decode_name), replacing invalid
+    /// sequences with U+FFFD. Returns the name and whether any bytes were replaced.
+    pub fn decode_name_lossy(&self) -> (String, bool) {
+        let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| -> (String, bool) {
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|c| from_bytes([c[0], c[1]])).collect();
+            let mut lossy = !bytes.len().is_multiple_of(2);
+            let mut name = String::from_utf16(&units).unwrap_or_else(|_| {
+                lossy = true;
+                String::from_utf16_lossy(&units)
+            });
+            if !bytes.len().is_multiple_of(2) {
+                name.push(char::REPLACEMENT_CHARACTER);
+            }
+            (name, lossy)
+        };
+        match self.name.as_slice() {
+            [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+            [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+            bytes => match String::from_utf8_lossy(bytes) {
+                Cow::Borrowed(name) => (name.to_string(), false),
+                Cow::Owned(name) => (name, true),
+            },
+        }
+    }
 
-/// PACK::TOCC::STRG chunk entry
-#[binrw]
-#[derive(Clone, Debug, Default)]
-pub struct StringTableEntry {
-    // Byteswapped
-    #[br(map = FourCC::from_u32)]
-    #[bw(map = FourCC::as_u32)]
-    pub kind: FourCC,
-    #[br(map = Uuid::from_bytes_le)]
-    #[bw(map = Uuid::to_bytes_le)]
-    pub asset_id: Uuid,
-    #[bw(try_calc = name.len().try_into())]
-    pub name_length: u32,
-    #[br(count = name_length)]
-    pub name: Vec<u8>,
+    /// Decodes the asset name, logging a warning if it isn't valid.
+    fn decode_name_or_warn(&self) -> (String, bool) {
+        let (name, lossy) = self.decode_name_lossy();
+        if lossy {
+            log::warn!("Asset {} has an invalid name, decoded as {:?}", self.asset_id, name);
+        }
+        (name, lossy)
+    }
 }
 
 /// Custom AINF chunk
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct AssetInfo {
-    #[br(map = Uuid::from_bytes_le)]
-    #[bw(map = Uuid::to_bytes_le)]
+    #[br(parse_with = parse_uuid)]
+    #[bw(write_with = write_uuid)]
     pub id: Uuid,
     pub compression_mode: u32,
     pub orig_offset: u64,
@@ -120,214 +158,3728 @@ pub struct Asset<'a> {
     pub id: Uuid,
     pub kind: FourCC,
     pub name: Option<String>,
-    // TODO lazy decompression?
-    pub data: Cow<'a, [u8]>,
+    /// Decompressed asset data, or `None` for assets read from a compressed block until it's
+    /// decompressed into `pending`. See [`Asset::data`].
+    data: Option<Cow<'a, [u8]>>,
+    /// Compressed data block, written as-is when the asset is written with its original
+    /// compression mode. Cleared by [`Asset::set_data`]. See [`Asset::raw_compressed`].
+    compressed: Option<Cow<'a, [u8]>>,
+    /// How to decompress `compressed`, when `data` is `None`
+    pending: Option<PendingData>,
     pub meta: Option<Cow<'a, [u8]>>,
     pub info: AssetInfo,
     pub version: u32,
     pub other_version: u32,
 }
 
-/// Combined package information
+/// Directory entry and read options of an asset read from a compressed block, used to
+/// decompress and validate it on first access
 #[derive(Debug, Clone)]
-pub struct Package<'a> {
-    pub assets: Vec<Asset<'a>>,
+struct PendingData {
+    entry: AssetDirectoryEntry,
+    options: Arc<ReadOptions>,
+    decompressed: OnceLock<Vec<u8>>,
 }
 
-impl Package<'_> {
-    pub fn read(data: &[u8], e: Endian) -> Result<Package> {
-        let (pack, pack_data, _) = FormDescriptor::slice(data, e)?;
-        ensure!(pack.id == K_FORM_PACK);
-        ensure!(pack.version_a == 1);
-        log::debug!("PACK: {:?}", pack);
-        let (tocc, mut tocc_data, _) = FormDescriptor::slice(pack_data, e)?;
-        ensure!(tocc.id == K_FORM_TOCC);
-        ensure!(tocc.version_a == 3);
-        log::debug!("TOCC: {:?}", tocc);
-        let mut adir: Option<AssetDirectory> = None;
-        let mut meta: HashMap<Uuid, &[u8]> = HashMap::new();
-        let mut strg: HashMap<Uuid, String> = HashMap::new();
-        while !tocc_data.is_empty() {
-            let (desc, chunk_data, remain) = ChunkDescriptor::slice(tocc_data, e)?;
-            let mut reader = Cursor::new(chunk_data);
-            log::debug!("{:?} data size {}", desc, chunk_data.len());
-            match desc.id {
-                K_CHUNK_ADIR => {
-                    let chunk: AssetDirectory = reader.read_type(e)?;
-                    for entry in &chunk.entries {
-                        log::debug!("- {:?}", entry);
-                    }
-                    adir = Some(chunk);
+/// Presence of the 4-byte compression mode header on compressed asset blocks
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum HeaderPresence {
+    /// Use the header when the first 4 bytes hold a known compression mode
+    #[default]
+    Auto,
+    /// Compressed blocks always start with the header
+    Always,
+    /// Compressed blocks never have a header; see [`ReadOptions::headerless_mode`]
+    Never,
+}
+
+/// Package read options
+#[derive(Clone, Debug, Default)]
+pub struct ReadOptions {
+    /// Presence of the compression mode header on compressed assets
+    pub header_present: HeaderPresence,
+    /// Compression mode used for compressed assets without a mode header
+    pub headerless_mode: Option<u32>,
+    /// Expected compression mode per asset type, overriding `headerless_mode`. With
+    /// [`HeaderPresence::Auto`], a block whose mode header doesn't match the expected mode
+    /// is decompressed as headerless data.
+    pub type_modes: HashMap<FourCC, u32>,
+    /// Warn on ADIR entries with asset types that aren't printable ASCII
+    pub validate_asset_types: bool,
+    /// Checksum that may follow the RFRM form in decompressed asset data
+    pub trailing_checksum: Option<TrailingChecksum>,
+    /// Recursively validate the forms and chunks in each asset
+    pub validate_forms: bool,
+    /// Maximum form nesting depth when validating forms, or [`DEFAULT_MAX_FORM_DEPTH`]
+    pub max_form_depth: Option<usize>,
+    /// Asset names taking precedence over the names in the STRG chunk, e.g. from an external
+    /// name database. Assets not listed keep their STRG name.
+    pub name_overrides: HashMap<Uuid, String>,
+    /// Decompress and validate every asset while reading, instead of on first access with
+    /// [`Asset::data`]. See [`Package::decompress_all`].
+    pub eager: bool,
+}
+
+// Default maximum form nesting depth for ReadOptions::validate_forms
+pub const DEFAULT_MAX_FORM_DEPTH: usize = 32;
+
+/// Checksum region following the RFRM form in decompressed asset data
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TrailingChecksum {
+    /// Size of the checksum in bytes
+    pub len: u64,
+    /// Verify the checksum as a little-endian CRC-32 of the RFRM form. Requires `len == 4`.
+    pub verify_crc32: bool,
+}
+
+/// Compression applied to an asset when writing
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CompressionChoice {
+    /// Store the asset uncompressed
+    Store,
+    /// Compress the asset with the given mode
+    Mode(u32),
+}
+
+/// Order of asset data blocks when writing
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum AssetOrdering {
+    /// Order by [`AssetInfo::orig_offset`], reproducing the original package layout.
+    /// Combined with [`WriteOptions::preserve_raw_tables`], repacking an unmodified package
+    /// reproduces it byte for byte.
+    PreserveOriginal,
+    /// Order by asset ID, matching the asset directory
+    #[default]
+    Directory,
+}
+
+/// Package write options
+#[derive(Clone, Debug)]
+pub struct WriteOptions {
+    /// Compression per asset type. Types not listed are compressed with the mode they were
+    /// read with ([`AssetInfo::compression_mode`]).
+    pub per_type: HashMap<FourCC, CompressionChoice>,
+    /// Order of asset data blocks
+    pub ordering: AssetOrdering,
+    /// Re-emit the original TOCC bytes verbatim, only patching ADIR offsets and sizes.
+    /// Requires the package to be read from package data, with the same assets, names and
+    /// metadata. Applies to [`Package::write_with_options`] and [`Package::to_vec_with_options`].
+    pub preserve_raw_tables: bool,
+    /// Write identical asset data blocks once, pointing their ADIR entries at the same offset.
+    /// Applies to the same methods as `preserve_raw_tables`.
+    pub deduplicate: bool,
+    /// Alignment of each asset data block within the file. Defaults to 1 (unaligned).
+    /// Applies to the same methods as `preserve_raw_tables`.
+    pub asset_alignment: u64,
+    /// Alignment of the end of the file, padded after the PACK form. Defaults to 16.
+    pub final_alignment: u64,
+    /// Byte used for alignment padding. Defaults to 0.
+    pub pad_byte: u8,
+    /// Leave out the META and STRG chunks when they have no entries. Packages read from
+    /// package data instead keep the chunks they were read with, so this only affects new
+    /// packages. Defaults to `false`, always writing both chunks.
+    pub omit_empty_tables: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            per_type: HashMap::new(),
+            ordering: AssetOrdering::default(),
+            preserve_raw_tables: false,
+            deduplicate: false,
+            asset_alignment: 1,
+            final_alignment: 16,
+            pad_byte: 0,
+            omit_empty_tables: false,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Compression for the given asset. Assets that don't compress smaller are stored
+    /// uncompressed regardless.
+    pub fn choice(&self, asset: &Asset) -> CompressionChoice {
+        self.per_type.get(&asset.kind).copied().unwrap_or(match asset.info.compression_mode {
+            0 => CompressionChoice::Store,
+            mode => CompressionChoice::Mode(mode),
+        })
+    }
+}
+
+// Number of assets per type sampled by Package::recommend_compression
+const RECOMMEND_SAMPLE_COUNT: usize = 8;
+// Minimum size reduction (in percent) for Package::recommend_compression to compress a type
+const RECOMMEND_MIN_SAVINGS: u64 = 10;
+
+/// Problem with an asset found by [`Package::verify`]
+#[derive(Clone, Debug)]
+pub struct VerifyIssue {
+    pub asset_id: Uuid,
+    pub reason: String,
+}
+
+/// Asset selection for [`Package::select`]. Assets must match every criterion set.
+#[derive(Clone, Debug, Default)]
+pub struct AssetFilter {
+    /// Asset type
+    pub kind: Option<FourCC>,
+    /// Name pattern, as in [`Package::find_assets_with_case`]. Assets without a name never match.
+    pub name: Option<String>,
+    /// Match `name` case-sensitively
+    pub case_sensitive: bool,
+    /// Asset IDs
+    pub ids: Option<HashSet<Uuid>>,
+}
+
+impl AssetFilter {
+    /// Whether the asset matches every criterion set.
+    pub fn matches(&self, asset: &Asset) -> bool {
+        self.kind.is_none_or(|kind| asset.kind == kind)
+            && self.name.as_deref().is_none_or(|pattern| {
+                asset
+                    .name
+                    .as_deref()
+                    .is_some_and(|name| glob::matches(pattern, name, self.case_sensitive))
+            })
+            && self.ids.as_ref().is_none_or(|ids| ids.contains(&asset.id))
+    }
+}
+
+/// Asset counts and sizes from the asset directory. See [`Package::stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackageStats {
+    pub asset_count: usize,
+    /// Total size of asset data blocks in the package
+    pub total_compressed: u64,
+    pub total_decompressed: u64,
+    /// Asset count and total decompressed size per asset type
+    pub by_type: HashMap<FourCC, (usize, u64)>,
+}
+
+/// Estimated decompression cost of loading a package
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LoadCost {
+    /// Number of compressed assets
+    pub compressed_assets: usize,
+    /// Total decompressed size of compressed assets
+    pub decompressed_bytes: u64,
+    /// Estimated total decompression time in seconds
+    pub seconds: f64,
+}
+
+impl<'a> Asset<'a> {
+    /// Creates an asset with the given data, stored uncompressed when written. Versions are
+    /// left at zero, and should match the RFRM form header.
+    pub fn new<D: Into<Cow<'a, [u8]>>>(id: Uuid, kind: FourCC, data: D) -> Self {
+        Asset {
+            id,
+            kind,
+            name: None,
+            data: Some(data.into()),
+            compressed: None,
+            pending: None,
+            meta: None,
+            info: AssetInfo { id, compression_mode: 0, orig_offset: 0 },
+            version: 0,
+            other_version: 0,
+        }
+    }
+
+    /// Decompressed asset data.
+    ///
+    /// Assets read from a compressed block are decompressed and checked against their RFRM
+    /// form on first access, and the result is kept for later calls.
+    pub fn data(&self) -> Result<&[u8]> {
+        if let Some(data) = &self.data {
+            return Ok(data);
+        }
+        let (Some(block), Some(pending)) = (&self.compressed, &self.pending) else {
+            bail!("Asset {} has no data", self.id);
+        };
+        if let Some(data) = pending.decompressed.get() {
+            return Ok(data);
+        }
+        let (_, data) = decode_asset_data(block, &pending.entry, &pending.options)?;
+        Ok(pending.decompressed.get_or_init(|| data.into_owned()))
+    }
+
+    /// Replaces the asset data. Any compressed block read with the asset is dropped, so the
+    /// new data is compressed when the asset is written.
+    pub fn set_data<D: Into<Cow<'a, [u8]>>>(&mut self, data: D) {
+        self.data = Some(data.into());
+        self.compressed = None;
+        self.pending = None;
+    }
+
+    /// Size of the decompressed asset data, without decompressing it.
+    pub fn decompressed_size(&self) -> u64 {
+        match (&self.data, &self.pending) {
+            (Some(data), _) => data.len() as u64,
+            (None, Some(pending)) => pending.entry.decompressed_size,
+            (None, None) => 0,
+        }
+    }
+
+    /// Reads an asset extracted with a custom FOOT footer, restoring its name, metadata
+    /// and [`AssetInfo`]. The original package layout can be reproduced with
+    /// [`AssetOrdering::PreserveOriginal`], using [`AssetInfo::orig_offset`].
+    pub fn read_with_footer(data: &'a [u8]) -> Result<Self> {
+        let (form, _, remain) = FormDescriptor::slice(data, Endian::Little)?;
+        let (foot, mut foot_data, _) = FormDescriptor::slice(remain, Endian::Little)?;
+        ensure!(foot.id == K_FORM_FOOT);
+        ensure!(foot.version_a == 1);
+        let mut ainfo: Option<AssetInfo> = None;
+        let mut meta: Option<&[u8]> = None;
+        let mut name: Option<String> = None;
+        while !is_padding(foot_data) {
+            let (chunk, chunk_data, remain) = ChunkDescriptor::slice(foot_data, Endian::Little)?;
+            match chunk.id {
+                K_CHUNK_AINF => {
+                    ainfo = Some(Cursor::new(chunk_data).read_type(Endian::Little)?);
                 }
                 K_CHUNK_META => {
-                    let chunk: MetadataTable = reader.read_type(e)?;
-                    for entry in chunk.entries {
-                        let meta_size = u32::from_le_bytes(
-                            chunk_data[entry.offset as usize..entry.offset as usize + 4]
-                                .try_into()
-                                .unwrap(),
-                        );
-                        let meta_data = &chunk_data
-                            [entry.offset as usize + 4..(entry.offset + 4 + meta_size) as usize];
-                        log::debug!("- {:?} (size {:#X})", entry, meta_size);
-                        meta.insert(entry.asset_id, meta_data);
-                    }
+                    meta = Some(chunk_data);
                 }
-                K_CHUNK_STRG => {
-                    let chunk: StringTable = reader.read_type(e)?;
-                    for entry in chunk.entries {
-                        log::debug!("- {:?}", entry);
-                        strg.insert(entry.asset_id, String::from_utf8(entry.name)?);
-                    }
+                K_CHUNK_NAME => {
+                    name = Some(String::from_utf8(chunk_data.to_vec())?);
                 }
-                kind => bail!("Unhandled TOCC chunk {:?}", kind),
+                _ => {}
             }
-            tocc_data = remain;
+            foot_data = remain;
         }
-
-        let Some(adir) = adir else {
-            bail!("Failed to locate asset directory");
+        let Some(ainfo) = ainfo else {
+            bail!("Failed to locate asset info footer");
         };
-        let mut package = Package { assets: Vec::with_capacity(adir.entries.len()) };
-        for asset_entry in &adir.entries {
-            let compressed_data = &data
-                [asset_entry.offset as usize..(asset_entry.offset + asset_entry.size) as usize];
-            let (compression_mode, data) = if asset_entry.size != asset_entry.decompressed_size {
-                decompress_buffer(compressed_data, asset_entry.decompressed_size)?
-            } else {
-                (0, Cow::Borrowed(compressed_data))
-            };
+        Ok(Asset {
+            id: ainfo.id,
+            kind: form.id,
+            name,
+            data: Some(Cow::Borrowed(&data[..data.len() - remain.len()])),
+            compressed: None,
+            pending: None,
+            meta: meta.map(Cow::Borrowed),
+            info: ainfo,
+            version: form.version_a,
+            other_version: form.version_b,
+        })
+    }
 
-            // Validate RFRM
-            {
-                let (form, _, _) = FormDescriptor::slice(&data, Endian::Little)?;
-                ensure!(asset_entry.asset_type == form.id);
-                ensure!(asset_entry.version == form.version_a);
-                ensure!(asset_entry.other_version == form.version_b);
-                ensure!(asset_entry.decompressed_size == form.size + 32 /* RFRM */);
-            }
-
-            package.assets.push(Asset {
-                id: asset_entry.asset_id,
-                kind: asset_entry.asset_type,
-                name: strg.get(&asset_entry.asset_id).cloned(),
-                data,
-                meta: meta.get(&asset_entry.asset_id).map(|data| Cow::Borrowed(*data)),
-                info: AssetInfo {
-                    id: asset_entry.asset_id,
-                    compression_mode,
-                    orig_offset: asset_entry.offset,
-                },
-                version: asset_entry.version,
-                other_version: asset_entry.other_version,
-            });
+    /// Writes the asset data followed by a FOOT form holding the asset info, metadata and name,
+    /// which [`Asset::read_with_footer`] reads back.
+    pub fn write_with_footer<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
+        w.write_all(self.data()?)?;
+        FormDescriptor { size: 0, unk: 0, id: K_FORM_FOOT, version_a: 1, version_b: 1 }.write(
+            w,
+            Endian::Little,
+            |w| {
+                ChunkDescriptor { id: K_CHUNK_AINF, size: 0, unk: 0, skip: 0 }.write(
+                    w,
+                    Endian::Little,
+                    |w| {
+                        w.write_le(&self.info)?;
+                        Ok(())
+                    },
+                )?;
+                if let Some(meta) = &self.meta {
+                    ChunkDescriptor { id: K_CHUNK_META, size: 0, unk: 0, skip: 0 }.write(
+                        w,
+                        Endian::Little,
+                        |w| {
+                            w.write_all(meta)?;
+                            Ok(())
+                        },
+                    )?;
+                }
+                if let Some(name) = &self.name {
+                    ChunkDescriptor { id: K_CHUNK_NAME, size: 0, unk: 0, skip: 0 }.write(
+                        w,
+                        Endian::Little,
+                        |w| {
+                            w.write_all(name.as_bytes())?;
+                            Ok(())
+                        },
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Reads an extracted asset file, with or without a footer. See [`Package::from_dir`].
+    fn read_file(data: &'a [u8], path: &Path, e: Endian) -> Result<Self> {
+        let (form, _, remain) = FormDescriptor::slice(data, e)?;
+        if FormDescriptor::slice(remain, Endian::Little)
+            .is_ok_and(|(foot, _, _)| foot.id == K_FORM_FOOT)
+        {
+            return Self::read_with_footer(data);
+        }
+        let id = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .context("File has no footer, and the file name is not an asset ID")?;
+        Ok(Asset {
+            id,
+            kind: form.id,
+            name: None,
+            data: Some(Cow::Borrowed(&data[..data.len() - remain.len()])),
+            compressed: None,
+            pending: None,
+            meta: None,
+            info: AssetInfo { id, compression_mode: 0, orig_offset: 0 },
+            version: form.version_a,
+            other_version: form.version_b,
+        })
+    }
+
+    /// File name used by [`Package::extract_to_dir`]: the asset name if present, otherwise
+    /// the asset ID, followed by the asset type rendered by [`FourCC::to_file_token`].
+    ///
+    /// `/` in names separates directories. Characters not allowed in file names on common
+    /// filesystems are replaced with `_`, as are empty, `.` and `..` path components.
+    pub fn file_name(&self) -> String { self.file_name_with_suffix(None) }
+
+    /// [`Asset::file_name`] without the asset type: the sanitized asset name if present,
+    /// otherwise the asset ID.
+    pub fn file_stem(&self) -> String {
+        match &self.name {
+            Some(name) => {
+                name.split('/').map(sanitize_path_component).collect::<Vec<_>>().join("/")
+            }
+            None => self.id.to_string(),
+        }
+    }
+
+    /// File name with a numeric suffix before the asset type, used to resolve collisions.
+    fn file_name_with_suffix(&self, suffix: Option<usize>) -> String {
+        let stem = self.file_stem();
+        match suffix {
+            Some(suffix) => format!("{}.{}.{}", stem, suffix, self.kind.to_file_token()),
+            None => format!("{}.{}", stem, self.kind.to_file_token()),
+        }
+    }
+
+    /// Copies any borrowed data, detaching the asset from the package data.
+    pub fn into_owned(self) -> Asset<'static> {
+        Asset {
+            id: self.id,
+            kind: self.kind,
+            name: self.name,
+            data: self.data.map(|data| Cow::Owned(data.into_owned())),
+            compressed: self.compressed.map(|block| Cow::Owned(block.into_owned())),
+            pending: self.pending,
+            meta: self.meta.map(|meta| Cow::Owned(meta.into_owned())),
+            info: self.info,
+            version: self.version,
+            other_version: self.other_version,
+        }
+    }
+
+    /// Metadata bytes, borrowed from the package data when read from it.
+    pub fn meta_bytes(&self) -> Option<&[u8]> { self.meta.as_deref() }
+
+    /// Compressed data block and compression mode of an asset read from a compressed entry.
+    ///
+    /// The block is as stored in the package, starting with the 4-byte mode header unless
+    /// the package stores compressed blocks without one.
+    pub fn raw_compressed(&self) -> Option<(&[u8], u32)> {
+        self.compressed.as_deref().map(|block| (block, self.info.compression_mode))
+    }
+
+    /// Size of the compressed data block relative to the decompressed data, if the asset holds
+    /// a compressed block (see [`Asset::raw_compressed`]).
+    pub fn compressed_ratio(&self) -> Option<f32> {
+        let block = self.compressed.as_ref()?;
+        let size = self.decompressed_size();
+        (size != 0).then(|| block.len() as f32 / size as f32)
+    }
+
+    /// Whether two assets have the same type and decompressed data, regardless of how
+    /// either is compressed in its package.
+    ///
+    /// Assets with different decompressed sizes differ without decompressing either, and
+    /// identical compressed blocks match without decompressing. Otherwise, both are
+    /// decompressed and compared.
+    pub fn same_content(&self, other: &Asset) -> Result<bool> {
+        if self.kind != other.kind || self.decompressed_size() != other.decompressed_size() {
+            return Ok(false);
+        }
+        if let (Some(a), Some(b)) = (self.raw_compressed(), other.raw_compressed()) {
+            if a == b {
+                return Ok(true);
+            }
+        }
+        Ok(self.data()? == other.data()?)
+    }
+
+    /// Size of the asset data as it would be written with the given compression.
+    pub fn estimate_compressed_size(&self, choice: CompressionChoice) -> Result<u64> {
+        Ok(compress_asset(self.data()?, choice)?.1.len() as u64)
+    }
+}
+
+/// Combined package information
+#[derive(Debug, Clone, Default)]
+pub struct Package<'a> {
+    pub assets: Vec<Asset<'a>>,
+    /// Unrecognized TOCC chunks, written back after STRG
+    pub unknown_chunks: Vec<(FourCC, Vec<u8>)>,
+    /// Edit journal, when enabled
+    journal: Option<Vec<PackageEdit<'a>>>,
+    /// Table of contents of the package data, when read from a file
+    source: Option<PackageSource<'a>>,
+}
+
+/// Layout of asset names in a package
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NameTableKind {
+    /// No name table
+    None,
+    /// Inline TOCC::STRG chunk
+    StringTable,
+}
+
+/// Parsed TOCC tables
+#[derive(Debug, Clone)]
+struct TableOfContents<'a> {
+    directory: AssetDirectory,
+    meta: HashMap<Uuid, Cow<'a, [u8]>>,
+    names: HashMap<Uuid, String>,
+    /// Asset type stored with each name, which should match the ADIR asset type
+    name_kinds: HashMap<Uuid, FourCC>,
+    /// Assets with names that weren't valid UTF-8 or UTF-16
+    lossy_names: HashSet<Uuid>,
+    /// Encoding of each name that isn't UTF-8, reused when writing the name
+    name_encodings: HashMap<Uuid, NameEncoding>,
+    name_table: NameTableKind,
+    unknown: Vec<(FourCC, Vec<u8>)>,
+    /// `unk` and `skip` of each chunk, by chunk ID
+    chunk_fields: HashMap<FourCC, (u32, u64)>,
+}
+
+/// Table of contents of the package data a [`Package`] was read from
+#[derive(Debug, Clone)]
+struct PackageSource<'a> {
+    /// SHA-256 of the package data up to the end of the TOCC
+    digest: [u8; 32],
+    toc: TableOfContents<'a>,
+    /// Original TOCC form, including the form header
+    raw_tocc: Cow<'a, [u8]>,
+    forms: FormFields,
+    endian: Endian,
+}
+
+/// `unk` and `version_b` of the PACK and TOCC form headers, replayed when writing
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct FormFields {
+    pack: (u64, u32),
+    tocc: (u64, u32),
+}
+
+impl Default for FormFields {
+    /// Values written for new packages
+    fn default() -> Self { Self { pack: (0, 1), tocc: (0, 3) } }
+}
+
+impl FormFields {
+    /// Reads the fields from the PACK and TOCC form headers at the start of `data`.
+    fn read(data: &[u8], e: Endian) -> Result<Self> {
+        let mut reader = Cursor::new(data);
+        let pack = FormDescriptor::read(&mut reader, e)?;
+        let tocc = FormDescriptor::read(&mut reader, e)?;
+        Ok(Self { pack: (pack.unk, pack.version_b), tocc: (tocc.unk, tocc.version_b) })
+    }
+
+    fn pack_form(&self, size: u64) -> FormDescriptor {
+        FormDescriptor {
+            size,
+            unk: self.pack.0,
+            id: K_FORM_PACK,
+            version_a: 1,
+            version_b: self.pack.1,
+        }
+    }
+
+    fn tocc_form(&self) -> FormDescriptor {
+        FormDescriptor {
+            size: 0,
+            unk: self.tocc.0,
+            id: K_FORM_TOCC,
+            version_a: 3,
+            version_b: self.tocc.1,
+        }
+    }
+}
+
+impl Package<'_> {
+    /// Reads a package, borrowing uncompressed asset data from `data`.
+    ///
+    /// Compressed assets are decompressed on first access with [`Asset::data`]. To
+    /// decompress every asset up front, set [`ReadOptions::eager`].
+    ///
+    /// To read a file without copying it, open it with `MappedPackage` (with the `mmap`
+    /// feature), which owns the map and hands out packages borrowing from it. Packages can be
+    /// detached from the data they borrow with [`Package::into_owned`].
+    pub fn read(data: &[u8], e: Endian) -> Result<Package<'_>> {
+        Self::read_with_options(data, e, &ReadOptions::default())
+    }
+
+    pub fn read_with_options<'a>(
+        data: &'a [u8],
+        e: Endian,
+        options: &ReadOptions,
+    ) -> Result<Package<'a>> {
+        Self::read_with_progress(data, e, options, |_, _, _| Ok(()))
+    }
+
+    /// Reads a package like [`Package::read_with_options`], calling `progress` with the asset
+    /// index, asset count and asset ID after each asset is read, or decompressed with
+    /// [`ReadOptions::eager`]. An error returned by `progress` aborts the read.
+    pub fn read_with_progress<'a, F>(
+        data: &'a [u8],
+        e: Endian,
+        options: &ReadOptions,
+        mut progress: F,
+    ) -> Result<Package<'a>>
+    where
+        F: FnMut(usize, usize, Uuid) -> Result<()>,
+    {
+        let (tocc_data, toc_len) = slice_tocc(data, e)?;
+        let toc = TableOfContents::read(tocc_data, e)?;
+        check_entry_bounds(&toc.directory, data.len())?;
+        let digest = Sha256::digest(&data[..toc_len]).into();
+        let header = Cow::Borrowed(&data[..toc_len]);
+        let package = Self::read_assets(toc, digest, header, e, options, |entry| {
+            entry_block(data, entry, e).map(Cow::Borrowed)
+        })?;
+        let total = package.assets.len();
+        if options.eager {
+            map_with_progress(
+                &package.assets,
+                |asset| asset.data().map(|_| ()),
+                |index| progress(index, total, package.assets[index].id),
+            )?;
+        } else {
+            for (index, asset) in package.assets.iter().enumerate() {
+                progress(index, total, asset.id)?;
+            }
         }
         Ok(package)
     }
 
-    pub fn write<W: Write + Seek>(&self, w: &mut W, e: Endian) -> Result<()> {
-        let mut asset_directory = AssetDirectory::default();
-        let mut metadata = MetadataTable::default();
-        let mut string_table = StringTable::default();
-        let mut last_uuid = Uuid::nil();
-        for asset in &self.assets {
-            ensure!(asset.id >= last_uuid, "Assets must be ordered by ID ascending");
-            last_uuid = asset.id;
+    /// Decompresses every asset not yet decompressed, as [`Asset::data`] would on first
+    /// access, returning the first error. With the `rayon` feature, assets are decompressed
+    /// across threads.
+    pub fn decompress_all(&self) -> Result<()> {
+        map_with_progress(&self.assets, |asset| asset.data().map(|_| ()), |_| Ok(()))?;
+        Ok(())
+    }
 
-            asset_directory.entries.push(AssetDirectoryEntry {
-                asset_type: asset.kind,
-                asset_id: asset.id,
-                version: asset.version,
-                other_version: asset.other_version,
-                offset: 0,
-                decompressed_size: asset.data.len() as u64,
-                size: asset.data.len() as u64,
-            });
-            if asset.meta.is_some() {
-                metadata.entries.push(MetadataTableEntry { asset_id: asset.id, offset: 0 });
+    /// Decompresses assets accepted by `filter` across threads, calling `f` with each asset
+    /// and its data. Decompressed data is cached, as with [`Asset::data`].
+    ///
+    /// Returns the errors for any assets that failed to decompress. See also
+    /// [`LazyPackage::par_process`], which doesn't cache the data.
+    #[cfg(feature = "rayon")]
+    pub fn par_process<P, F>(&self, filter: P, f: F) -> Vec<(Uuid, anyhow::Error)>
+    where
+        P: Fn(&Asset) -> bool + Sync,
+        F: Fn(&Asset, &[u8]) + Sync,
+    {
+        use rayon::prelude::*;
+
+        self.assets
+            .par_iter()
+            .filter(|asset| filter(asset))
+            .filter_map(|asset| match asset.data() {
+                Ok(data) => {
+                    f(asset, data);
+                    None
+                }
+                Err(e) => Some((asset.id, e)),
+            })
+            .collect()
+    }
+
+    /// Reads a package, also returning a description of its form and chunk structure as
+    /// rendered by [`PackageLayout`], for display without enabling the logger.
+    pub fn read_verbose(data: &[u8], e: Endian) -> Result<(Package<'_>, String)> {
+        let layout = Self::describe(data, e)?;
+        Ok((Self::read(data, e)?, layout.to_string()))
+    }
+
+    /// Reads every PACK form in a container file holding several top-level forms back to back.
+    ///
+    /// Each package's offsets are relative to the start of its own form. Zero padding between
+    /// forms is skipped, and top-level forms other than PACK are skipped with a warning.
+    pub fn read_all(data: &[u8], e: Endian) -> Result<Vec<Package<'_>>> {
+        let mut packages = Vec::new();
+        let mut remain = data;
+        while !is_padding(remain) {
+            remain = &remain[remain.iter().take_while(|&&b| b == 0).count()..];
+            let offset = data.len() - remain.len();
+            let (form, _, next) = FormDescriptor::slice(remain, e)
+                .with_context(|| format!("Failed to read form at offset {:#X}", offset))?;
+            let form_data = &remain[..remain.len() - next.len()];
+            if form.id == K_FORM_PACK {
+                packages.push(
+                    Self::read(form_data, e)
+                        .with_context(|| format!("Failed to read PACK at offset {:#X}", offset))?,
+                );
+            } else {
+                log::warn!("Skipping {} form at offset {:#X}", form.id, offset);
             }
-            if let Some(name) = &asset.name {
-                // Default::default makes the IDE happy,
-                // just need to suppress clippy
-                #[allow(clippy::needless_update)]
-                string_table.entries.push(StringTableEntry {
-                    kind: asset.kind,
-                    asset_id: asset.id,
-                    name: name.as_bytes().to_vec(),
-                    ..Default::default()
+            remain = next;
+        }
+        Ok(packages)
+    }
+
+    /// Reads a package and copies all borrowed data, so it doesn't borrow `data`.
+    /// Equivalent to [`Package::read`] followed by [`Package::into_owned`].
+    pub fn read_owned(data: &[u8], e: Endian) -> Result<Package<'static>> {
+        Ok(Self::read(data, e)?.into_owned())
+    }
+
+    /// Reads a package, detecting its endianness from the PACK form header.
+    /// Returns the detected endianness along with the package, for writing it back.
+    pub fn read_autodetect(data: &[u8]) -> Result<(Package<'_>, Endian)> {
+        let e = detect_endian(data)?;
+        Ok((Self::read(data, e)?, e))
+    }
+
+    /// Reads the table of contents, returning an iterator that reads and decompresses each
+    /// asset as it's reached, in directory order.
+    ///
+    /// To skip decompressing assets based on their type or name, filter
+    /// [`LazyPackage::headers`] instead.
+    pub fn iter_assets<'a>(
+        data: &'a [u8],
+        e: Endian,
+    ) -> Result<impl Iterator<Item = Result<Asset<'a>>>> {
+        let (tocc_data, _) = slice_tocc(data, e)?;
+        let toc = TableOfContents::read(tocc_data, e)?;
+        let options = Arc::new(ReadOptions::default());
+        let mut index = 0;
+        Ok(std::iter::from_fn(move || {
+            let entry = toc.directory.entries.get(index)?;
+            index += 1;
+            let asset = entry_block(data, entry, e)
+                .and_then(|block| toc.asset(entry, Cow::Borrowed(block), &options))
+                .and_then(|asset| {
+                    asset.data()?;
+                    Ok(asset)
                 });
+            Some(asset)
+        }))
+    }
+
+    /// Builds a package from its table of contents, reading each asset's data block with
+    /// `read_block`. `header` is the package data up to the end of the TOCC.
+    fn read_assets<'a, F>(
+        toc: TableOfContents<'a>,
+        digest: [u8; 32],
+        header: Cow<'a, [u8]>,
+        e: Endian,
+        options: &ReadOptions,
+        mut read_block: F,
+    ) -> Result<Package<'a>>
+    where
+        F: FnMut(&AssetDirectoryEntry) -> Result<Cow<'a, [u8]>>,
+    {
+        let options = Arc::new(options.clone());
+        let mut package = Package {
+            assets: Vec::with_capacity(toc.directory.entries.len()),
+            unknown_chunks: toc.unknown.clone(),
+            ..Default::default()
+        };
+        // Directory order doesn't need to match the data layout, offsets are explicit
+        for asset_entry in &toc.directory.entries {
+            if options.validate_asset_types {
+                asset_entry.validate();
             }
+            let mut asset = toc.asset(asset_entry, read_block(asset_entry)?, &options)?;
+            if let Some(name) = options.name_overrides.get(&asset.id) {
+                asset.name = Some(name.clone());
+            }
+            package.assets.push(asset);
         }
-        let mut adir_pos = 0;
-        FormDescriptor { size: 0, unk: 0, id: K_FORM_PACK, version_a: 1, version_b: 1 }.write(
-            w,
-            e,
-            |w| {
-                FormDescriptor { size: 0, unk: 0, id: K_FORM_TOCC, version_a: 3, version_b: 3 }
-                    .write(w, e, |w| {
-                        ChunkDescriptor { id: K_CHUNK_ADIR, size: 0, unk: 1, skip: 0 }.write(
-                            w,
-                            e,
-                            |w| {
-                                adir_pos = w.stream_position()?;
-                                w.write_type(&asset_directory, e)?;
-                                Ok(())
-                            },
-                        )?;
-                        ChunkDescriptor { id: K_CHUNK_META, size: 0, unk: 1, skip: 0 }.write(
-                            w,
-                            e,
-                            |w| {
-                                let start = w.stream_position()?;
-                                w.write_type(&metadata, e)?;
-                                for (asset, entry) in self
-                                    .assets
-                                    .iter()
-                                    .filter(|a| a.meta.is_some())
-                                    .zip(&mut metadata.entries)
-                                {
-                                    entry.offset = (w.stream_position()? - start) as u32;
-                                    let data = asset.meta.as_ref().unwrap();
-                                    w.write_type(&(data.len() as u32), e)?;
-                                    w.write_all(data)?;
-                                }
-                                let end = w.stream_position()?;
-                                w.seek(SeekFrom::Start(start))?;
-                                w.write_type(&metadata, e)?;
-                                w.seek(SeekFrom::Start(end))?;
-                                Ok(())
-                            },
-                        )?;
-                        ChunkDescriptor { id: K_CHUNK_STRG, size: 0, unk: 1, skip: 0 }.write(
-                            w,
-                            e,
-                            |w| {
-                                w.write_type(&string_table, e)?;
-                                Ok(())
-                            },
-                        )?;
-                        Ok(())
-                    })?;
-                let mut entries: Vec<(&Asset, &mut AssetDirectoryEntry)> =
-                    self.assets.iter().zip(&mut asset_directory.entries).collect();
-                entries.sort_by_key(|(a, _)| a.info.orig_offset);
-                for (asset, entry) in entries {
-                    entry.offset = w.stream_position()?;
-                    w.write_all(&asset.data)?;
-                }
-                Ok(())
-            },
-        )?;
+        let forms = FormFields::read(&header, e)?;
+        let raw_tocc = match header {
+            Cow::Borrowed(header) => Cow::Borrowed(&header[FORM_DESCRIPTOR_SIZE..]),
+            Cow::Owned(mut header) => {
+                header.drain(..FORM_DESCRIPTOR_SIZE);
+                Cow::Owned(header)
+            }
+        };
+        package.source = Some(PackageSource { digest, toc, raw_tocc, forms, endian: e });
+        Ok(package)
+    }
 
-        // Write updated ADIR offsets
+    /// Writes the package with default options, laying out asset data in directory order.
+    /// To repack a package keeping its original layout, use [`Package::write_with_options`]
+    /// with [`AssetOrdering::PreserveOriginal`].
+    pub fn write<W: Write + Seek>(&self, w: &mut W, e: Endian) -> Result<()> {
+        self.write_with_options(w, e, &WriteOptions::default())
+    }
+
+    pub fn write_with_options<W: Write + Seek>(
+        &self,
+        w: &mut W,
+        e: Endian,
+        options: &WriteOptions,
+    ) -> Result<()> {
+        let mut plan = self.plan_write(options)?;
+        plan.raw_tocc = self.raw_tocc(e, options)?;
+        plan.write(w, e, &self.data_order(options.ordering))
+    }
+
+    /// Writes the package like [`Package::write_with_options`], calling `progress` with the
+    /// asset index, asset count and asset ID after each asset is compressed. With the `rayon`
+    /// feature, assets are compressed across threads, and `progress` is called in the order
+    /// they complete. An error returned by `progress` aborts the write before anything is
+    /// written.
+    pub fn write_with_progress<W, F>(
+        &self,
+        w: &mut W,
+        e: Endian,
+        options: &WriteOptions,
+        mut progress: F,
+    ) -> Result<()>
+    where
+        W: Write + Seek,
+        F: FnMut(usize, usize, Uuid) -> Result<()>,
+    {
+        let total = self.assets.len();
+        let mut plan = self.plan_write_with_progress(options, |index| {
+            progress(index, total, self.assets[index].id)
+        })?;
+        plan.raw_tocc = self.raw_tocc(e, options)?;
+        plan.write(w, e, &self.data_order(options.ordering))
+    }
+
+    /// Writes the package to a new buffer.
+    pub fn to_vec(&self, e: Endian) -> Result<Vec<u8>> {
+        self.to_vec_with_options(e, &WriteOptions::default())
+    }
+
+    /// Writes the package to a new buffer, allocated up front with the final package size.
+    pub fn to_vec_with_options(&self, e: Endian, options: &WriteOptions) -> Result<Vec<u8>> {
+        let mut plan = self.plan_write(options)?;
+        plan.raw_tocc = self.raw_tocc(e, options)?;
+        let mut cursor = Cursor::new(Vec::with_capacity(plan.package_size() as usize));
+        plan.write(&mut cursor, e, &self.data_order(options.ordering))?;
+        Ok(cursor.into_inner())
+    }
+
+    /// Writes the package as a TOCC-only package, with the asset data written separately.
+    ///
+    /// The format has no way to reference assets stored in another file, so ADIR offsets are
+    /// simply relative to the start of the data written to `data_w`. Concatenating the data
+    /// after the TOCC-only package does not produce a valid package; the offsets must be
+    /// rebased first.
+    ///
+    /// Asset data blocks are aligned to `asset_alignment` relative to the start of the data,
+    /// and the TOCC-only package is padded to `final_alignment`.
+    pub fn write_split<W: Write + Seek, D: Write + Seek>(
+        &self,
+        w: &mut W,
+        data_w: &mut D,
+        e: Endian,
+        options: &WriteOptions,
+    ) -> Result<()> {
+        let mut plan = self.plan_write(options)?;
+        let data_start = data_w.stream_position()?;
+        for index in self.data_order(options.ordering) {
+            let pos = data_w.stream_position()? - data_start;
+            let offset = align(pos, options.asset_alignment);
+            data_w.write_all(&vec![options.pad_byte; (offset - pos) as usize])?;
+            plan.directory.entries[index].offset = offset;
+            data_w.write_all(&plan.data[index])?;
+        }
+        plan.forms.pack_form(0).write(w, e, |w| {
+            plan.forms.tocc_form().write(w, e, |w| {
+                let (meta, string_table) = plan.tables();
+                write_toc_chunks(
+                    w,
+                    e,
+                    &plan.directory,
+                    meta,
+                    string_table,
+                    plan.unknown,
+                    plan.chunk_fields,
+                )?;
+                Ok(())
+            })
+        })?;
         let pos = w.stream_position()?;
-        w.seek(SeekFrom::Start(adir_pos))?;
-        w.write_type(&asset_directory, e)?;
-        w.seek(SeekFrom::Start(pos))?;
+        let aligned_end = align(pos, options.final_alignment);
+        w.write_all(&vec![options.pad_byte; (aligned_end - pos) as usize])?;
+        Ok(())
+    }
 
-        // Align 16
-        let aligned_end = (pos + 15) & !15;
-        w.write_all(&vec![0u8; (aligned_end - pos) as usize])?;
+    /// Writes only the asset data region, returning the asset directory entries with
+    /// offsets relative to the start of the region.
+    ///
+    /// Together with [`Package::write_tocc_with_entries`], this allows writing the TOCC and
+    /// data region separately. Concatenating the two produces a valid package, laid out as
+    /// [`Package::write_with_options`] would: asset data blocks are aligned to
+    /// `asset_alignment` and the end of the package to `final_alignment`, both relative to
+    /// the start of the package.
+    pub fn write_data_region<W: Write>(
+        &self,
+        w: &mut W,
+        options: &WriteOptions,
+    ) -> Result<Vec<AssetDirectoryEntry>> {
+        let mut plan = self.plan_write(options)?;
+        // Package::write_tocc_with_entries always writes both tables
+        plan.omit_empty_tables = false;
+        let toc_size = plan.toc_size();
+        let mut pos = toc_size;
+        for index in self.data_order(options.ordering) {
+            let offset = align(pos, options.asset_alignment);
+            w.write_all(&vec![options.pad_byte; (offset - pos) as usize])?;
+            plan.directory.entries[index].offset = offset - toc_size;
+            w.write_all(&plan.data[index])?;
+            pos = offset + plan.data[index].len() as u64;
+        }
+        let aligned_end = align(pos, options.final_alignment);
+        w.write_all(&vec![options.pad_byte; (aligned_end - pos) as usize])?;
+        Ok(plan.directory.entries)
+    }
+
+    /// Writes the PACK header and TOCC for a data region written by
+    /// [`Package::write_data_region`], which must directly follow it.
+    pub fn write_tocc_with_entries<W: Write + Seek>(
+        &self,
+        w: &mut W,
+        e: Endian,
+        entries: &[AssetDirectoryEntry],
+    ) -> Result<()> {
+        ensure!(
+            entries.len() == self.assets.len(),
+            "Expected {} asset directory entries, got {}",
+            self.assets.len(),
+            entries.len()
+        );
+        let mut plan = self.plan_tables(&WriteOptions::default());
+        plan.directory.entries = entries.to_vec();
+        let toc_size = plan.toc_size();
+        let mut data_size = 0;
+        for entry in &mut plan.directory.entries {
+            let end = entry.offset.checked_add(entry.size);
+            data_size = data_size.max(end.context("Asset directory entry out of range")?);
+            entry.offset += toc_size;
+        }
+        let pack = plan.forms.pack_form(toc_size + data_size - FORM_DESCRIPTOR_SIZE as u64);
+        w.write_type(&pack, e)?;
+        plan.forms.tocc_form().write(w, e, |w| {
+            let (meta, string_table) = plan.tables();
+            write_toc_chunks(
+                w,
+                e,
+                &plan.directory,
+                meta,
+                string_table,
+                plan.unknown,
+                plan.chunk_fields,
+            )?;
+            Ok(())
+        })?;
         Ok(())
     }
+
+    /// Builds the TOCC tables, and compresses asset data for writing.
+    /// ADIR offsets are left at zero.
+    fn plan_write(&self, options: &WriteOptions) -> Result<WritePlan<'_>> {
+        self.plan_write_with_progress(options, |_| Ok(()))
+    }
+
+    /// Like [`Package::plan_write`], calling `progress` with the index of each asset as it's
+    /// compressed. See [`map_with_progress`].
+    fn plan_write_with_progress<P>(
+        &self,
+        options: &WriteOptions,
+        progress: P,
+    ) -> Result<WritePlan<'_>>
+    where
+        P: FnMut(usize) -> Result<()>,
+    {
+        self.ensure_sorted()?;
+        let data = map_with_progress(
+            &self.assets,
+            |asset| Ok(asset_block(asset, options.choice(asset))?.1),
+            progress,
+        )?;
+        Ok(self.plan_with_data(data, options))
+    }
+
+    /// Original TOCC form and the offset of its ADIR chunk data, when
+    /// [`WriteOptions::preserve_raw_tables`] is set.
+    fn raw_tocc(&self, e: Endian, options: &WriteOptions) -> Result<Option<(&[u8], u64)>> {
+        if !options.preserve_raw_tables {
+            return Ok(None);
+        }
+        let Some(source) = &self.source else {
+            bail!("Raw tables require a package read from package data");
+        };
+        let toc = &source.toc;
+        ensure!(
+            self.assets.len() == toc.directory.entries.len()
+                && self.assets.iter().zip(&toc.directory.entries).all(|(a, entry)| {
+                    a.id == entry.asset_id
+                        && a.name.as_ref() == toc.names.get(&a.id)
+                        && a.meta_bytes() == toc.meta.get(&a.id).map(|m| m.as_ref())
+                })
+                && self.unknown_chunks == toc.unknown,
+            "Assets, names, metadata or unknown chunks changed since the package was read; raw tables can't be preserved"
+        );
+        ensure!(source.endian == e, "Raw tables must be written with the original endianness");
+
+        let raw = source.raw_tocc.as_ref();
+        let mut remain = &raw[FORM_DESCRIPTOR_SIZE..];
+        while !is_padding(remain) {
+            let (desc, chunk_data, next) = ChunkDescriptor::slice(remain, e)?;
+            if desc.id == K_CHUNK_ADIR {
+                let offset = chunk_data.as_ptr() as usize - raw.as_ptr() as usize;
+                return Ok(Some((raw, offset as u64)));
+            }
+            remain = next;
+        }
+        bail!("Failed to locate asset directory");
+    }
+
+    /// Builds the TOCC tables for compressed asset data blocks, in asset order.
+    fn plan_with_data<'b>(
+        &'b self,
+        data: Vec<Cow<'b, [u8]>>,
+        options: &WriteOptions,
+    ) -> WritePlan<'b> {
+        let mut plan = self.plan_tables(options);
+        for (asset, data) in self.assets.iter().zip(&data) {
+            plan.directory.entries.push(AssetDirectoryEntry {
+                asset_type: asset.kind,
+                asset_id: asset.id,
+                version: asset.version,
+                other_version: asset.other_version,
+                offset: 0,
+                decompressed_size: asset.decompressed_size(),
+                size: data.len() as u64,
+            });
+        }
+        plan.data = data;
+        plan
+    }
+
+    fn ensure_sorted(&self) -> Result<()> {
+        let mut last_uuid = Uuid::nil();
+        for asset in &self.assets {
+            ensure!(asset.id >= last_uuid, "Assets must be ordered by ID ascending");
+            last_uuid = asset.id;
+        }
+        Ok(())
+    }
+
+    /// Builds the META and STRG tables, with an empty asset directory and no asset data.
+    fn plan_tables(&self, options: &WriteOptions) -> WritePlan<'_> {
+        let mut plan = WritePlan::new(options);
+        for asset in &self.assets {
+            if let Some(name) = &asset.name {
+                plan.string_table.entries.push(StringTableEntry::new(
+                    asset.kind,
+                    asset.id,
+                    self.name_encoding(asset.id).encode(name),
+                ));
+            }
+        }
+        plan.meta = self.assets.iter().filter_map(|a| a.meta_bytes().map(|m| (a.id, m))).collect();
+        plan.unknown = &self.unknown_chunks;
+        plan.chunk_fields = self.source.as_ref().map(|source| &source.toc.chunk_fields);
+        plan.forms = self.source.as_ref().map_or_else(FormFields::default, |source| source.forms);
+        plan
+    }
+
+    /// Asset indices in the order their data is written.
+    ///
+    /// The directory is always sorted by ID, and by default data follows the same order.
+    /// With [`AssetOrdering::PreserveOriginal`], packages with an ID-sorted directory but a
+    /// different data layout keep their layout. Ties in `orig_offset` keep directory order.
+    fn data_order(&self, ordering: AssetOrdering) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.assets.len()).collect();
+        if ordering == AssetOrdering::PreserveOriginal {
+            order.sort_by_key(|&i| self.assets[i].info.orig_offset);
+        }
+        order
+    }
+
+    /// Recommends a compression choice per asset type.
+    ///
+    /// A few assets of each type are compressed with every mode, and the best mode is chosen
+    /// if it saves enough space. Otherwise (e.g. for already-compressed texture data), the type
+    /// is stored uncompressed. The result can be used as [`WriteOptions::per_type`].
+    pub fn recommend_compression(&self) -> Result<HashMap<FourCC, CompressionChoice>> {
+        let mut samples: HashMap<FourCC, Vec<&Asset>> = HashMap::new();
+        for asset in &self.assets {
+            let sample = samples.entry(asset.kind).or_default();
+            if sample.len() < RECOMMEND_SAMPLE_COUNT {
+                sample.push(asset);
+            }
+        }
+        let mut result = HashMap::with_capacity(samples.len());
+        for (kind, assets) in samples {
+            let stored: u64 = assets.iter().map(|a| a.decompressed_size()).sum();
+            let mut best = (CompressionChoice::Store, stored);
+            for mode in 1..=3 {
+                let choice = CompressionChoice::Mode(mode);
+                let mut size = 0;
+                for asset in &assets {
+                    size += asset.estimate_compressed_size(choice)?;
+                }
+                if size < best.1 {
+                    best = (choice, size);
+                }
+            }
+            if best.1 * 100 > stored * (100 - RECOMMEND_MIN_SAVINGS) {
+                best.0 = CompressionChoice::Store;
+            }
+            result.insert(kind, best.0);
+        }
+        Ok(result)
+    }
+
+    /// Compresses every asset with `mode`, replacing any compressed blocks and updating
+    /// [`AssetInfo::compression_mode`]. Assets that don't compress smaller, or aren't a valid
+    /// size for the mode, are stored uncompressed. Mode 0 stores every asset uncompressed.
+    pub fn recompress(&mut self, mode: u32) -> Result<()> {
+        for asset in &mut self.assets {
+            let (mode, block) = compress_asset(asset.data()?, CompressionChoice::Mode(mode))?;
+            let block = (mode != 0).then(|| block.into_owned());
+            if let Some(pending) = asset.pending.take() {
+                asset.data = pending.decompressed.into_inner().map(Cow::Owned);
+            }
+            asset.compressed = block.map(Cow::Owned);
+            asset.info.compression_mode = mode;
+        }
+        Ok(())
+    }
+
+    /// Copies any borrowed data, detaching the package from the package data.
+    pub fn into_owned(self) -> Package<'static> {
+        Package {
+            assets: self.assets.into_iter().map(Asset::into_owned).collect(),
+            unknown_chunks: self.unknown_chunks,
+            journal: self
+                .journal
+                .map(|journal| journal.into_iter().map(PackageEdit::into_owned).collect()),
+            source: self.source.map(|source| PackageSource {
+                digest: source.digest,
+                toc: source.toc.into_owned(),
+                raw_tocc: Cow::Owned(source.raw_tocc.into_owned()),
+                forms: source.forms,
+                endian: source.endian,
+            }),
+        }
+    }
+
+    /// Writes each asset to a file in `dir` named by [`Asset::file_name`], with a footer
+    /// written by [`Asset::write_with_footer`]. Names containing `/` create subdirectories.
+    ///
+    /// If two assets map to the same file name (compared case-insensitively), the later one
+    /// gets a numeric suffix before the asset type, e.g. `name.1.TXTR`. Only files written
+    /// by this call are considered. Returns the path written for each asset.
+    pub fn extract_to_dir<P: AsRef<Path>>(&self, dir: P) -> Result<HashMap<Uuid, PathBuf>> {
+        self.extract_selected_to_dir(dir, &AssetFilter::default())
+    }
+
+    /// Writes the assets matching `filter` to files in `dir`, like [`Package::extract_to_dir`].
+    pub fn extract_selected_to_dir<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        filter: &AssetFilter,
+    ) -> Result<HashMap<Uuid, PathBuf>> {
+        let dir = dir.as_ref();
+        let mut used = HashSet::new();
+        let mut paths = HashMap::new();
+        for asset in self.select(filter) {
+            let mut file_name = asset.file_name();
+            let mut suffix = 0;
+            while !used.insert(file_name.to_lowercase()) {
+                suffix += 1;
+                file_name = asset.file_name_with_suffix(Some(suffix));
+            }
+            let path = dir.join(file_name);
+            if let Some(parent) = path.parent() {
+                DirBuilder::new().recursive(true).create(parent).with_context(|| {
+                    format!("Failed to create directory '{}'", parent.display())
+                })?;
+            }
+            let mut file = BufWriter::new(
+                File::create(&path)
+                    .with_context(|| format!("Failed to create file '{}'", path.display()))?,
+            );
+            asset.write_with_footer(&mut file)?;
+            file.flush()?;
+            paths.insert(asset.id, path);
+        }
+        Ok(paths)
+    }
+
+    /// Loads assets from files in `dir` and its subdirectories, as written by
+    /// [`Package::extract_to_dir`].
+    ///
+    /// Files without a footer are loaded with the type and versions from their RFRM form
+    /// header, and the asset ID from the file name. Assets are sorted by ID.
+    pub fn from_dir<P: AsRef<Path>>(dir: P, e: Endian) -> Result<Package<'static>> {
+        let mut package = Package::default();
+        let mut dirs = vec![dir.as_ref().to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let entries = fs::read_dir(&dir)
+                .with_context(|| format!("Failed to read directory '{}'", dir.display()))?;
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                log::debug!("Loading {}", path.display());
+                let data = fs::read(&path)
+                    .with_context(|| format!("Failed to read file '{}'", path.display()))?;
+                let asset = Asset::read_file(&data, &path, e)
+                    .with_context(|| format!("Failed to load asset '{}'", path.display()))?;
+                package.assets.push(asset.into_owned());
+            }
+        }
+        package.assets.sort_by_key(|a| a.id);
+        Ok(package)
+    }
+
+    /// Checks every asset in package data, collecting problems instead of stopping at the first.
+    ///
+    /// Each asset's data is bounds checked, decompressed and checked against its RFRM form,
+    /// as when reading. META and STRG entries for assets missing from the directory are
+    /// reported too, since reading drops them, as are STRG entries with an asset type that
+    /// doesn't match the directory. Errors reading the table of contents are returned directly.
+    pub fn verify(data: &[u8], e: Endian) -> Result<Vec<VerifyIssue>> {
+        let (tocc_data, _) = slice_tocc(data, e)?;
+        let toc = TableOfContents::read(tocc_data, e)?;
+        let options = ReadOptions::default();
+        let mut issues: Vec<VerifyIssue> = toc
+            .directory
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                read_asset_data(data, entry, e, &options).err().map(|err| VerifyIssue {
+                    asset_id: entry.asset_id,
+                    reason: format!("{:#}", err),
+                })
+            })
+            .collect();
+
+        let ids: HashSet<Uuid> = toc.directory.entries.iter().map(|entry| entry.asset_id).collect();
+        let mut orphans: Vec<(Uuid, &str)> = toc
+            .meta
+            .keys()
+            .map(|&id| (id, "META"))
+            .chain(toc.names.keys().map(|&id| (id, "STRG")))
+            .filter(|(id, _)| !ids.contains(id))
+            .collect();
+        orphans.sort();
+        issues.extend(orphans.into_iter().map(|(asset_id, chunk)| VerifyIssue {
+            asset_id,
+            reason: format!("{} entry for an asset not in the asset directory", chunk),
+        }));
+        issues.extend(toc.directory.entries.iter().filter_map(|entry| {
+            let kind = *toc.name_kinds.get(&entry.asset_id)?;
+            (kind != entry.asset_type).then(|| VerifyIssue {
+                asset_id: entry.asset_id,
+                reason: format!(
+                    "STRG asset type {} does not match asset directory type {}",
+                    kind, entry.asset_type
+                ),
+            })
+        }));
+
+        let unreferenced = ids
+            .iter()
+            .filter(|id| !toc.meta.contains_key(id) && !toc.names.contains_key(id))
+            .count();
+        if unreferenced > 0 && (!toc.meta.is_empty() || !toc.names.is_empty()) {
+            log::warn!("{} assets have neither a META nor a STRG entry", unreferenced);
+        }
+        Ok(issues)
+    }
+
+    /// Reads the versions of each asset from the asset directory, without reading asset data.
+    pub fn asset_versions(data: &[u8], e: Endian) -> Result<HashMap<Uuid, (u32, u32)>> {
+        let directory = read_asset_directory(data, e)?;
+        Ok(directory
+            .entries
+            .iter()
+            .map(|entry| (entry.asset_id, (entry.version, entry.other_version)))
+            .collect())
+    }
+
+    /// Counts assets and sums their sizes from the asset directory, without reading asset data.
+    /// Sizes come straight from the directory, so totals saturate rather than overflow.
+    pub fn stats(data: &[u8], e: Endian) -> Result<PackageStats> {
+        let directory = read_asset_directory(data, e)?;
+        let mut stats = PackageStats::default();
+        for entry in &directory.entries {
+            stats.asset_count += 1;
+            stats.total_compressed = stats.total_compressed.saturating_add(entry.size);
+            stats.total_decompressed =
+                stats.total_decompressed.saturating_add(entry.decompressed_size);
+            let (count, bytes) = stats.by_type.entry(entry.asset_type).or_default();
+            *count += 1;
+            *bytes = bytes.saturating_add(entry.decompressed_size);
+        }
+        Ok(stats)
+    }
+
+    /// Reads the metadata and name of a single asset, parsing only the META and STRG chunks.
+    pub fn asset_meta_and_name(
+        data: &[u8],
+        id: Uuid,
+        e: Endian,
+    ) -> Result<(Option<Vec<u8>>, Option<String>)> {
+        let (mut tocc_data, _) = slice_tocc(data, e)?;
+        let mut meta = None;
+        let mut name = None;
+        while !is_padding(tocc_data) {
+            let (desc, chunk_data, remain) = ChunkDescriptor::slice(tocc_data, e)?;
+            match desc.id {
+                K_CHUNK_META => {
+                    meta = read_metadata(chunk_data, e)?
+                        .into_iter()
+                        .find(|&(asset_id, _)| asset_id == id)
+                        .map(|(_, data)| data.to_vec());
+                }
+                K_CHUNK_STRG => {
+                    let chunk: StringTable = Cursor::new(chunk_data).read_type(e)?;
+                    if let Some(entry) =
+                        chunk.entries.into_iter().find(|entry| entry.asset_id == id)
+                    {
+                        name = Some(entry.decode_name_or_warn().0);
+                    }
+                }
+                _ => {}
+            }
+            tocc_data = remain;
+        }
+        Ok((meta, name))
+    }
+
+    /// `unk` and `skip` fields of a TOCC chunk, as read from package data. Packages not read
+    /// from data write `unk` 1 and `skip` 0.
+    pub fn chunk_fields(&self, id: FourCC) -> Option<(u32, u64)> {
+        self.source.as_ref().and_then(|source| source.toc.chunk_fields.get(&id).copied())
+    }
+
+    /// Asset with the given ID.
+    ///
+    /// Assets are kept in ID order, so this is a binary search. If `assets` was reordered, IDs
+    /// not found by the search fall back to a linear scan.
+    pub fn asset_by_id(&self, id: Uuid) -> Option<&Asset<'_>> {
+        match self.assets.binary_search_by_key(&id, |asset| asset.id) {
+            Ok(index) => Some(&self.assets[index]),
+            Err(_) => self.assets.iter().find(|asset| asset.id == id),
+        }
+    }
+
+    /// First asset with exactly the given name.
+    pub fn asset_by_name(&self, name: &str) -> Option<&Asset<'_>> {
+        self.assets.iter().find(|asset| asset.name.as_deref() == Some(name))
+    }
+
+    /// Assets of the given type, in asset order.
+    pub fn assets_by_type(&self, kind: FourCC) -> impl Iterator<Item = &Asset<'_>> {
+        self.assets.iter().filter(move |asset| asset.kind == kind)
+    }
+
+    /// Finds assets with names matching `pattern`, ignoring case.
+    /// See [`Package::find_assets_with_case`].
+    pub fn find_assets(&self, pattern: &str) -> Vec<&Asset<'_>> {
+        self.find_assets_with_case(pattern, false)
+    }
+
+    /// Finds assets with names matching `pattern`. Patterns containing `*` or `?` are matched
+    /// as globs against the whole name, and other patterns match any part of the name.
+    pub fn find_assets_with_case(&self, pattern: &str, case_sensitive: bool) -> Vec<&Asset<'_>> {
+        self.assets
+            .iter()
+            .filter(|asset| {
+                asset
+                    .name
+                    .as_deref()
+                    .is_some_and(|name| glob::matches(pattern, name, case_sensitive))
+            })
+            .collect()
+    }
+
+    /// Assets matching all criteria of `filter`.
+    pub fn select(&self, filter: &AssetFilter) -> Vec<&Asset<'_>> {
+        self.assets.iter().filter(|asset| filter.matches(asset)).collect()
+    }
+
+    /// Whether the asset's name in the package data wasn't valid, and invalid bytes were
+    /// replaced with U+FFFD when decoding it.
+    pub fn is_name_lossy(&self, id: Uuid) -> bool {
+        self.source.as_ref().is_some_and(|source| source.toc.lossy_names.contains(&id))
+    }
+
+    /// Encoding of the asset's name in the package data this package was read from, used when
+    /// writing the name. Names of new assets are UTF-8.
+    pub fn name_encoding(&self, id: Uuid) -> NameEncoding {
+        self.source.as_ref().map_or(NameEncoding::Utf8, |source| source.toc.name_encoding(id))
+    }
+
+    /// Layout of the asset names in the package data this package was read from.
+    ///
+    /// Only the inline STRG chunk is known to be used by packages; names from any layout
+    /// are mapped to [`Asset::name`]. Returns `None` for packages not read from package data.
+    pub fn name_table_kind(&self) -> Option<NameTableKind> {
+        self.source.as_ref().map(|source| source.toc.name_table)
+    }
+
+    /// Estimates the time spent decompressing assets when loading the package, given a
+    /// decompression throughput in MB/s. Uncompressed assets cost nothing.
+    ///
+    /// This is a simple linear model based on each asset's compression mode and decompressed size.
+    pub fn estimate_load_cost(&self, decompress_mb_per_s: f64) -> LoadCost {
+        let mut cost = LoadCost::default();
+        for asset in self.assets.iter().filter(|a| a.info.compression_mode != 0) {
+            cost.compressed_assets += 1;
+            cost.decompressed_bytes += asset.decompressed_size();
+        }
+        if cost.decompressed_bytes > 0 {
+            cost.seconds = cost.decompressed_bytes as f64 / (decompress_mb_per_s * 1_000_000.0);
+        }
+        cost
+    }
+
+    /// Compresses each asset as it would be written with `options`, then decompresses it and
+    /// checks that the result matches the asset data.
+    ///
+    /// This compresses every asset, so it's as expensive as writing the package.
+    pub fn verify_recompression(&self, options: &WriteOptions) -> Result<()> {
+        self.verify_recompression_with(options, compress_asset)
+    }
+
+    /// [`Package::verify_recompression`] with the given compressor, as [`compress_asset`].
+    fn verify_recompression_with<C>(&self, options: &WriteOptions, compress: C) -> Result<()>
+    where C: for<'d> Fn(&'d [u8], CompressionChoice) -> Result<(u32, Cow<'d, [u8]>)> {
+        for asset in &self.assets {
+            let asset_data = asset.data()?;
+            let (mode, block) = compress(asset_data, options.choice(asset))?;
+            if mode == 0 {
+                continue;
+            }
+            let (_, data) =
+                decompress_buffer(&block, asset_data.len() as u64).with_context(|| {
+                    format!("Failed to decompress asset {} (mode {})", asset.id, mode)
+                })?;
+            ensure!(
+                data == asset_data,
+                "Asset {} does not match after compression with mode {}",
+                asset.id,
+                mode
+            );
+        }
+        Ok(())
+    }
+
+    /// Writes the package, then reads it back and checks that the written assets match, along
+    /// with the PACK and TOCC form header fields of the package it was read from.
+    ///
+    /// This costs an additional read pass, and is intended to catch writer bugs early.
+    /// The package is written to `w` only once it has been verified.
+    pub fn write_verified<W: Write>(&self, w: &mut W, e: Endian) -> Result<()> {
+        let written = self.to_vec(e)?;
+        self.verify_written(&written, e)?;
+        w.write_all(&written)?;
+        Ok(())
+    }
+
+    fn verify_written(&self, written: &[u8], e: Endian) -> Result<()> {
+        let package = Package::read(written, e).context("Failed to read written package")?;
+        ensure!(
+            package.assets.len() == self.assets.len(),
+            "Written package has {} assets, expected {}",
+            package.assets.len(),
+            self.assets.len()
+        );
+        if let (Some(expected), Some(actual)) = (&self.source, &package.source) {
+            ensure!(
+                actual.forms == expected.forms,
+                "Written form header fields {:?} do not match {:?}",
+                actual.forms,
+                expected.forms
+            );
+        }
+        for (expected, actual) in self.assets.iter().zip(&package.assets) {
+            ensure!(
+                actual.id == expected.id,
+                "Written asset {} does not match expected asset {}",
+                actual.id,
+                expected.id
+            );
+            ensure!(
+                actual.kind == expected.kind && actual.data()? == expected.data()?,
+                "Written asset {} contents do not match",
+                actual.id
+            );
+        }
+        Ok(())
+    }
+}
+
+/// TOCC tables and asset data blocks to be written
+struct WritePlan<'a> {
+    directory: AssetDirectory,
+    string_table: StringTable,
+    meta: Vec<(Uuid, &'a [u8])>,
+    data: Vec<Cow<'a, [u8]>>,
+    /// Unrecognized TOCC chunks, written after STRG
+    unknown: &'a [(FourCC, Vec<u8>)],
+    /// `unk` and `skip` of each chunk, if read from package data
+    chunk_fields: Option<&'a HashMap<FourCC, (u32, u64)>>,
+    /// PACK and TOCC form header fields
+    forms: FormFields,
+    /// Original TOCC form and the offset of its ADIR chunk data, written in place of the tables
+    raw_tocc: Option<(&'a [u8], u64)>,
+    /// Write identical data blocks once
+    deduplicate: bool,
+    asset_alignment: u64,
+    final_alignment: u64,
+    pad_byte: u8,
+    /// Leave out empty META and STRG chunks, if not read from package data
+    omit_empty_tables: bool,
+}
+
+impl WritePlan<'_> {
+    /// Empty plan for a new package, with the data layout and table settings from `options`.
+    fn new(options: &WriteOptions) -> Self {
+        Self {
+            directory: AssetDirectory::default(),
+            string_table: StringTable::default(),
+            meta: vec![],
+            data: vec![],
+            unknown: &[],
+            chunk_fields: None,
+            forms: FormFields::default(),
+            raw_tocc: None,
+            deduplicate: options.deduplicate,
+            asset_alignment: options.asset_alignment,
+            final_alignment: options.final_alignment,
+            pad_byte: options.pad_byte,
+            omit_empty_tables: options.omit_empty_tables,
+        }
+    }
+
+    /// META and STRG tables to write. Empty tables are written if the source package had
+    /// them, or for new packages unless `omit_empty_tables` is set.
+    #[allow(clippy::type_complexity)]
+    fn tables(&self) -> (Option<&[(Uuid, &[u8])]>, Option<&StringTable>) {
+        let keep = |id: FourCC, empty: bool| {
+            !empty
+                || match self.chunk_fields {
+                    Some(fields) => fields.contains_key(&id),
+                    None => !self.omit_empty_tables,
+                }
+        };
+        (
+            keep(K_CHUNK_META, self.meta.is_empty()).then_some(self.meta.as_slice()),
+            keep(K_CHUNK_STRG, self.string_table.entries.is_empty()).then_some(&self.string_table),
+        )
+    }
+
+    /// Writes the package, with asset data blocks in the given order.
+    fn write<W: Write + Seek>(mut self, w: &mut W, e: Endian, order: &[usize]) -> Result<()> {
+        let mut adir_pos = 0;
+        self.forms.pack_form(0).write(w, e, |w| {
+            if let Some((raw, adir_offset)) = self.raw_tocc {
+                adir_pos = w.stream_position()? + adir_offset;
+                w.write_all(raw)?;
+            } else {
+                self.forms.tocc_form().write(w, e, |w| {
+                    let (meta, string_table) = self.tables();
+                    adir_pos = write_toc_chunks(
+                        w,
+                        e,
+                        &self.directory,
+                        meta,
+                        string_table,
+                        self.unknown,
+                        self.chunk_fields,
+                    )?;
+                    Ok(())
+                })?;
+            }
+            let mut written: HashMap<&[u8], u64> = HashMap::new();
+            for &index in order {
+                let data = self.data[index].as_ref();
+                if self.deduplicate {
+                    if let Some(&offset) = written.get(data) {
+                        self.directory.entries[index].offset = offset;
+                        continue;
+                    }
+                }
+                let pos = w.stream_position()?;
+                let offset = align(pos, self.asset_alignment);
+                w.write_all(&vec![self.pad_byte; (offset - pos) as usize])?;
+                if self.deduplicate {
+                    written.insert(data, offset);
+                }
+                self.directory.entries[index].offset = offset;
+                w.write_all(data)?;
+            }
+            Ok(())
+        })?;
+
+        // Write updated ADIR offsets
+        let pos = w.stream_position()?;
+        w.seek(SeekFrom::Start(adir_pos))?;
+        w.write_type(&self.directory, e)?;
+        w.seek(SeekFrom::Start(pos))?;
+
+        let aligned_end = align(pos, self.final_alignment);
+        w.write_all(&vec![self.pad_byte; (aligned_end - pos) as usize])?;
+        Ok(())
+    }
+
+    /// Size of the PACK and TOCC headers and TOCC chunks.
+    fn toc_size(&self) -> u64 {
+        if let Some((raw, _)) = self.raw_tocc {
+            return FORM_DESCRIPTOR_SIZE as u64 + raw.len() as u64;
+        }
+        let (meta_table, string_table) = self.tables();
+        // Chunk header + entry count + asset type, ID, versions, offset and sizes
+        let adir = CHUNK_DESCRIPTOR_SIZE as u64 + 4 + self.directory.entries.len() as u64 * 52;
+        // Chunk header + entry count + ID and offset, followed by size-prefixed metadata
+        let meta = meta_table.map_or(0, |meta| {
+            CHUNK_DESCRIPTOR_SIZE as u64
+                + 4
+                + meta.iter().map(|(_, data)| 20 + 4 + data.len() as u64).sum::<u64>()
+        });
+        // Chunk header + entry count + asset type, ID and size-prefixed name
+        let strg = string_table.map_or(0, |table| {
+            CHUNK_DESCRIPTOR_SIZE as u64
+                + 4
+                + table
+                    .entries
+                    .iter()
+                    .map(|entry| 4 + 16 + 4 + entry.name.len() as u64)
+                    .sum::<u64>()
+        });
+        // Chunk header + raw chunk data
+        let unknown = self
+            .unknown
+            .iter()
+            .map(|(_, data)| CHUNK_DESCRIPTOR_SIZE as u64 + data.len() as u64)
+            .sum::<u64>();
+        // Padding declared by chunk skip fields
+        let skip = self.chunk_fields.map_or(0, |fields| {
+            [
+                Some(K_CHUNK_ADIR),
+                meta_table.map(|_| K_CHUNK_META),
+                string_table.map(|_| K_CHUNK_STRG),
+            ]
+            .iter()
+            .flatten()
+            .chain(self.unknown.iter().map(|(id, _)| id))
+            .filter_map(|id| fields.get(id))
+            .map(|&(_, skip)| skip)
+            .sum::<u64>()
+        });
+        FORM_DESCRIPTOR_SIZE as u64 * 2 + skip + adir + meta + strg + unknown
+    }
+
+    /// Upper bound of the written package size, including alignment.
+    fn package_size(&self) -> u64 {
+        let padding = self.asset_alignment.saturating_sub(1);
+        let data = self.data.iter().map(|data| data.len() as u64 + padding).sum::<u64>();
+        align(self.toc_size() + data, self.final_alignment)
+    }
+}
+
+/// Maps `f` over `items`, calling `progress` with the index of each item as it completes.
+/// Results are returned in item order. The first error from `f` or `progress` is returned,
+/// and stops mapping further items.
+///
+/// With the `rayon` feature, items are mapped across threads, and `progress` is called on
+/// the calling thread in the order items complete.
+#[cfg(feature = "rayon")]
+fn map_with_progress<'a, T, R, F, P>(items: &'a [T], f: F, mut progress: P) -> Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&'a T) -> Result<R> + Sync,
+    P: FnMut(usize) -> Result<()>,
+{
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    };
+
+    use rayon::prelude::*;
+
+    let cancelled = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            items.par_iter().enumerate().for_each_with(sender, |sender, (index, item)| {
+                if !cancelled.load(Ordering::Relaxed) {
+                    // Only fails once the receiver stopped after an error
+                    let _ = sender.send((index, f(item)));
+                }
+            });
+        });
+        for (index, result) in receiver {
+            match result.and_then(|result| progress(index).map(|_| result)) {
+                Ok(result) => results[index] = Some(result),
+                Err(e) => {
+                    cancelled.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    })?;
+    Ok(results.into_iter().map(|result| result.expect("item not mapped")).collect())
+}
+
+#[cfg(not(feature = "rayon"))]
+fn map_with_progress<'a, T, R, F, P>(items: &'a [T], f: F, mut progress: P) -> Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&'a T) -> Result<R> + Sync,
+    P: FnMut(usize) -> Result<()>,
+{
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let result = f(item)?;
+            progress(index)?;
+            Ok(result)
+        })
+        .collect()
+}
+
+/// Rounds `pos` up to a multiple of `alignment`. Alignments of 0 and 1 leave it unchanged.
+fn align(pos: u64, alignment: u64) -> u64 {
+    match alignment {
+        0 | 1 => pos,
+        _ => match pos % alignment {
+            0 => pos,
+            rem => pos + (alignment - rem),
+        },
+    }
+}
+
+/// Detects the endianness of package data from the PACK form version and size.
+fn detect_endian(data: &[u8]) -> Result<Endian> {
+    for e in [Endian::Little, Endian::Big] {
+        let Ok(pack) = FormDescriptor::read(&mut Cursor::new(data), e) else {
+            continue;
+        };
+        if pack.id == K_FORM_PACK
+            && pack.version_a == 1
+            && pack.size <= (data.len() - FORM_DESCRIPTOR_SIZE) as u64
+        {
+            return Ok(e);
+        }
+    }
+    bail!("Data is not a little- or big-endian PACK");
+}
+
+/// Replaces characters that aren't allowed in file names on common filesystems with `_`.
+fn sanitize_path_component(component: &str) -> String {
+    if matches!(component, "" | "." | "..") {
+        return "_".to_string();
+    }
+    component
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"\\|?*".contains(c) { '_' } else { c })
+        .collect()
+}
+
+/// Slices the PACK and TOCC form headers, returning the TOCC contents along with
+/// the size of the package data up to the end of the TOCC.
+fn slice_tocc(data: &[u8], e: Endian) -> Result<(&[u8], usize)> {
+    let (pack, pack_data, _) = FormDescriptor::slice(data, e)?;
+    ensure!(pack.id == K_FORM_PACK);
+    ensure!(pack.version_a == 1);
+    log::debug!("PACK: {:?}", pack);
+    let (tocc, tocc_data, _) = FormDescriptor::slice(pack_data, e)?;
+    ensure!(tocc.id == K_FORM_TOCC);
+    ensure!(tocc.version_a == 3);
+    log::debug!("TOCC: {:?}", tocc);
+    Ok((tocc_data, FORM_DESCRIPTOR_SIZE * 2 + tocc_data.len()))
+}
+
+impl<'a> TableOfContents<'a> {
+    /// Encoding of an asset's name in the STRG chunk.
+    fn name_encoding(&self, id: Uuid) -> NameEncoding {
+        self.name_encodings.get(&id).copied().unwrap_or_default()
+    }
+
+    /// Parses the ADIR, META and STRG chunks of a TOCC form. Chunks may appear in any order;
+    /// assets are only resolved once all of them are read.
+    fn read(mut tocc_data: &'a [u8], e: Endian) -> Result<Self> {
+        let mut adir: Option<AssetDirectory> = None;
+        let mut meta: HashMap<Uuid, Cow<[u8]>> = HashMap::new();
+        let mut strg: HashMap<Uuid, String> = HashMap::new();
+        let mut name_table = NameTableKind::None;
+        let mut unknown = Vec::new();
+        let mut chunk_fields = HashMap::new();
+        let mut lossy_names = HashSet::new();
+        let mut name_kinds = HashMap::new();
+        let mut name_encodings = HashMap::new();
+        while !is_padding(tocc_data) {
+            let (desc, chunk_data, remain) = ChunkDescriptor::slice(tocc_data, e)?;
+            let mut reader = Cursor::new(chunk_data);
+            log::debug!("{:?} data size {}", desc, chunk_data.len());
+            chunk_fields.insert(desc.id, (desc.unk, desc.skip));
+            match desc.id {
+                K_CHUNK_ADIR => {
+                    // A second directory would make the result depend on chunk order
+                    ensure!(adir.is_none(), "Multiple asset directories in TOCC");
+                    let chunk: AssetDirectory = reader.read_type(e)?;
+                    log::debug!("- {} assets", chunk.entries.len());
+                    for entry in &chunk.entries {
+                        log::trace!("- {:?}", entry);
+                    }
+                    adir = Some(chunk);
+                }
+                K_CHUNK_META => {
+                    let entries = read_metadata(chunk_data, e)?;
+                    log::debug!("- {} metadata entries", entries.len());
+                    for (asset_id, meta_data) in entries {
+                        meta.insert(asset_id, Cow::Borrowed(meta_data));
+                    }
+                }
+                K_CHUNK_STRG => {
+                    let chunk: StringTable = reader.read_type(e)?;
+                    log::debug!("- {} names", chunk.entries.len());
+                    for entry in chunk.entries {
+                        log::trace!("- {:?}", entry);
+                        let (name, lossy) = entry.decode_name_or_warn();
+                        match strg.entry(entry.asset_id) {
+                            hash_map::Entry::Occupied(existing) => log::warn!(
+                                "Asset {} has multiple names, keeping {:?} and ignoring {:?}",
+                                entry.asset_id,
+                                existing.get(),
+                                name
+                            ),
+                            hash_map::Entry::Vacant(slot) => {
+                                slot.insert(name);
+                                name_kinds.insert(entry.asset_id, entry.kind);
+                                if lossy {
+                                    lossy_names.insert(entry.asset_id);
+                                }
+                                let encoding = entry.name_encoding();
+                                if encoding != NameEncoding::Utf8 {
+                                    name_encodings.insert(entry.asset_id, encoding);
+                                }
+                            }
+                        }
+                    }
+                    name_table = NameTableKind::StringTable;
+                }
+                kind => {
+                    log::warn!(
+                        "Preserving unknown TOCC chunk {:?} ({} bytes)",
+                        kind,
+                        chunk_data.len()
+                    );
+                    log::trace!("- {:02X?}", chunk_data);
+                    unknown.push((kind, chunk_data.to_vec()));
+                }
+            }
+            tocc_data = remain;
+        }
+
+        let Some(adir) = adir else {
+            bail!("Failed to locate asset directory");
+        };
+        Ok(Self {
+            directory: adir,
+            meta,
+            names: strg,
+            name_kinds,
+            lossy_names,
+            name_encodings,
+            name_table,
+            unknown,
+            chunk_fields,
+        })
+    }
+
+    /// Combines an asset directory entry with its data block, name and metadata.
+    ///
+    /// Uncompressed data is checked against its RFRM form right away. Compressed blocks are
+    /// kept to be decompressed and checked on first access with [`Asset::data`].
+    fn asset(
+        &self,
+        entry: &AssetDirectoryEntry,
+        block: Cow<'a, [u8]>,
+        options: &Arc<ReadOptions>,
+    ) -> Result<Asset<'a>> {
+        let (compression_mode, _) = block_mode(&block, entry, options)?;
+        let (data, compressed, pending) = if compression_mode == 0 {
+            let data = match block {
+                Cow::Borrowed(block) => decode_asset_data(block, entry, options)?.1,
+                Cow::Owned(block) => {
+                    Cow::Owned(decode_asset_data(&block, entry, options)?.1.into_owned())
+                }
+            };
+            (Some(data), None, None)
+        } else {
+            let pending = PendingData {
+                entry: entry.clone(),
+                options: options.clone(),
+                decompressed: OnceLock::new(),
+            };
+            (None, Some(block), Some(pending))
+        };
+        Ok(Asset {
+            id: entry.asset_id,
+            kind: entry.asset_type,
+            name: self.names.get(&entry.asset_id).cloned(),
+            data,
+            compressed,
+            pending,
+            meta: self.meta.get(&entry.asset_id).cloned(),
+            info: AssetInfo { id: entry.asset_id, compression_mode, orig_offset: entry.offset },
+            version: entry.version,
+            other_version: entry.other_version,
+        })
+    }
+
+    fn into_owned(self) -> TableOfContents<'static> {
+        TableOfContents {
+            directory: self.directory,
+            meta: self
+                .meta
+                .into_iter()
+                .map(|(id, data)| (id, Cow::Owned(data.into_owned())))
+                .collect(),
+            names: self.names,
+            name_kinds: self.name_kinds,
+            lossy_names: self.lossy_names,
+            name_encodings: self.name_encodings,
+            name_table: self.name_table,
+            unknown: self.unknown,
+            chunk_fields: self.chunk_fields,
+        }
+    }
+}
+
+/// Reads only the ADIR chunk of a package.
+fn read_asset_directory(data: &[u8], e: Endian) -> Result<AssetDirectory> {
+    let (mut tocc_data, _) = slice_tocc(data, e)?;
+    while !is_padding(tocc_data) {
+        let (desc, chunk_data, remain) = ChunkDescriptor::slice(tocc_data, e)?;
+        if desc.id == K_CHUNK_ADIR {
+            return Ok(Cursor::new(chunk_data).read_type(e)?);
+        }
+        tocc_data = remain;
+    }
+    bail!("Failed to locate asset directory");
+}
+
+/// Parses a META chunk, returning the metadata of each asset.
+///
+/// Each entry's data is prefixed with its size, so entries are read independently of table
+/// order, and sizes are never computed from the distance between offsets.
+fn read_metadata(chunk_data: &[u8], e: Endian) -> Result<Vec<(Uuid, &[u8])>> {
+    let chunk: MetadataTable = Cursor::new(chunk_data).read_type(e)?;
+    let mut result = Vec::with_capacity(chunk.entries.len());
+    for entry in chunk.entries {
+        let out_of_bounds = || format!("Asset {} metadata out of bounds", entry.asset_id);
+        // The size prefix is in package byte order, like the table
+        let size = slice_range(chunk_data, entry.offset as u64, 4).with_context(out_of_bounds)?;
+        let meta_size: u32 = Cursor::new(size).read_type(e)?;
+        let meta_data = slice_range(chunk_data, entry.offset as u64 + 4, meta_size as u64)
+            .with_context(out_of_bounds)?;
+        log::trace!("- {:?} (size {:#X})", entry, meta_size);
+        result.push((entry.asset_id, meta_data));
+    }
+    Ok(result)
+}
+
+/// Writes the ADIR, META and STRG chunks, returning the position of the asset directory.
+fn write_toc_chunks<W: Write + Seek>(
+    w: &mut W,
+    e: Endian,
+    asset_directory: &AssetDirectory,
+    meta: Option<&[(Uuid, &[u8])]>,
+    string_table: Option<&StringTable>,
+    unknown: &[(FourCC, Vec<u8>)],
+    chunk_fields: Option<&HashMap<FourCC, (u32, u64)>>,
+) -> Result<u64> {
+    let chunk = |id: FourCC| {
+        let (unk, skip) =
+            chunk_fields.and_then(|fields| fields.get(&id)).copied().unwrap_or((1, 0));
+        ChunkDescriptor { id, size: 0, unk, skip }
+    };
+    let mut adir_pos = 0;
+    chunk(K_CHUNK_ADIR).write(w, e, |w| {
+        adir_pos = w.stream_position()?;
+        w.write_type(asset_directory, e)?;
+        Ok(())
+    })?;
+    if let Some(meta) = meta {
+        chunk(K_CHUNK_META).write(w, e, |w| {
+            #[allow(clippy::needless_update)]
+            let mut metadata = MetadataTable {
+                entries: meta
+                    .iter()
+                    .map(|&(asset_id, _)| MetadataTableEntry { asset_id, offset: 0 })
+                    .collect(),
+                ..Default::default()
+            };
+            let start = w.stream_position()?;
+            w.write_type(&metadata, e)?;
+            for (entry, (_, data)) in metadata.entries.iter_mut().zip(meta) {
+                entry.offset = (w.stream_position()? - start) as u32;
+                w.write_type(&(data.len() as u32), e)?;
+                w.write_all(data)?;
+            }
+            let end = w.stream_position()?;
+            w.seek(SeekFrom::Start(start))?;
+            w.write_type(&metadata, e)?;
+            w.seek(SeekFrom::Start(end))?;
+            Ok(())
+        })?;
+    }
+    if let Some(string_table) = string_table {
+        chunk(K_CHUNK_STRG).write(w, e, |w| {
+            w.write_type(string_table, e)?;
+            Ok(())
+        })?;
+    }
+    for (id, data) in unknown {
+        chunk(*id).write(w, e, |w| {
+            w.write_all(data)?;
+            Ok(())
+        })?;
+    }
+    Ok(adir_pos)
+}
+
+/// Slices `size` bytes at `offset`, or returns `None` if they're out of bounds.
+fn slice_range(data: &[u8], offset: u64, size: u64) -> Option<&[u8]> {
+    let end = usize::try_from(offset.checked_add(size)?).ok()?;
+    data.get(usize::try_from(offset).ok()?..end)
+}
+
+/// Strips the chunk header wrapping an asset data block, if present.
+///
+/// ADIR offsets usually point directly at an RFRM form, or at a compressed block starting
+/// with its 4-byte compression mode. Some packages instead wrap the block in a chunk header,
+/// followed by `skip` bytes of padding before the payload. A block is only treated as wrapped
+/// when the chunk header accounts for the block's entire size.
+fn unwrap_asset_block(block: &[u8], e: Endian) -> Result<&[u8]> {
+    if block.len() < CHUNK_DESCRIPTOR_SIZE || peek_four_cc(block) == K_CHUNK_RFRM {
+        return Ok(block);
+    }
+    if peek_compression_mode(block).is_some() {
+        return Ok(block);
+    }
+    let desc: ChunkDescriptor = Cursor::new(block).read_type(e)?;
+    let payload_start = (CHUNK_DESCRIPTOR_SIZE as u64).checked_add(desc.skip);
+    match payload_start.and_then(|start| start.checked_add(desc.size).map(|end| (start, end))) {
+        Some((start, end)) if end == block.len() as u64 => {
+            log::debug!("Unwrapped asset chunk {:?}", desc);
+            Ok(&block[start as usize..])
+        }
+        _ => Ok(block),
+    }
+}
+
+/// Checks that every asset directory entry's data is within the package data.
+fn check_entry_bounds(directory: &AssetDirectory, data_len: usize) -> Result<()> {
+    for entry in &directory.entries {
+        if entry.offset.checked_add(entry.size).is_none_or(|end| end > data_len as u64) {
+            return Err(PackError::EntryOutOfBounds {
+                asset_id: entry.asset_id,
+                offset: entry.offset,
+                size: entry.size,
+                data_len: data_len as u64,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Data block of an asset directory entry, without any chunk header wrapping it.
+fn entry_block<'a>(data: &'a [u8], entry: &AssetDirectoryEntry, e: Endian) -> Result<&'a [u8]> {
+    let block = slice_range(data, entry.offset, entry.size)
+        .with_context(|| format!("Asset {} data out of bounds", entry.asset_id))?;
+    unwrap_asset_block(block, e)
+}
+
+/// Reads and decompresses the data of an asset directory entry, returning the compression mode
+/// and decompressed data.
+fn read_asset_data<'a>(
+    data: &'a [u8],
+    entry: &AssetDirectoryEntry,
+    e: Endian,
+    options: &ReadOptions,
+) -> Result<(u32, Cow<'a, [u8]>)> {
+    decode_asset_data(entry_block(data, entry, e)?, entry, options)
+}
+
+/// Decompresses an asset data block and checks it against its RFRM form, returning the
+/// compression mode and decompressed data.
+fn decode_asset_data<'a>(
+    block: &'a [u8],
+    entry: &AssetDirectoryEntry,
+    options: &ReadOptions,
+) -> Result<(u32, Cow<'a, [u8]>)> {
+    let (compression_mode, data) = decompress_asset(block, entry, options)?;
+
+    // Validate RFRM
+    {
+        let (form, _, _) = FormDescriptor::slice(&data, Endian::Little)?;
+        if entry.asset_type != form.id {
+            return Err(PackError::FormIdMismatch {
+                asset_id: entry.asset_id,
+                expected: entry.asset_type,
+                found: form.id,
+            }
+            .into());
+        }
+        if (entry.version, entry.other_version) != (form.version_a, form.version_b) {
+            return Err(PackError::VersionMismatch {
+                asset_id: entry.asset_id,
+                expected: (entry.version, entry.other_version),
+                found: (form.version_a, form.version_b),
+            }
+            .into());
+        }
+        let form_len = form.size + 32 /* RFRM */;
+        if entry.decompressed_size != form_len {
+            let Some(checksum) = options.trailing_checksum else {
+                return Err(PackError::SizeMismatch {
+                    asset_id: entry.asset_id,
+                    expected: entry.decompressed_size,
+                    found: form_len,
+                }
+                .into());
+            };
+            ensure!(
+                entry.decompressed_size == form_len + checksum.len,
+                "Asset {} size {:#X} does not match RFRM size {:#X} with {} byte checksum",
+                entry.asset_id,
+                entry.decompressed_size,
+                form_len,
+                checksum.len
+            );
+            if checksum.verify_crc32 {
+                ensure!(checksum.len == 4, "CRC-32 checksum must be 4 bytes");
+                let (form_data, stored) = data.split_at(form_len as usize);
+                let expected = u32::from_le_bytes(stored.try_into().unwrap());
+                let actual = crc32fast::hash(form_data);
+                ensure!(
+                    actual == expected,
+                    "Asset {} checksum mismatch: expected {:#010X}, got {:#010X}",
+                    entry.asset_id,
+                    expected,
+                    actual
+                );
+            }
+        }
+    }
+    if options.validate_forms {
+        let max_depth = options.max_form_depth.unwrap_or(DEFAULT_MAX_FORM_DEPTH);
+        let remain = validate_form(&data, entry.asset_id, 1, max_depth)?;
+        ensure!(
+            options.trailing_checksum.is_some() || remain.is_empty(),
+            "Asset {} has {:#X} bytes following the RFRM form",
+            entry.asset_id,
+            remain.len()
+        );
+    }
+    Ok((compression_mode, data))
+}
+
+/// Recursively validates the bounds of a form and the chunks and forms nested in it,
+/// returning the data following the form.
+fn validate_form(data: &[u8], asset_id: Uuid, depth: usize, max_depth: usize) -> Result<&[u8]> {
+    if depth > max_depth {
+        return Err(PackError::TooDeep { asset_id, max_depth }.into());
+    }
+    let form = FormDescriptor::read(&mut Cursor::new(data), Endian::Little)?;
+    let end = usize::try_from(form.size)
+        .ok()
+        .and_then(|size| size.checked_add(FORM_DESCRIPTOR_SIZE))
+        .filter(|&end| end <= data.len())
+        .with_context(|| format!("Asset {} form {:?} out of bounds", asset_id, form.id))?;
+    let mut form_data = &data[FORM_DESCRIPTOR_SIZE..end];
+    while !is_padding(form_data) {
+        if form_data.len() >= 4 && peek_four_cc(form_data) == K_CHUNK_RFRM {
+            form_data = validate_form(form_data, asset_id, depth + 1, max_depth)?;
+            continue;
+        }
+        let desc: ChunkDescriptor = Cursor::new(form_data).read_type(Endian::Little)?;
+        let chunk_end = (CHUNK_DESCRIPTOR_SIZE as u64)
+            .checked_add(desc.skip)
+            .and_then(|start| start.checked_add(desc.size))
+            .filter(|&end| end <= form_data.len() as u64)
+            .with_context(|| {
+                format!(
+                    "Asset {} chunk {:?} in form {:?} out of bounds",
+                    asset_id, desc.id, form.id
+                )
+            })?;
+        form_data = &form_data[chunk_end as usize..];
+    }
+    Ok(&data[end..])
+}
+
+/// Reads the compression mode header of a block, if it holds a known mode.
+fn peek_compression_mode(block: &[u8]) -> Option<u32> {
+    let mode = u32::from_le_bytes(block.get(0..4)?.try_into().unwrap());
+    is_supported_mode(mode).then_some(mode)
+}
+
+/// Decompresses an asset data block, returning the compression mode and decompressed data.
+fn decompress_asset<'a>(
+    block: &'a [u8],
+    entry: &AssetDirectoryEntry,
+    options: &ReadOptions,
+) -> Result<(u32, Cow<'a, [u8]>)> {
+    let (mode, has_header) = block_mode(block, entry, options)?;
+    if block.len() as u64 == entry.decompressed_size && !has_header {
+        return Ok((0, Cow::Borrowed(block)));
+    }
+    // Check the size read from the package before allocating the output
+    let data_len = if has_header { block.len() - 4 } else { block.len() };
+    if let Some(max_size) = max_decompressed_size(mode, data_len) {
+        if entry.decompressed_size > max_size {
+            return Err(PackError::DecompressedSizeTooLarge {
+                asset_id: entry.asset_id,
+                mode,
+                size: block.len() as u64,
+                decompressed_size: entry.decompressed_size,
+                max_size,
+            }
+            .into());
+        }
+    }
+    if has_header {
+        return decompress_buffer(block, entry.decompressed_size);
+    }
+    let mut out = vec![0u8; entry.decompressed_size as usize];
+    decompress_mode(mode, block, &mut out)?;
+    Ok((mode, Cow::Owned(out)))
+}
+
+/// Compression mode of an asset data block, and whether the block starts with the 4-byte mode
+/// header. Blocks the size of the decompressed data are stored uncompressed.
+fn block_mode(
+    block: &[u8],
+    entry: &AssetDirectoryEntry,
+    options: &ReadOptions,
+) -> Result<(u32, bool)> {
+    if block.len() as u64 == entry.decompressed_size {
+        return Ok((0, false));
+    }
+    let expected_mode = options.type_modes.get(&entry.asset_type).copied();
+    let has_header = match options.header_present {
+        HeaderPresence::Auto => match (peek_compression_mode(block), expected_mode) {
+            (Some(mode), Some(expected)) => mode == expected,
+            (mode, _) => mode.is_some(),
+        },
+        HeaderPresence::Always => true,
+        HeaderPresence::Never => false,
+    };
+    if has_header {
+        ensure!(
+            block.len() >= 4,
+            "Asset {} compressed block is too small for a mode header ({} bytes)",
+            entry.asset_id,
+            block.len()
+        );
+        let mode = u32::from_le_bytes(block[0..4].try_into().unwrap());
+        check_compression_mode(mode, entry.asset_id)?;
+        return Ok((mode, true));
+    }
+    let Some(mode) = expected_mode.or(options.headerless_mode) else {
+        bail!(
+            "Asset {} has no compression mode header, and no headerless mode is set",
+            entry.asset_id
+        );
+    };
+    check_compression_mode(mode, entry.asset_id)?;
+    Ok((mode, false))
+}
+
+fn check_compression_mode(mode: u32, asset_id: Uuid) -> Result<()> {
+    if !is_supported_mode(mode) {
+        return Err(PackError::UnsupportedCompression { asset_id, mode }.into());
+    }
+    Ok(())
+}
+
+/// Data block to write for an asset. An asset's compressed block is reused when writing with
+/// its original compression mode, otherwise the data is compressed with [`compress_asset`].
+fn asset_block<'a>(asset: &'a Asset, choice: CompressionChoice) -> Result<(u32, Cow<'a, [u8]>)> {
+    match (&asset.compressed, choice) {
+        (Some(block), CompressionChoice::Mode(mode)) if mode == asset.info.compression_mode => {
+            Ok((mode, Cow::Borrowed(block.as_ref())))
+        }
+        _ => compress_asset(asset.data()?, choice),
+    }
+}
+
+/// Compresses asset data for writing, returning the compression mode and data block.
+///
+/// Falls back to storing the data uncompressed when it's not a valid size for the
+/// requested mode, or when compression wouldn't make it smaller.
+fn compress_asset(data: &[u8], choice: CompressionChoice) -> Result<(u32, Cow<'_, [u8]>)> {
+    if let CompressionChoice::Mode(mode) = choice {
+        if mode != 0 && group_len(mode).is_none_or(|len| data.len().is_multiple_of(len)) {
+            let compressed = compress_buffer(mode, data)?;
+            if compressed.len() < data.len() {
+                return Ok((mode, Cow::Owned(compressed)));
+            }
+        }
+    }
+    Ok((0, Cow::Borrowed(data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub const K_TEST_TXTR: FourCC = FourCC(*b"TXTR");
+    pub const K_TEST_CMDL: FourCC = FourCC(*b"CMDL");
+
+    /// RFRM form of the given type and versions holding `payload`.
+    pub fn rfrm_with_version(kind: FourCC, version: (u32, u32), payload: &[u8]) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        FormDescriptor { size: 0, unk: 0, id: kind, version_a: version.0, version_b: version.1 }
+            .write(&mut cursor, Endian::Little, |w| {
+                w.write_all(payload)?;
+                Ok(())
+            })
+            .unwrap();
+        cursor.into_inner()
+    }
+
+    /// RFRM form of the given type, with versions 1 and 0, holding `payload`.
+    pub fn rfrm(kind: FourCC, payload: &[u8]) -> Vec<u8> {
+        rfrm_with_version(kind, (1, 0), payload)
+    }
+
+    /// Compressible payload of `len` bytes, varying with `seed`.
+    pub fn payload(seed: u8, len: usize) -> Vec<u8> {
+        (0..len).map(|i| seed.wrapping_add((i / 24) as u8)).collect()
+    }
+
+    /// Package with named assets of two types, some with metadata, compressed with `mode`.
+    pub fn sample_package(mode: u32) -> Package<'static> {
+        let mut builder = PackageBuilder::new();
+        for i in 0..4u8 {
+            let kind = if i % 2 == 0 { K_TEST_TXTR } else { K_TEST_CMDL };
+            let asset = builder
+                .add_asset(kind, rfrm(kind, &payload(i, 256)))
+                .with_name(format!("asset{}", i))
+                .with_compression(mode);
+            if i < 2 {
+                asset.with_meta(vec![i; 8]);
+            }
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn read_decompresses_on_first_access() {
+        let package = sample_package(2);
+        let data = package.to_vec(Endian::Little).unwrap();
+        let read = Package::read(&data, Endian::Little).unwrap();
+        for (asset, original) in read.assets.iter().zip(&package.assets) {
+            assert_eq!(asset.info.compression_mode, 2);
+            assert!(asset.data.is_none());
+            assert_eq!(asset.decompressed_size(), original.decompressed_size());
+            assert_eq!(asset.data().unwrap(), original.data().unwrap());
+            assert!(asset.pending.as_ref().unwrap().decompressed.get().is_some());
+        }
+    }
+
+    #[test]
+    fn read_uncompressed_borrows_data() {
+        let data = sample_package(0).to_vec(Endian::Little).unwrap();
+        let read = Package::read(&data, Endian::Little).unwrap();
+        for asset in &read.assets {
+            assert!(matches!(asset.data, Some(Cow::Borrowed(_))));
+            assert!(asset.compressed.is_none());
+        }
+    }
+
+    #[test]
+    fn read_defers_form_validation() {
+        let mut builder = PackageBuilder::new();
+        builder
+            .add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(0, 256)))
+            .with_version(2, 0)
+            .with_compression(2);
+        let data = builder.build().to_vec(Endian::Little).unwrap();
+
+        let read = Package::read(&data, Endian::Little).unwrap();
+        let err = read.assets[0].data().unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(PackError::VersionMismatch { .. })));
+
+        let options = ReadOptions { eager: true, ..Default::default() };
+        assert!(Package::read_with_options(&data, Endian::Little, &options).is_err());
+    }
+
+    #[test]
+    fn eager_read_decompresses_every_asset() {
+        let data = sample_package(3).to_vec(Endian::Little).unwrap();
+        let options = ReadOptions { eager: true, ..Default::default() };
+        let read = Package::read_with_options(&data, Endian::Little, &options).unwrap();
+        for asset in &read.assets {
+            assert!(asset.pending.as_ref().unwrap().decompressed.get().is_some());
+        }
+    }
+
+    #[test]
+    fn set_data_drops_compressed_block() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let mut package = Package::read(&data, Endian::Little).unwrap();
+        let (block, mode) = package.assets[0].raw_compressed().unwrap();
+        assert_eq!(mode, 2);
+        assert_eq!(&block[..4], &2u32.to_le_bytes());
+
+        let replacement = rfrm(package.assets[0].kind, &payload(9, 512));
+        package.assets[0].set_data(replacement.clone());
+        assert!(package.assets[0].raw_compressed().is_none());
+        let written = package.to_vec(Endian::Little).unwrap();
+        let read = Package::read(&written, Endian::Little).unwrap();
+        assert_eq!(read.assets[0].data().unwrap(), replacement);
+        assert_eq!(read.assets[0].info.compression_mode, 2);
+    }
+
+    #[test]
+    fn compressed_block_written_verbatim() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let package = Package::read(&data, Endian::Little).unwrap();
+        let written = package.to_vec(Endian::Little).unwrap();
+        let read = Package::read(&written, Endian::Little).unwrap();
+        for (a, b) in package.assets.iter().zip(&read.assets) {
+            assert_eq!(a.raw_compressed(), b.raw_compressed());
+        }
+    }
+
+    #[test]
+    fn lazy_package_rejects_out_of_range_index() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let lazy = LazyPackage::read(&data, Endian::Little).unwrap();
+        assert_eq!(lazy.data(3).unwrap(), sample_package(2).assets[3].data().unwrap());
+        assert!(lazy.data(4).is_err());
+        assert!(lazy.decompress(4).is_err());
+    }
+
+    #[test]
+    fn write_verified_after_existing_data() {
+        let package = sample_package(2);
+        let mut cursor = Cursor::new(vec![0xAA; 5]);
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        package.write_verified(&mut cursor, Endian::Little).unwrap();
+        let written = cursor.into_inner();
+        assert_eq!(&written[..5], &[0xAA; 5]);
+        assert_eq!(written[5..], package.to_vec(Endian::Little).unwrap());
+    }
+
+    #[test]
+    fn verify_written_detects_mismatch() {
+        let package = sample_package(0);
+        let mut modified = package.clone();
+        modified.assets[1].set_data(rfrm(K_TEST_CMDL, &payload(7, 256)));
+        let written = modified.to_vec(Endian::Little).unwrap();
+        assert!(package.verify_written(&written, Endian::Little).is_err());
+
+        modified.assets.pop();
+        let written = modified.to_vec(Endian::Little).unwrap();
+        assert!(package.verify_written(&written, Endian::Little).is_err());
+    }
+
+    /// Path of a temporary file unique to this test process.
+    pub fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("retrotool-test-{}-{}", std::process::id(), name))
+    }
+
+    /// ADIR entry for a block of `size` bytes decompressing to `decompressed_size` bytes.
+    pub fn test_entry(
+        asset_type: FourCC,
+        decompressed_size: usize,
+        size: usize,
+    ) -> AssetDirectoryEntry {
+        AssetDirectoryEntry {
+            asset_type,
+            asset_id: Uuid::from_u128(1),
+            version: 1,
+            other_version: 0,
+            offset: 0,
+            decompressed_size: decompressed_size as u64,
+            size: size as u64,
+        }
+    }
+
+    #[test]
+    fn headerless_block_decodes_with_never() {
+        let data = rfrm(K_TEST_TXTR, &payload(3, 512));
+        let block = compress_buffer(2, &data).unwrap().split_off(4);
+        let entry = test_entry(K_TEST_TXTR, data.len(), block.len());
+
+        let options = ReadOptions {
+            header_present: HeaderPresence::Never,
+            headerless_mode: Some(2),
+            ..Default::default()
+        };
+        let (mode, decompressed) = decompress_asset(&block, &entry, &options).unwrap();
+        assert_eq!(mode, 2);
+        assert_eq!(decompressed, data);
+
+        // Without a headerless mode, there's no way to decode the block
+        let options = ReadOptions { header_present: HeaderPresence::Never, ..Default::default() };
+        assert!(decompress_asset(&block, &entry, &options).is_err());
+        // A type mode takes precedence over the headerless mode
+        let options = ReadOptions {
+            header_present: HeaderPresence::Never,
+            headerless_mode: Some(1),
+            type_modes: HashMap::from([(K_TEST_TXTR, 2)]),
+            ..Default::default()
+        };
+        assert_eq!(decompress_asset(&block, &entry, &options).unwrap().1, data);
+    }
+
+    #[test]
+    fn header_presence_auto_and_always() {
+        let data = rfrm(K_TEST_TXTR, &payload(3, 512));
+        let block = compress_buffer(3, &data).unwrap();
+        let entry = test_entry(K_TEST_TXTR, data.len(), block.len());
+        for header_present in [HeaderPresence::Auto, HeaderPresence::Always] {
+            let options = ReadOptions { header_present, ..Default::default() };
+            let (mode, decompressed) = decompress_asset(&block, &entry, &options).unwrap();
+            assert_eq!(mode, 3);
+            assert_eq!(decompressed, data);
+        }
+
+        // With Auto, a mode header not matching the type's mode is treated as payload
+        let (mode, has_header) = block_mode(&block, &entry, &ReadOptions {
+            type_modes: HashMap::from([(K_TEST_TXTR, 1)]),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!((mode, has_header), (1, false));
+
+        let options = ReadOptions { header_present: HeaderPresence::Always, ..Default::default() };
+        let mut bad = block.clone();
+        bad[0..4].copy_from_slice(&9u32.to_le_bytes());
+        assert!(decompress_asset(&bad, &entry, &options).is_err());
+        assert!(decompress_asset(&block[..2], &test_entry(K_TEST_TXTR, data.len(), 2), &options)
+            .is_err());
+    }
+
+    #[test]
+    fn write_split_offsets_reference_data_file() {
+        let package = sample_package(2);
+        let (mut toc, mut data) = (Cursor::new(Vec::new()), Cursor::new(Vec::new()));
+        package.write_split(&mut toc, &mut data, Endian::Little, &WriteOptions::default()).unwrap();
+        let (toc, data) = (toc.into_inner(), data.into_inner());
+
+        let (tocc_data, _) = slice_tocc(&toc, Endian::Little).unwrap();
+        let directory = TableOfContents::read(tocc_data, Endian::Little).unwrap().directory;
+        assert_eq!(directory.entries.len(), package.assets.len());
+        for (entry, asset) in directory.entries.iter().zip(&package.assets) {
+            assert_eq!(entry.asset_id, asset.id);
+            let block = &data[entry.offset as usize..(entry.offset + entry.size) as usize];
+            let (mode, decompressed) =
+                decompress_asset(block, entry, &ReadOptions::default()).unwrap();
+            assert_eq!(mode, 2);
+            assert_eq!(decompressed, asset.data().unwrap());
+        }
+    }
+
+    #[test]
+    fn write_split_applies_alignment() {
+        let package = sample_package(2);
+        let options = WriteOptions { asset_alignment: 64, pad_byte: 0xFF, ..Default::default() };
+        let (mut toc, mut data) = (Cursor::new(Vec::new()), Cursor::new(Vec::new()));
+        package.write_split(&mut toc, &mut data, Endian::Little, &options).unwrap();
+        let (toc, data) = (toc.into_inner(), data.into_inner());
+        assert_eq!(toc.len() % 16, 0);
+        let (pack, _, padding) = FormDescriptor::slice(&toc, Endian::Little).unwrap();
+        assert_eq!(pack.id, K_FORM_PACK);
+        assert!(padding.iter().all(|&b| b == 0xFF));
+
+        let (tocc_data, _) = slice_tocc(&toc, Endian::Little).unwrap();
+        let directory = TableOfContents::read(tocc_data, Endian::Little).unwrap().directory;
+        for entry in &directory.entries {
+            assert_eq!(entry.offset % 64, 0);
+            assert!(entry.offset + entry.size <= data.len() as u64);
+        }
+    }
+
+    #[test]
+    fn data_region_concatenates_with_tocc() {
+        let package = sample_package(2);
+        for options in [WriteOptions::default(), WriteOptions {
+            asset_alignment: 64,
+            pad_byte: 0xFF,
+            ..Default::default()
+        }] {
+            let mut region = Vec::new();
+            let entries = package.write_data_region(&mut region, &options).unwrap();
+            let mut out = Cursor::new(Vec::new());
+            package.write_tocc_with_entries(&mut out, Endian::Little, &entries).unwrap();
+            let mut out = out.into_inner();
+            out.extend_from_slice(&region);
+            assert_eq!(out, package.to_vec_with_options(Endian::Little, &options).unwrap());
+
+            let read = Package::read(&out, Endian::Little).unwrap();
+            for (asset, original) in read.assets.iter().zip(&package.assets) {
+                assert_eq!(asset.id, original.id);
+                assert_eq!(asset.data().unwrap(), original.data().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_wrapped_asset_blocks() {
+        for e in [Endian::Little, Endian::Big] {
+            for mode in [0, 2] {
+                let package = sample_package(mode);
+                let mut region = Vec::new();
+                let mut entries =
+                    package.write_data_region(&mut region, &WriteOptions::default()).unwrap();
+                // Wrap every other block in a chunk header followed by `skip` bytes of padding
+                let mut wrapped = Vec::new();
+                for (index, entry) in entries.iter_mut().enumerate() {
+                    let block =
+                        &region[entry.offset as usize..(entry.offset + entry.size) as usize];
+                    entry.offset = wrapped.len() as u64;
+                    if index % 2 == 0 {
+                        let desc = ChunkDescriptor {
+                            id: FourCC(*b"WRAP"),
+                            size: block.len() as u64,
+                            unk: 1,
+                            skip: 8,
+                        };
+                        let mut header = Cursor::new(Vec::new());
+                        header.write_type(&desc, e).unwrap();
+                        wrapped.extend_from_slice(&header.into_inner());
+                        wrapped.extend_from_slice(&[0xFF; 8]);
+                    }
+                    wrapped.extend_from_slice(block);
+                    entry.size = wrapped.len() as u64 - entry.offset;
+                }
+                let mut data = Cursor::new(Vec::new());
+                package.write_tocc_with_entries(&mut data, e, &entries).unwrap();
+                let mut data = data.into_inner();
+                data.extend_from_slice(&wrapped);
+
+                let read = Package::read(&data, e).unwrap();
+                let streamed = Package::read_from(&mut Cursor::new(&data), e).unwrap();
+                for read in [&read, &streamed] {
+                    for (asset, original) in read.assets.iter().zip(&package.assets) {
+                        assert_eq!(asset.id, original.id);
+                        assert_eq!(asset.info.compression_mode, mode);
+                        assert_eq!(asset.data().unwrap(), original.data().unwrap());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn recommend_compression_by_type() {
+        // Incompressible texture data, and repetitive model data
+        let mut state = 0x12345678u32;
+        let mut noise = |len: usize| -> Vec<u8> {
+            (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect()
+        };
+        let mut builder = PackageBuilder::new();
+        for i in 0..4u8 {
+            builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &noise(1024)));
+            builder.add_asset(K_TEST_CMDL, rfrm(K_TEST_CMDL, &payload(i, 1024)));
+        }
+        let package = builder.build();
+
+        let recommended = package.recommend_compression().unwrap();
+        assert_eq!(recommended.len(), 2);
+        assert_eq!(recommended[&K_TEST_TXTR], CompressionChoice::Store);
+        assert!(matches!(recommended[&K_TEST_CMDL], CompressionChoice::Mode(1..=3)));
+        assert!(PackageBuilder::new().build().recommend_compression().unwrap().is_empty());
+    }
+
+    #[test]
+    fn stored_assets_have_no_load_cost() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let mut package = Package::read(&data, Endian::Little).unwrap();
+        let cost = package.estimate_load_cost(100.0);
+        assert_eq!(cost.compressed_assets, 4);
+        let total: u64 = package.assets.iter().map(Asset::decompressed_size).sum();
+        assert_eq!(cost.decompressed_bytes, total);
+        assert!(cost.seconds > 0.0);
+        assert!(package.estimate_load_cost(200.0).seconds < cost.seconds);
+
+        package.recompress(0).unwrap();
+        let cost = package.estimate_load_cost(100.0);
+        assert_eq!((cost.compressed_assets, cost.decompressed_bytes), (0, 0));
+        assert_eq!(cost.seconds, 0.0);
+    }
+
+    fn checksummed_package(corrupt: bool) -> Vec<u8> {
+        let mut data = rfrm(K_TEST_TXTR, &payload(1, 256));
+        let crc = crc32fast::hash(&data) ^ corrupt as u32;
+        data.extend_from_slice(&crc.to_le_bytes());
+        let mut builder = PackageBuilder::new();
+        builder.add_asset(K_TEST_TXTR, data).with_compression(2);
+        builder.build().to_vec(Endian::Little).unwrap()
+    }
+
+    #[test]
+    fn trailing_crc_validates_when_enabled() {
+        let checksum = TrailingChecksum { len: 4, verify_crc32: true };
+        let options =
+            ReadOptions { trailing_checksum: Some(checksum), eager: true, ..Default::default() };
+        let data = checksummed_package(false);
+        let package = Package::read_with_options(&data, Endian::Little, &options).unwrap();
+        assert_eq!(package.assets[0].decompressed_size(), 256 + 32 + 4);
+
+        let eager = ReadOptions { eager: true, ..Default::default() };
+        assert!(Package::read_with_options(&data, Endian::Little, &eager).is_err());
+        let data = checksummed_package(true);
+        assert!(Package::read_with_options(&data, Endian::Little, &options).is_err());
+        let unverified = ReadOptions {
+            trailing_checksum: Some(TrailingChecksum { verify_crc32: false, ..checksum }),
+            ..options
+        };
+        assert!(Package::read_with_options(&data, Endian::Little, &unverified).is_ok());
+    }
+
+    /// TXTR forms nested `depth` deep.
+    fn nested_forms(depth: usize) -> Vec<u8> {
+        (1..depth).fold(rfrm(K_TEST_TXTR, &[]), |inner, _| rfrm(K_TEST_TXTR, &inner))
+    }
+
+    #[test]
+    fn validate_forms_limits_nesting_depth() {
+        let id = Uuid::from_u128(1);
+        let data = nested_forms(DEFAULT_MAX_FORM_DEPTH);
+        assert!(validate_form(&data, id, 1, DEFAULT_MAX_FORM_DEPTH).unwrap().is_empty());
+
+        let data = nested_forms(DEFAULT_MAX_FORM_DEPTH + 1);
+        let err = validate_form(&data, id, 1, DEFAULT_MAX_FORM_DEPTH).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(PackError::TooDeep { max_depth: 32, .. })));
+        assert!(validate_form(&data, id, 1, 64).is_ok());
+
+        // Deeply nested input fails on the depth limit rather than overflowing the stack
+        let mut builder = PackageBuilder::new();
+        builder.add_asset(K_TEST_TXTR, nested_forms(1000)).with_compression(2);
+        let data = builder.build().to_vec(Endian::Little).unwrap();
+        let options = ReadOptions { validate_forms: true, eager: true, ..Default::default() };
+        let err = Package::read_with_options(&data, Endian::Little, &options).unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(PackError::TooDeep { .. })));
+    }
+
+    #[test]
+    fn asset_meta_and_name_lookup() {
+        let package = sample_package(2);
+        let data = package.to_vec(Endian::Little).unwrap();
+        for asset in &package.assets {
+            let (meta, name) =
+                Package::asset_meta_and_name(&data, asset.id, Endian::Little).unwrap();
+            assert_eq!(meta.as_deref(), asset.meta.as_deref());
+            assert_eq!(name, asset.name);
+        }
+        let missing = Package::asset_meta_and_name(&data, Uuid::nil(), Endian::Little).unwrap();
+        assert_eq!(missing, (None, None));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_process_visits_matching_assets() {
+        use std::sync::Mutex;
+
+        let package = sample_package(2);
+        let mut data = package.to_vec(Endian::Little).unwrap();
+        let read = Package::read(&data, Endian::Little).unwrap();
+        let textures: Vec<Uuid> = read.assets_by_type(K_TEST_TXTR).map(|a| a.id).collect();
+        assert_eq!(textures.len(), 2);
+
+        let visited = Mutex::new(vec![]);
+        let errors = read.par_process(
+            |asset| asset.kind == K_TEST_TXTR,
+            |asset, data| {
+                assert_eq!(data, package.asset_by_id(asset.id).unwrap().data().unwrap());
+                visited.lock().unwrap().push(asset.id);
+            },
+        );
+        assert!(errors.is_empty());
+        let mut visited = visited.into_inner().unwrap();
+        visited.sort();
+        assert_eq!(visited, textures);
+
+        // Assets that fail to decompress are reported instead of visited
+        let entries = &read.source.as_ref().unwrap().toc.directory.entries;
+        let entry = entries.iter().find(|entry| entry.asset_id == textures[1]).unwrap().clone();
+        data[entry.offset as usize + 4..(entry.offset + entry.size) as usize].fill(0xFF);
+        let read = Package::read(&data, Endian::Little).unwrap();
+        let visited = Mutex::new(vec![]);
+        let errors = read.par_process(
+            |asset| asset.kind == K_TEST_TXTR,
+            |asset, _| visited.lock().unwrap().push(asset.id),
+        );
+        assert_eq!(visited.into_inner().unwrap(), [textures[0]]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, textures[1]);
+    }
+
+    #[test]
+    fn read_autodetect_byte_order() {
+        let package = sample_package(2);
+        for e in [Endian::Little, Endian::Big] {
+            let data = package.to_vec(e).unwrap();
+            let (read, detected) = Package::read_autodetect(&data).unwrap();
+            assert_eq!(detected, e);
+            assert_eq!(read.assets.len(), package.assets.len());
+            for (asset, original) in read.assets.iter().zip(&package.assets) {
+                assert_eq!(asset.id, original.id);
+                assert_eq!(asset.data().unwrap(), original.data().unwrap());
+            }
+        }
+        assert!(Package::read_autodetect(&[0; 64]).is_err());
+        assert!(Package::read_autodetect(b"PACK").is_err());
+    }
+
+    #[test]
+    fn iter_assets_matches_read() {
+        for mode in [0, 2] {
+            let data = sample_package(mode).to_vec(Endian::Little).unwrap();
+            let package = Package::read(&data, Endian::Little).unwrap();
+            let assets: Vec<Asset> =
+                Package::iter_assets(&data, Endian::Little).unwrap().map(Result::unwrap).collect();
+            assert_eq!(assets.len(), package.assets.len());
+            for (asset, expected) in assets.iter().zip(&package.assets) {
+                assert_eq!((asset.id, asset.kind), (expected.id, expected.kind));
+                assert_eq!((&asset.name, &asset.meta), (&expected.name, &expected.meta));
+                assert_eq!(asset.info.compression_mode, expected.info.compression_mode);
+                assert_eq!(asset.data().unwrap(), expected.data().unwrap());
+            }
+        }
+        assert!(Package::iter_assets(&[0; 64], Endian::Little).is_err());
+    }
+
+    #[test]
+    fn read_verbose_describes_layout() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let (package, layout) = Package::read_verbose(&data, Endian::Little).unwrap();
+        assert_eq!(layout, Package::describe(&data, Endian::Little).unwrap().to_string());
+        let lines: Vec<&str> = layout.lines().collect();
+        assert!(lines[0].starts_with("PACK v1.1 @ 0x0"));
+        assert!(lines.iter().any(|line| line.trim_start().starts_with("- ADIR @")));
+        for asset in &package.assets {
+            assert!(layout.contains(&format!("{} {}", asset.kind, asset.id)), "{}", asset.id);
+        }
+        assert!(Package::read_verbose(&data[..32], Endian::Little).is_err());
+    }
+
+    #[test]
+    fn read_all_concatenated_packages() {
+        for e in [Endian::Little, Endian::Big] {
+            let packages = [sample_package(0), sample_package(2)];
+            let mut data = packages[0].to_vec(e).unwrap();
+            data.extend_from_slice(&[0; 12]);
+            // Other top-level forms are skipped
+            let mut other = Cursor::new(Vec::new());
+            FormDescriptor { size: 0, unk: 0, id: K_TEST_TXTR, version_a: 1, version_b: 0 }
+                .write(&mut other, e, |w| Ok(w.write_all(&payload(5, 32))?))
+                .unwrap();
+            data.extend_from_slice(&other.into_inner());
+            data.extend_from_slice(&packages[1].to_vec(e).unwrap());
+
+            let read = Package::read_all(&data, e).unwrap();
+            assert_eq!(read.len(), 2);
+            for (read, expected) in read.iter().zip(&packages) {
+                assert_eq!(read.assets.len(), expected.assets.len());
+                for (asset, original) in read.assets.iter().zip(&expected.assets) {
+                    assert_eq!(asset.id, original.id);
+                    assert_eq!(asset.info.compression_mode, original.info.compression_mode);
+                    assert_eq!(asset.data().unwrap(), original.data().unwrap());
+                }
+            }
+        }
+        assert!(Package::read_all(&[], Endian::Little).unwrap().is_empty());
+        assert!(Package::read_all(&[0xFF; 8], Endian::Little).is_err());
+    }
+
+    #[test]
+    fn compressed_ratio_of_read_assets() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let mut read = Package::read(&data, Endian::Little).unwrap();
+        for asset in &read.assets {
+            let (block, _) = asset.raw_compressed().unwrap();
+            let ratio = asset.compressed_ratio().unwrap();
+            assert_eq!(ratio, block.len() as f32 / asset.decompressed_size() as f32);
+            assert!(ratio < 1.0);
+        }
+        // Assets without a compressed block have no ratio, rather than 1.0
+        let data = read.assets[0].data().unwrap().to_vec();
+        read.assets[0].set_data(data);
+        assert_eq!(read.assets[0].compressed_ratio(), None);
+        let data = sample_package(0).to_vec(Endian::Little).unwrap();
+        let stored = Package::read(&data, Endian::Little).unwrap();
+        assert!(stored.assets.iter().all(|asset| asset.compressed_ratio().is_none()));
+        assert!(sample_package(2).assets.iter().all(|asset| asset.compressed_ratio().is_none()));
+    }
+
+    #[test]
+    fn lossy_names_are_reported() {
+        let package = sample_package(0);
+        assert!(package.assets.iter().all(|asset| !package.is_name_lossy(asset.id)));
+        let data = package.to_vec(Endian::Little).unwrap();
+        let mut read = Package::read(&data, Endian::Little).unwrap();
+        let (utf8, utf16) = (read.assets[0].id, read.assets[1].id);
+        read.assets[1].name = Some("ab".to_string());
+        read.source.as_mut().unwrap().toc.name_encodings.insert(utf16, NameEncoding::Utf16Le);
+        let mut data = read.to_vec(Endian::Little).unwrap();
+        assert!(read.assets.iter().all(|asset| !read.is_name_lossy(asset.id)));
+
+        // Invalid UTF-8, and an unpaired UTF-16 surrogate
+        let name = read.assets[0].name.clone().unwrap();
+        let find = |data: &[u8], bytes: &[u8]| {
+            data.windows(bytes.len()).position(|window| window == bytes).unwrap()
+        };
+        let offset = find(&data, name.as_bytes());
+        data[offset] = 0xFF;
+        let offset = find(&data, &[0xFF, 0xFE, b'a', 0, b'b', 0]);
+        data[offset + 2..offset + 4].copy_from_slice(&0xD800u16.to_le_bytes());
+
+        let read = Package::read(&data, Endian::Little).unwrap();
+        for id in [utf8, utf16] {
+            assert!(read.is_name_lossy(id));
+            assert!(read.asset_by_id(id).unwrap().name.as_ref().unwrap().contains('\u{FFFD}'));
+        }
+        assert_eq!(read.name_encoding(utf16), NameEncoding::Utf16Le);
+        assert!(!read.is_name_lossy(read.assets[2].id));
+    }
+
+    #[test]
+    fn asset_lookup() {
+        let mut package = sample_package(2);
+        for asset in &package.assets {
+            assert_eq!(package.asset_by_id(asset.id).unwrap().id, asset.id);
+        }
+        assert!(package.asset_by_id(Uuid::nil()).is_none());
+        // Reordered assets are still found
+        package.assets.reverse();
+        for asset in &package.assets {
+            assert_eq!(package.asset_by_id(asset.id).unwrap().id, asset.id);
+        }
+        package.assets.reverse();
+
+        // Names are case-sensitive, and the first of duplicate names is returned
+        package.assets[1].name = Some("Duplicate".to_string());
+        package.assets[3].name = Some("Duplicate".to_string());
+        assert_eq!(package.asset_by_name("Duplicate").unwrap().id, package.assets[1].id);
+        let name = package.assets[2].name.clone().unwrap();
+        assert_eq!(package.asset_by_name(&name).unwrap().id, package.assets[2].id);
+        assert!(package.asset_by_name(&name.to_uppercase()).is_none());
+
+        let textures: Vec<Uuid> = package.assets_by_type(K_TEST_TXTR).map(|a| a.id).collect();
+        let expected: Vec<Uuid> =
+            package.assets.iter().filter(|a| a.kind == K_TEST_TXTR).map(|a| a.id).collect();
+        assert_eq!(textures.len(), 2);
+        assert_eq!(textures, expected);
+        assert_eq!(package.assets_by_type(FourCC(*b"NONE")).count(), 0);
+    }
+
+    #[test]
+    fn utf16_names_keep_their_encoding() {
+        let data = sample_package(0).to_vec(Endian::Little).unwrap();
+        let mut package = Package::read(&data, Endian::Little).unwrap();
+        let (le, be) = (package.assets[0].id, package.assets[1].id);
+        package.assets[0].name = Some("名前".to_string());
+        let toc = &mut package.source.as_mut().unwrap().toc;
+        toc.name_encodings.insert(le, NameEncoding::Utf16Le);
+        toc.name_encodings.insert(be, NameEncoding::Utf16Be);
+        let written = package.to_vec(Endian::Little).unwrap();
+
+        let read = Package::read(&written, Endian::Little).unwrap();
+        assert_eq!(read.assets[0].name.as_deref(), Some("名前"));
+        assert_eq!(read.assets[1].name, package.assets[1].name);
+        assert_eq!(read.name_encoding(le), NameEncoding::Utf16Le);
+        assert_eq!(read.name_encoding(be), NameEncoding::Utf16Be);
+        assert_eq!(read.name_encoding(read.assets[2].id), NameEncoding::Utf8);
+        let (_, name) = Package::asset_meta_and_name(&written, le, Endian::Little).unwrap();
+        assert_eq!(name.as_deref(), Some("名前"));
+        // Rewriting the tables re-encodes names with their original encoding
+        let options = WriteOptions { preserve_raw_tables: false, ..Default::default() };
+        assert_eq!(read.to_vec_with_options(Endian::Little, &options).unwrap(), written);
+    }
+
+    #[test]
+    fn name_encoding_round_trip() {
+        for encoding in [NameEncoding::Utf8, NameEncoding::Utf16Le, NameEncoding::Utf16Be] {
+            let entry =
+                StringTableEntry { name: encoding.encode("Ünïcode"), ..Default::default() };
+            assert_eq!(entry.name_encoding(), encoding);
+            assert_eq!(entry.decode_name().unwrap(), "Ünïcode");
+        }
+        assert_eq!(NameEncoding::Utf16Le.encode("A"), [0xFF, 0xFE, b'A', 0]);
+        assert_eq!(NameEncoding::Utf16Be.encode("A"), [0xFE, 0xFF, 0, b'A']);
+    }
+
+    #[test]
+    fn type_mode_resolves_ambiguous_header() {
+        // Headerless mode 2 data starting with four zero bytes, which also reads as mode 0
+        let data: Vec<u8> = [0u8; 4].into_iter().chain(1..=12).collect();
+        let block = crate::util::lzss::compress::<2>(&data).unwrap();
+        assert_eq!(&block[..4], &[0; 4]);
+        let entry = test_entry(K_TEST_TXTR, data.len(), block.len());
+        assert!(decompress_asset(&block, &entry, &ReadOptions::default()).is_err());
+
+        let options =
+            ReadOptions { type_modes: HashMap::from([(K_TEST_TXTR, 2)]), ..Default::default() };
+        let (mode, decompressed) = decompress_asset(&block, &entry, &options).unwrap();
+        assert_eq!(mode, 2);
+        assert_eq!(decompressed, data);
+        // Other types still use the header
+        let entry = AssetDirectoryEntry { asset_type: K_TEST_CMDL, ..entry };
+        assert!(decompress_asset(&block, &entry, &options).is_err());
+    }
+
+    #[test]
+    fn asset_versions_match_form_headers() {
+        let mut builder = PackageBuilder::new();
+        for i in 0..4u32 {
+            let kind = if i % 2 == 0 { K_TEST_TXTR } else { K_TEST_CMDL };
+            builder
+                .add_asset(kind, rfrm_with_version(kind, (i + 1, i * 7), &payload(i as u8, 128)))
+                .with_compression(i % 4);
+        }
+        let data = builder.build().to_vec(Endian::Little).unwrap();
+        let versions = Package::asset_versions(&data, Endian::Little).unwrap();
+        let read = Package::read(&data, Endian::Little).unwrap();
+        assert_eq!(versions.len(), 4);
+        for asset in &read.assets {
+            let (form, _, _) =
+                FormDescriptor::slice(asset.data().unwrap(), Endian::Little).unwrap();
+            assert_eq!(versions[&asset.id], (form.version_a, form.version_b));
+        }
+    }
+
+    #[test]
+    fn chunk_out_of_bounds_is_pack_error() {
+        let mut cursor = Cursor::new(Vec::new());
+        ChunkDescriptor { id: K_CHUNK_META, size: 0, unk: 0, skip: 0 }
+            .write(&mut cursor, Endian::Little, |w| {
+                w.write_all(&[1; 8])?;
+                Ok(())
+            })
+            .unwrap();
+        let mut data = cursor.into_inner();
+        data.truncate(data.len() - 1);
+        let err = ChunkDescriptor::slice(&data, Endian::Little).unwrap_err();
+        assert!(matches!(
+            err.custom_err::<PackError>(),
+            Some(PackError::ChunkOutOfBounds { id: K_CHUNK_META, size: 8, remaining: 7 })
+        ));
+    }
+
+    #[test]
+    fn dump_rfrm_rejects_short_trailing_data() {
+        let mut form = rfrm(K_TEST_TXTR, &[1, 2]);
+        let mut out = Vec::new();
+        assert!(crate::format::rfrm::dump_rfrm(&mut out, &form, 0).is_err());
+        // Trailing zeros are padding
+        form = rfrm(K_TEST_TXTR, &[0; 3]);
+        assert!(crate::format::rfrm::dump_rfrm(&mut out, &form, 0).is_ok());
+    }
+
+    #[test]
+    fn same_content_compares_decompressed_data() {
+        let stored_data = sample_package(0).to_vec(Endian::Little).unwrap();
+        let compressed_data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let stored = Package::read(&stored_data, Endian::Little).unwrap();
+        let compressed = Package::read(&compressed_data, Endian::Little).unwrap();
+        for (a, b) in stored.assets.iter().zip(&compressed.assets) {
+            assert_eq!(b.info.compression_mode, 2);
+            assert!(a.same_content(b).unwrap());
+            assert!(b.same_content(a).unwrap());
+        }
+        // Identical blocks match without decompressing
+        let again = Package::read(&compressed_data, Endian::Little).unwrap();
+        assert!(compressed.assets[0].same_content(&again.assets[0]).unwrap());
+        assert!(again.assets[0].pending.as_ref().unwrap().decompressed.get().is_none());
+
+        // Same size, different data
+        let first = &stored.assets[0];
+        let other =
+            compressed.assets.iter().find(|a| a.kind == first.kind && a.id != first.id).unwrap();
+        assert_eq!(first.decompressed_size(), other.decompressed_size());
+        assert!(!first.same_content(other).unwrap());
+        let mut changed = sample_package(0);
+        changed.assets[0].set_data(rfrm(K_TEST_TXTR, &payload(9, 256)));
+        assert!(!changed.assets[0].same_content(&compressed.assets[0]).unwrap());
+        // Different type
+        let other = stored.assets.iter().find(|a| a.kind != first.kind).unwrap();
+        assert!(!first.same_content(other).unwrap());
+    }
+
+    #[test]
+    fn map_with_progress_keeps_item_order() {
+        let items: Vec<u32> = (0..100).collect();
+        let mut seen = vec![false; items.len()];
+        let results = map_with_progress(
+            &items,
+            |&i| Ok(i * 2),
+            |index| {
+                assert!(!std::mem::replace(&mut seen[index], true));
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(results, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+        assert!(seen.iter().all(|&s| s));
+
+        let err = map_with_progress(
+            &items,
+            |&i| if i == 50 { bail!("item {}", i) } else { Ok(i) },
+            |_| Ok(()),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "item 50");
+        assert!(map_with_progress(&items, |&i| Ok(i), |_| bail!("cancelled")).is_err());
+    }
+
+    #[test]
+    fn write_with_progress_reports_each_asset() {
+        let package = sample_package(2);
+        let mut reported = vec![];
+        let mut cursor = Cursor::new(Vec::new());
+        package
+            .write_with_progress(
+                &mut cursor,
+                Endian::Little,
+                &WriteOptions::default(),
+                |index, total, id| {
+                    assert_eq!(total, 4);
+                    assert_eq!(package.assets[index].id, id);
+                    reported.push(index);
+                    Ok(())
+                },
+            )
+            .unwrap();
+        reported.sort();
+        assert_eq!(reported, [0, 1, 2, 3]);
+        assert_eq!(cursor.into_inner(), package.to_vec(Endian::Little).unwrap());
+
+        let mut cursor = Cursor::new(Vec::new());
+        let options = WriteOptions::default();
+        let result =
+            package.write_with_progress(&mut cursor, Endian::Little, &options, |_, _, _| {
+                bail!("cancelled")
+            });
+        assert!(result.is_err());
+        assert!(cursor.into_inner().is_empty());
+    }
+
+    #[test]
+    fn id_sorted_directory_keeps_data_layout() {
+        let mut package = sample_package(2);
+        let count = package.assets.len() as u64;
+        for (index, asset) in package.assets.iter_mut().enumerate() {
+            asset.info.orig_offset = count - index as u64;
+        }
+        let options =
+            WriteOptions { ordering: AssetOrdering::PreserveOriginal, ..Default::default() };
+        let data = package.to_vec_with_options(Endian::Little, &options).unwrap();
+
+        // Directory is sorted by ID, data blocks are in reverse
+        let (tocc_data, _) = slice_tocc(&data, Endian::Little).unwrap();
+        let directory = TableOfContents::read(tocc_data, Endian::Little).unwrap().directory;
+        assert!(directory.entries.windows(2).all(|w| w[0].asset_id < w[1].asset_id));
+        assert!(directory.entries.windows(2).all(|w| w[0].offset > w[1].offset));
+
+        let read = Package::read(&data, Endian::Little).unwrap();
+        for (asset, original) in read.assets.iter().zip(&package.assets) {
+            assert_eq!(asset.id, original.id);
+            assert_eq!(asset.data().unwrap(), original.data().unwrap());
+        }
+        assert_eq!(read.to_vec_with_options(Endian::Little, &options).unwrap(), data);
+    }
+
+    #[test]
+    fn find_assets_by_name_pattern() {
+        let mut builder = PackageBuilder::new();
+        for name in ["tex_rock", "TEX_grass", "cmdl_rock", "tex"] {
+            builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, name.as_bytes())).with_name(name);
+        }
+        builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &[]));
+        let package = builder.build();
+        let names = |assets: Vec<&Asset>| {
+            let mut names: Vec<_> = assets.iter().filter_map(|a| a.name.clone()).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(package.find_assets("tex_*")), ["TEX_grass", "tex_rock"]);
+        assert_eq!(names(package.find_assets_with_case("tex_*", true)), ["tex_rock"]);
+        assert_eq!(names(package.find_assets("rock")), ["cmdl_rock", "tex_rock"]);
+        assert_eq!(names(package.find_assets("*")).len(), 4);
+        assert!(package.find_assets("model*").is_empty());
+    }
+
+    #[test]
+    fn footer_repack_preserves_original_offsets() {
+        let mut package = sample_package(2);
+        let count = package.assets.len() as u64;
+        for (index, asset) in package.assets.iter_mut().enumerate() {
+            asset.info.orig_offset = count - index as u64;
+        }
+        let options =
+            WriteOptions { ordering: AssetOrdering::PreserveOriginal, ..Default::default() };
+        let data = package.to_vec_with_options(Endian::Little, &options).unwrap();
+        let source = Package::read(&data, Endian::Little).unwrap();
+
+        let dir = temp_path("footer-repack");
+        fs::create_dir_all(&dir).unwrap();
+        for asset in &source.assets {
+            let mut file = fs::File::create(dir.join(format!("{}.bin", asset.id))).unwrap();
+            asset.write_with_footer(&mut file).unwrap();
+        }
+        let extracted = Package::from_dir(&dir, Endian::Little).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        for (asset, original) in extracted.assets.iter().zip(&source.assets) {
+            assert_eq!(asset.id, original.id);
+            assert_eq!(asset.info.orig_offset, original.info.orig_offset);
+            assert_eq!(asset.info.compression_mode, 2);
+        }
+        let repacked = extracted.to_vec_with_options(Endian::Little, &options).unwrap();
+        let offsets = |data: &[u8]| {
+            let (tocc_data, _) = slice_tocc(data, Endian::Little).unwrap();
+            let directory = TableOfContents::read(tocc_data, Endian::Little).unwrap().directory;
+            directory.entries.iter().map(|entry| entry.offset).collect::<Vec<_>>()
+        };
+        assert_eq!(offsets(&repacked), offsets(&data));
+        assert_eq!(repacked, data);
+    }
+
+    #[test]
+    fn verify_recompression_detects_lossy_encoder() {
+        let package = sample_package(0);
+        let options = WriteOptions::default();
+        package.verify_recompression(&options).unwrap();
+        let per_type = HashMap::from([
+            (K_TEST_TXTR, CompressionChoice::Mode(2)),
+            (K_TEST_CMDL, CompressionChoice::Mode(3)),
+        ]);
+        let options = WriteOptions { per_type, ..Default::default() };
+        package.verify_recompression(&options).unwrap();
+
+        // Compresses a different byte than it was given
+        fn buggy(data: &[u8], choice: CompressionChoice) -> Result<(u32, Cow<'_, [u8]>)> {
+            let mut data = data.to_vec();
+            data[40] ^= 1;
+            let (mode, block) = compress_asset(&data, choice)?;
+            Ok((mode, Cow::Owned(block.into_owned())))
+        }
+        let err = package.verify_recompression_with(&options, buggy).unwrap_err();
+        assert!(err.to_string().contains("does not match after compression"));
+        // Stored assets aren't decompressed, so they can't differ
+        package.verify_recompression_with(&WriteOptions::default(), buggy).unwrap();
+    }
+
+    #[test]
+    fn short_compressed_blocks_are_rejected() {
+        let headerless = ReadOptions { headerless_mode: Some(2), ..Default::default() };
+        let all_options = [
+            ReadOptions::default(),
+            ReadOptions { header_present: HeaderPresence::Always, ..Default::default() },
+            ReadOptions { header_present: HeaderPresence::Never, ..headerless.clone() },
+            headerless,
+        ];
+        for len in 1..=3 {
+            for block in [vec![0u8; len], vec![2u8; len], vec![0xFFu8; len]] {
+                let entry = test_entry(K_TEST_TXTR, 64, len);
+                for options in &all_options {
+                    assert!(decompress_asset(&block, &entry, options).is_err());
+                }
+                assert!(decompress_buffer(&block, 64).is_err());
+                assert!(crate::util::lzss::decompress_into(&block, &mut [0u8; 64]).is_err());
+            }
+        }
+
+        // Mode 0 blocks hold the data verbatim after the header
+        let mut block = 0u32.to_le_bytes().to_vec();
+        block.extend_from_slice(&[1, 2, 3]);
+        let (mode, data) = decompress_buffer(&block, 3).unwrap();
+        assert_eq!((mode, data.as_ref()), (0, [1u8, 2, 3].as_slice()));
+        assert!(decompress_buffer(&block, 4).is_err());
+        assert!(decompress_buffer(&block[..4], 3).is_err());
+
+        // Reading a package checks each block's mode header
+        let mut builder = PackageBuilder::new();
+        builder
+            .add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(1, 64)))
+            .with_compressed([2, 0], 2);
+        let data = builder.build().to_vec(Endian::Little).unwrap();
+        assert!(Package::read(&data, Endian::Little).is_err());
+        assert!(LazyPackage::read(&data, Endian::Little).unwrap().data(0).is_err());
+    }
+
+    /// Offset of the data of a TOCC chunk in package data.
+    fn chunk_data_offset(data: &[u8], e: Endian, id: FourCC) -> usize {
+        let (tocc_data, _) = slice_tocc(data, e).unwrap();
+        let mut remain = tocc_data;
+        loop {
+            let (desc, chunk_data, next) = ChunkDescriptor::slice(remain, e).unwrap();
+            if desc.id == id {
+                return chunk_data.as_ptr() as usize - data.as_ptr() as usize;
+            }
+            remain = next;
+        }
+    }
+
+    /// Offset of the ADIR entries in package data, following the entry count.
+    fn adir_entries_offset(data: &[u8]) -> usize {
+        chunk_data_offset(data, Endian::Little, K_CHUNK_ADIR) + 4
+    }
+
+    /// Reads package data every way, which must fail or succeed without panicking.
+    fn read_untrusted(data: &[u8]) {
+        if let Ok(package) = Package::read(data, Endian::Little) {
+            for asset in &package.assets {
+                let _ = asset.data();
+            }
+        }
+        if let Ok(lazy) = LazyPackage::read(data, Endian::Little) {
+            for index in 0..lazy.len() {
+                let _ = lazy.data(index);
+            }
+        }
+        let _ = Package::verify(data, Endian::Little);
+        let _ = Package::describe(data, Endian::Little);
+        let _ = Package::stats(data, Endian::Little);
+    }
+
+    #[test]
+    fn untrusted_sizes_and_offsets_do_not_panic() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        for len in 0..data.len() {
+            read_untrusted(&data[..len]);
+        }
+
+        let entries = adir_entries_offset(&data);
+        // Offset, decompressed size and size fields of the first entry
+        for field in [28, 36, 44] {
+            for value in [u64::MAX, u64::MAX - 1, 1 << 40, data.len() as u64, 0] {
+                let mut patched = data.clone();
+                patched[entries + field..entries + field + 8].copy_from_slice(&value.to_le_bytes());
+                read_untrusted(&patched);
+            }
+        }
+    }
+
+    #[test]
+    fn over_large_decompressed_size_is_rejected() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let mut patched = data.clone();
+        let field = adir_entries_offset(&data) + 36;
+        patched[field..field + 8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        let lazy = LazyPackage::read(&patched, Endian::Little).unwrap();
+        let err = lazy.data(0).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(PackError::DecompressedSizeTooLarge { mode: 2, decompressed_size, .. })
+                if *decompressed_size == 1 << 40
+        ));
+        let header = lazy.headers().next().unwrap();
+        let block = slice_range(&patched, header.offset, header.size).unwrap();
+        assert!(decompress_buffer(block, 1 << 40).is_err());
+    }
+
+    #[cfg(feature = "experimental-deflate")]
+    #[test]
+    fn deflate_package_round_trip() {
+        use crate::util::lzss::DEFLATE_MODE;
+
+        let package = sample_package(DEFLATE_MODE);
+        let data = package.to_vec(Endian::Little).unwrap();
+        let read = Package::read(&data, Endian::Little).unwrap();
+        for (asset, original) in read.assets.iter().zip(&package.assets) {
+            assert_eq!(asset.info.compression_mode, DEFLATE_MODE);
+            assert_eq!(asset.data().unwrap(), original.data().unwrap());
+        }
+    }
+
+    #[test]
+    fn string_table_kind_byte_order() {
+        let mut builder = PackageBuilder::new();
+        builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(1, 64))).with_name("texture");
+        let package = builder.build();
+        // Type is stored as a u32 in file byte order
+        for (e, stored) in [(Endian::Little, *b"RTXT"), (Endian::Big, *b"TXTR")] {
+            let data = package.to_vec(e).unwrap();
+            let strg = chunk_data_offset(&data, e, K_CHUNK_STRG);
+            // Entry count, then the first entry's type
+            assert_eq!(data[strg + 4..strg + 8], stored);
+
+            let (tocc_data, _) = slice_tocc(&data, e).unwrap();
+            let toc = TableOfContents::read(tocc_data, e).unwrap();
+            assert_eq!(toc.names[&package.assets[0].id], "texture");
+            let read = Package::read(&data, e).unwrap();
+            assert_eq!(read.assets[0].kind, K_TEST_TXTR);
+            assert_eq!(read.assets[0].name.as_deref(), Some("texture"));
+            assert_eq!(read.to_vec(e).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn toc_chunk_order_does_not_affect_read() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let (tocc_data, _) = slice_tocc(&data, Endian::Little).unwrap();
+        let start = tocc_data.as_ptr() as usize - data.as_ptr() as usize;
+        let mut chunks = Vec::new();
+        let mut remain = tocc_data;
+        while !is_padding(remain) {
+            let (desc, _, next) = ChunkDescriptor::slice(remain, Endian::Little).unwrap();
+            chunks.push((desc.id, &remain[..remain.len() - next.len()]));
+            remain = next;
+        }
+        assert_eq!(chunks.iter().map(|(id, _)| *id).collect::<Vec<_>>(), [
+            K_CHUNK_ADIR,
+            K_CHUNK_META,
+            K_CHUNK_STRG
+        ]);
+
+        // Same chunks, reordered as STRG, META, ADIR; data offsets are unaffected
+        let reordered: Vec<u8> =
+            chunks.iter().rev().flat_map(|(_, chunk)| chunk.iter().copied()).collect();
+        let mut patched = data.clone();
+        patched[start..start + reordered.len()].copy_from_slice(&reordered);
+
+        let expected = Package::read(&data, Endian::Little).unwrap();
+        let read = Package::read(&patched, Endian::Little).unwrap();
+        assert_eq!(read.assets.len(), expected.assets.len());
+        for (asset, original) in read.assets.iter().zip(&expected.assets) {
+            assert_eq!(asset.id, original.id);
+            assert_eq!(asset.kind, original.kind);
+            assert_eq!(asset.name, original.name);
+            assert_eq!(asset.meta, original.meta);
+            assert_eq!(asset.data().unwrap(), original.data().unwrap());
+        }
+    }
+
+    #[test]
+    fn built_package_data_order() {
+        let mut builder = PackageBuilder::new();
+        let ids: Vec<Uuid> = (0..4u8)
+            .map(|i| builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(i, 64))).id())
+            .collect();
+        let package = builder.build();
+        let offsets = |ordering| {
+            let options = WriteOptions { ordering, ..Default::default() };
+            let data = package.to_vec_with_options(Endian::Little, &options).unwrap();
+            let read = Package::read(&data, Endian::Little).unwrap();
+            let mut assets: Vec<_> =
+                read.assets.iter().map(|a| (a.info.orig_offset, a.id)).collect();
+            assets.sort();
+            assets.into_iter().map(|(_, id)| id).collect::<Vec<_>>()
+        };
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(offsets(AssetOrdering::Directory), sorted);
+        assert_eq!(offsets(AssetOrdering::PreserveOriginal), ids);
+    }
+
+    #[test]
+    fn stats_sum_directory_sizes() {
+        let package = sample_package(2);
+        let data = package.to_vec(Endian::Little).unwrap();
+        let stats = Package::stats(&data, Endian::Little).unwrap();
+        assert_eq!(stats.asset_count, 4);
+        let decompressed = package.assets.iter().map(|a| a.decompressed_size()).sum::<u64>();
+        assert_eq!(stats.total_decompressed, decompressed);
+        assert_eq!(stats.by_type[&K_TEST_TXTR].0, 2);
+        assert_eq!(stats.by_type[&K_TEST_CMDL].0, 2);
+
+        // Untrusted sizes saturate
+        let mut patched = data.clone();
+        let entries = adir_entries_offset(&data);
+        for index in 0..2 {
+            for field in [36, 44] {
+                let pos = entries + index * 52 + field;
+                patched[pos..pos + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+            }
+        }
+        let stats = Package::stats(&patched, Endian::Little).unwrap();
+        assert_eq!((stats.total_compressed, stats.total_decompressed), (u64::MAX, u64::MAX));
+    }
+
+    /// Reads package data and writes it back with its original layout and tables, asserting
+    /// that the output reproduces the input.
+    ///
+    /// The only difference allowed is the zero padding after the last asset data block, which
+    /// is written to [`WriteOptions::final_alignment`] but may be aligned differently in the
+    /// input.
+    pub fn assert_roundtrip(data: &[u8], e: Endian) {
+        let package = Package::read(data, e).unwrap();
+        let options = WriteOptions {
+            ordering: AssetOrdering::PreserveOriginal,
+            preserve_raw_tables: true,
+            ..Default::default()
+        };
+        let written = package.to_vec_with_options(e, &options).unwrap();
+
+        let (tocc_data, toc_end) = slice_tocc(data, e).unwrap();
+        let directory = TableOfContents::read(tocc_data, e).unwrap().directory;
+        let end = directory
+            .entries
+            .iter()
+            .map(|entry| (entry.offset + entry.size) as usize)
+            .fold(toc_end, usize::max);
+        assert!(written.len() >= end, "Repacked package is shorter than {:#X} bytes", end);
+        if let Some(offset) = data[..end].iter().zip(&written[..end]).position(|(a, b)| a != b) {
+            panic!("Repacked package differs from the original at offset {:#X}", offset);
+        }
+        for (padding, name) in [(&data[end..], "Original"), (&written[end..], "Repacked")] {
+            assert!(
+                padding.len() < options.final_alignment as usize && is_padding(padding),
+                "{} package has {:#X} bytes after its data, which are not alignment padding",
+                name,
+                padding.len()
+            );
+        }
+    }
+
+    #[test]
+    fn fixture_packages_round_trip() {
+        assert_roundtrip(include_bytes!("../../fixtures/mixed-le.pak"), Endian::Little);
+        assert_roundtrip(include_bytes!("../../fixtures/mixed-be.pak"), Endian::Big);
+    }
+
+    #[test]
+    fn round_trip_allows_only_alignment_padding() {
+        let data = include_bytes!("../../fixtures/mixed-le.pak").to_vec();
+        let (tocc_data, _) = slice_tocc(&data, Endian::Little).unwrap();
+        let directory = TableOfContents::read(tocc_data, Endian::Little).unwrap().directory;
+        let end = directory.entries.iter().map(|e| (e.offset + e.size) as usize).max().unwrap();
+        assert_roundtrip(&data[..end], Endian::Little);
+
+        // Trailing data that isn't padding, or is more than the alignment, is reported
+        let result = std::panic::catch_unwind(|| {
+            let mut extended = data.clone();
+            extended.extend_from_slice(&[0; 16]);
+            assert_roundtrip(&extended, Endian::Little);
+        });
+        assert!(result.is_err());
+        let result = std::panic::catch_unwind(|| {
+            let mut extended = data[..end].to_vec();
+            extended.push(1);
+            assert_roundtrip(&extended, Endian::Little);
+        });
+        assert!(result.is_err());
+    }
+
+    /// Package data with the PACK and TOCC form header `unk` and `version_b` fields patched.
+    pub fn package_with_form_fields(e: Endian, fields: FormFields) -> Vec<u8> {
+        let mut data = sample_package(2).to_vec(e).unwrap();
+        for (start, (unk, version_b)) in [(0, fields.pack), (FORM_DESCRIPTOR_SIZE, fields.tocc)] {
+            let mut cursor = Cursor::new(&mut data[start..]);
+            let mut form = FormDescriptor::read(&mut cursor, e).unwrap();
+            (form.unk, form.version_b) = (unk, version_b);
+            cursor.set_position(0);
+            cursor.write_type(&form, e).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn form_fields_replayed_by_every_writer() {
+        let fields = FormFields { pack: (0x1234, 2), tocc: (0x5678, 3) };
+        for e in [Endian::Little, Endian::Big] {
+            let data = package_with_form_fields(e, fields);
+            assert_eq!(FormFields::read(&data, e).unwrap(), fields);
+            let package = Package::read(&data, e).unwrap();
+
+            let mut written = vec![package.to_vec(e).unwrap()];
+            let (mut toc, mut region) = (Cursor::new(Vec::new()), Cursor::new(Vec::new()));
+            package.write_split(&mut toc, &mut region, e, &WriteOptions::default()).unwrap();
+            written.push(toc.into_inner());
+            let mut verified = Vec::new();
+            package.write_verified(&mut verified, e).unwrap();
+            written.push(verified);
+            let mut edited = Cursor::new(Vec::new());
+            EditablePackage::new(LazyPackage::read(&data, e).unwrap()).write(&mut edited).unwrap();
+            written.push(edited.into_inner());
+            for written in written {
+                assert_eq!(FormFields::read(&written, e).unwrap(), fields);
+            }
+        }
+    }
+
+    #[test]
+    fn form_other_versions_round_trip() {
+        // New packages use the previous constants
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        assert_eq!(FormFields::read(&data, Endian::Little).unwrap(), FormFields::default());
+        assert_eq!(FormFields::default(), FormFields { pack: (0, 1), tocc: (0, 3) });
+
+        let fields = FormFields { pack: (0, 3), tocc: (0, 1) };
+        let data = package_with_form_fields(Endian::Little, fields);
+        let package = Package::read(&data, Endian::Little).unwrap();
+        let written = package.to_vec(Endian::Little).unwrap();
+        assert_eq!(FormFields::read(&written, Endian::Little).unwrap(), fields);
+        let read = Package::read(&written, Endian::Little).unwrap();
+        assert_eq!(read.source.as_ref().unwrap().forms, fields);
+
+        // Written headers are checked against the source package
+        let other = package_with_form_fields(Endian::Little, FormFields::default());
+        let err = package.verify_written(&other, Endian::Little).unwrap_err();
+        assert!(err.to_string().contains("form header fields"));
+    }
+
+    #[test]
+    fn mixed_metadata_written_against_each_asset() {
+        let mut builder = PackageBuilder::new();
+        for i in 0..6u8 {
+            let asset = builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(i, 64)));
+            if matches!(i, 1 | 2 | 4) {
+                asset.with_meta(vec![i; 4 + i as usize]);
+            }
+        }
+        let package = builder.build();
+        let expected: HashMap<Uuid, Option<Vec<u8>>> =
+            package.assets.iter().map(|a| (a.id, a.meta_bytes().map(<[u8]>::to_vec))).collect();
+        assert_eq!(expected.values().filter(|meta| meta.is_some()).count(), 3);
+
+        let data = package.to_vec(Endian::Little).unwrap();
+        let read = Package::read(&data, Endian::Little).unwrap();
+        for asset in &read.assets {
+            assert_eq!(asset.meta_bytes(), expected[&asset.id].as_deref(), "asset {}", asset.id);
+        }
+        let lazy = LazyPackage::read(&data, Endian::Little).unwrap();
+        for header in lazy.headers() {
+            assert_eq!(header.meta.as_deref(), expected[&header.id].as_deref());
+        }
+    }
+
+    /// IDs of the TOCC chunks in package data, in order.
+    fn toc_chunk_ids(data: &[u8]) -> Vec<FourCC> {
+        let (mut remain, _) = slice_tocc(data, Endian::Little).unwrap();
+        let mut ids = Vec::new();
+        while !is_padding(remain) {
+            let (desc, _, next) = ChunkDescriptor::slice(remain, Endian::Little).unwrap();
+            ids.push(desc.id);
+            remain = next;
+        }
+        ids
+    }
+
+    #[test]
+    fn empty_tables_omitted_on_request() {
+        let mut builder = PackageBuilder::new();
+        for i in 0..2u8 {
+            builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(i, 64)));
+        }
+        let package = builder.build();
+        let all = [K_CHUNK_ADIR, K_CHUNK_META, K_CHUNK_STRG];
+
+        let with_tables = package.to_vec(Endian::Little).unwrap();
+        assert_eq!(toc_chunk_ids(&with_tables), all);
+        let options = WriteOptions { omit_empty_tables: true, ..Default::default() };
+        let without_tables = package.to_vec_with_options(Endian::Little, &options).unwrap();
+        assert_eq!(toc_chunk_ids(&without_tables), [K_CHUNK_ADIR]);
+
+        // Read packages keep whether their tables were present, whatever the option
+        for (data, ids) in [(&with_tables, &all[..]), (&without_tables, &all[..1])] {
+            let read = Package::read(data, Endian::Little).unwrap();
+            for asset in &read.assets {
+                assert_eq!((asset.name.as_deref(), asset.meta_bytes()), (None, None));
+            }
+            for options in [WriteOptions::default(), options.clone()] {
+                let written = read.to_vec_with_options(Endian::Little, &options).unwrap();
+                assert_eq!(toc_chunk_ids(&written), ids);
+            }
+        }
+    }
+
+    #[test]
+    fn into_owned_round_trips_without_source() {
+        for mode in 0..=3 {
+            let original = sample_package(mode).to_vec(Endian::Little).unwrap();
+            let owned: Package<'static> = {
+                let data = original.clone();
+                let package = Package::read(&data, Endian::Little).unwrap();
+                // Decompress some assets before detaching, and leave the rest pending
+                package.assets[0].data().unwrap();
+                package.into_owned()
+            };
+            assert_eq!(owned.to_vec(Endian::Little).unwrap(), original, "mode {}", mode);
+            let options = WriteOptions {
+                ordering: AssetOrdering::PreserveOriginal,
+                preserve_raw_tables: true,
+                ..Default::default()
+            };
+            assert_eq!(owned.to_vec_with_options(Endian::Little, &options).unwrap(), original);
+            for (asset, expected) in owned.assets.iter().zip(&sample_package(mode).assets) {
+                assert_eq!(asset.data().unwrap(), expected.data().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn unsorted_metadata_table() {
+        for e in [Endian::Little, Endian::Big] {
+            let package = sample_package(2);
+            let data = package.to_vec(e).unwrap();
+            let meta = chunk_data_offset(&data, e, K_CHUNK_META);
+            let (_, chunk_data, _) =
+                ChunkDescriptor::slice(&data[meta - CHUNK_DESCRIPTOR_SIZE..], e).unwrap();
+            let table: MetadataTable = Cursor::new(chunk_data).read_type(e).unwrap();
+            assert_eq!(table.entries.len(), 2);
+            assert!(table.entries[0].offset < table.entries[1].offset);
+
+            // Swap the table entries, so offsets are in descending order
+            let mut patched = data.clone();
+            let entries = meta + 4;
+            patched[entries..entries + 20].copy_from_slice(&data[entries + 20..entries + 40]);
+            patched[entries + 20..entries + 40].copy_from_slice(&data[entries..entries + 20]);
+            let read = Package::read(&patched, e).unwrap();
+            for (asset, original) in read.assets.iter().zip(&package.assets) {
+                assert_eq!(asset.meta_bytes(), original.meta_bytes(), "asset {}", asset.id);
+            }
+
+            // Offsets past the end of the chunk are rejected
+            let mut patched = data.clone();
+            let offset = match e {
+                Endian::Little => (chunk_data.len() as u32 - 2).to_le_bytes(),
+                Endian::Big => (chunk_data.len() as u32 - 2).to_be_bytes(),
+            };
+            patched[entries + 16..entries + 20].copy_from_slice(&offset);
+            let err = Package::read(&patched, e).unwrap_err();
+            assert!(err.to_string().contains("metadata out of bounds"));
+        }
+    }
 }
@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+
+use binrw::Endian;
+use sha2::{Digest, Sha256};
+use uuid::{Builder, Uuid};
+
+use crate::format::{
+    pack::{Asset, AssetInfo, Package},
+    rfrm::FormDescriptor,
+    FourCC,
+};
+
+/// Builds a [`Package`] from asset data, assigning asset IDs and asset info.
+#[derive(Debug, Clone, Default)]
+pub struct PackageBuilder<'a> {
+    assets: Vec<Asset<'a>>,
+}
+
+/// Asset added to a [`PackageBuilder`]
+#[derive(Debug)]
+pub struct AssetBuilder<'b, 'a> {
+    asset: &'b mut Asset<'a>,
+}
+
+impl<'a> PackageBuilder<'a> {
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds an asset, stored uncompressed by default.
+    ///
+    /// The asset ID is derived from the asset type, data and number of assets added before it,
+    /// so building the same assets in the same order assigns the same IDs. Versions are read
+    /// from the RFRM form header, if present.
+    pub fn add_asset<D: Into<Cow<'a, [u8]>>>(
+        &mut self,
+        kind: FourCC,
+        data: D,
+    ) -> AssetBuilder<'_, 'a> {
+        let data = data.into();
+        let mut hasher = Sha256::new();
+        hasher.update(kind.0);
+        hasher.update((self.assets.len() as u64).to_le_bytes());
+        hasher.update(&data);
+        let digest = hasher.finalize();
+        let id = Builder::from_random_bytes(digest[..16].try_into().unwrap()).into_uuid();
+        let (version, other_version) = FormDescriptor::slice(&data, Endian::Little)
+            .map_or((0, 0), |(form, _, _)| (form.version_a, form.version_b));
+        self.assets.push(Asset {
+            id,
+            kind,
+            name: None,
+            data: Some(data),
+            compressed: None,
+            pending: None,
+            meta: None,
+            info: AssetInfo { id, compression_mode: 0, orig_offset: self.assets.len() as u64 },
+            version,
+            other_version,
+        });
+        AssetBuilder { asset: self.assets.last_mut().unwrap() }
+    }
+
+    /// Builds the package. Assets are sorted by ID. Their data is written in ID order with the
+    /// default [`AssetOrdering::Directory`](super::AssetOrdering::Directory), or in the order
+    /// they were added with
+    /// [`AssetOrdering::PreserveOriginal`](super::AssetOrdering::PreserveOriginal).
+    pub fn build(mut self) -> Package<'a> {
+        self.assets.sort_by_key(|a| a.id);
+        Package { assets: self.assets, ..Default::default() }
+    }
+}
+
+impl AssetBuilder<'_, '_> {
+    /// ID assigned to the asset.
+    pub fn id(&self) -> Uuid { self.asset.id }
+
+    pub fn with_name<S: Into<String>>(self, name: S) -> Self {
+        self.asset.name = Some(name.into());
+        self
+    }
+
+    pub fn with_meta<M: Into<Vec<u8>>>(self, meta: M) -> Self {
+        self.asset.meta = Some(Cow::Owned(meta.into()));
+        self
+    }
+
+    /// Versions to write to the asset directory, which must match the RFRM form header.
+    pub fn with_version(self, version: u32, other_version: u32) -> Self {
+        self.asset.version = version;
+        self.asset.other_version = other_version;
+        self
+    }
+
+    /// Compressed data block to write as-is, compressed with `mode`. The block must start
+    /// with the 4-byte mode header, and decompress to the asset data.
+    pub fn with_compressed<B: Into<Vec<u8>>>(self, block: B, mode: u32) -> Self {
+        self.asset.compressed = Some(Cow::Owned(block.into()));
+        self.asset.info.compression_mode = mode;
+        self
+    }
+
+    /// Compression mode used when writing. Mode 0 stores the asset uncompressed.
+    pub fn with_compression(self, mode: u32) -> Self {
+        self.asset.info.compression_mode = mode;
+        self
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{bail, Result};
+use binrw::Endian;
+use uuid::Uuid;
+
+use crate::format::{
+    pack::{Asset, Package},
+    FourCC,
+};
+
+// World and area asset types, which group the assets they reference
+const GROUPING_TYPES: [FourCC; 2] = [FourCC(*b"MLVL"), FourCC(*b"MREA")];
+
+impl<'a> Package<'a> {
+    /// Rewrites asset IDs using `map`, along with references to them in asset metadata.
+    /// IDs not in the map are left unchanged. Fails without modifying the package if two
+    /// assets would end up with the same ID.
+    ///
+    /// Assets are re-sorted by their new IDs, so any journaled edits are discarded.
+    pub fn remap_ids(&mut self, map: &HashMap<Uuid, Uuid>) -> Result<()> {
+        let mut ids = HashSet::with_capacity(self.assets.len());
+        for asset in &self.assets {
+            let id = map.get(&asset.id).copied().unwrap_or(asset.id);
+            if !ids.insert(id) {
+                bail!("Remapping asset {} would duplicate asset ID {}", asset.id, id);
+            }
+        }
+        let e = self.endian();
+        for asset in &mut self.assets {
+            if let Some(&id) = map.get(&asset.id) {
+                asset.id = id;
+                asset.info.id = id;
+            }
+            if let Some(meta) = &mut asset.meta {
+                let references = find_references(meta, e, |id| map.contains_key(&id));
+                if !references.is_empty() {
+                    let data = meta.to_mut();
+                    for (offset, id) in references {
+                        data[offset..offset + 16].copy_from_slice(&uuid_bytes(map[&id], e));
+                    }
+                }
+            }
+        }
+        self.assets.sort_by_key(|a| a.id);
+        if let Some(journal) = &mut self.journal {
+            journal.clear();
+        }
+        Ok(())
+    }
+
+    /// Suggests an asset order that places assets referenced by the same world or area
+    /// next to each other, for better locality when loading.
+    ///
+    /// Each world and area asset is followed by the assets it references that weren't already
+    /// placed. Remaining assets follow in their current order.
+    pub fn suggested_order(&self) -> Result<Vec<Uuid>> {
+        let ids: HashSet<Uuid> = self.assets.iter().map(|a| a.id).collect();
+        let e = self.endian();
+        let mut placed = HashSet::with_capacity(self.assets.len());
+        let mut order = Vec::with_capacity(self.assets.len());
+        for asset in self.assets.iter().filter(|a| GROUPING_TYPES.contains(&a.kind)) {
+            if placed.insert(asset.id) {
+                order.push(asset.id);
+            }
+            let data = asset.meta.iter().map(|m| m.as_ref()).chain([asset.data()?]);
+            for data in data {
+                for (_, id) in find_references(data, e, |id| ids.contains(&id)) {
+                    if placed.insert(id) {
+                        order.push(id);
+                    }
+                }
+            }
+        }
+        for asset in &self.assets {
+            if placed.insert(asset.id) {
+                order.push(asset.id);
+            }
+        }
+        Ok(order)
+    }
+
+    /// Assets referenced by each asset's metadata, in the order they appear. References to
+    /// IDs not in the package are ignored, and assets without metadata have no dependencies.
+    pub fn dependency_graph(&self) -> HashMap<Uuid, Vec<Uuid>> {
+        let ids: HashSet<Uuid> = self.assets.iter().map(|a| a.id).collect();
+        let e = self.endian();
+        self.assets
+            .iter()
+            .map(|asset| {
+                let mut seen = HashSet::new();
+                let dependencies = asset
+                    .meta
+                    .as_deref()
+                    .map(|meta| find_references(meta, e, |id| id != asset.id && ids.contains(&id)))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(_, id)| id)
+                    .filter(|&id| seen.insert(id))
+                    .collect();
+                (asset.id, dependencies)
+            })
+            .collect()
+    }
+
+    /// Assets transitively referenced from `roots` according to
+    /// [`Package::dependency_graph`], including the roots themselves.
+    pub fn closure(&self, roots: &[Uuid]) -> HashSet<Uuid> {
+        let graph = self.dependency_graph();
+        let mut result: HashSet<Uuid> = roots.iter().copied().collect();
+        let mut queue: VecDeque<Uuid> = roots.iter().copied().collect();
+        while let Some(id) = queue.pop_front() {
+            for &dependency in graph.get(&id).into_iter().flatten() {
+                if result.insert(dependency) {
+                    queue.push_back(dependency);
+                }
+            }
+        }
+        result
+    }
+
+    /// Copies the given assets, with their names and metadata, into a new package. IDs not in
+    /// the package are ignored. Combine with [`Package::closure`] to include dependencies.
+    ///
+    /// [`orig_offset`](super::AssetInfo::orig_offset) is renumbered from 0, keeping the
+    /// assets' original relative order. The new package is not tied to the source package data.
+    pub fn subset(&self, ids: &[Uuid]) -> Package<'a> {
+        let ids: HashSet<Uuid> = ids.iter().copied().collect();
+        let mut assets: Vec<Asset<'a>> =
+            self.assets.iter().filter(|a| ids.contains(&a.id)).cloned().collect();
+        let mut order: Vec<usize> = (0..assets.len()).collect();
+        order.sort_by_key(|&i| assets[i].info.orig_offset);
+        for (offset, index) in order.into_iter().enumerate() {
+            assets[index].info.orig_offset = offset as u64;
+        }
+        Package { assets, unknown_chunks: self.unknown_chunks.clone(), ..Default::default() }
+    }
+
+    /// Byte order of UUIDs in asset data: that of the package data the package was read from,
+    /// or little-endian for new packages.
+    pub(super) fn endian(&self) -> Endian {
+        self.source.as_ref().map_or(Endian::Little, |s| s.endian)
+    }
+}
+
+/// UUID bytes in the given byte order. See [`parse_uuid`](crate::format::parse_uuid).
+fn uuid_bytes(id: Uuid, e: Endian) -> [u8; 16] {
+    match e {
+        Endian::Little => id.to_bytes_le(),
+        Endian::Big => id.into_bytes(),
+    }
+}
+
+/// Scans data for UUIDs in byte order `e` accepted by `filter`, returning their offsets and
+/// values.
+///
+/// Asset data and metadata have no common layout, so any 16-byte window matching a known ID is treated
+/// as a reference.
+pub(super) fn find_references<F>(data: &[u8], e: Endian, filter: F) -> Vec<(usize, Uuid)>
+where F: Fn(Uuid) -> bool {
+    let mut result = vec![];
+    let mut offset = 0;
+    while offset + 16 <= data.len() {
+        let bytes = data[offset..offset + 16].try_into().unwrap();
+        let id = match e {
+            Endian::Little => Uuid::from_bytes_le(bytes),
+            Endian::Big => Uuid::from_bytes(bytes),
+        };
+        if !id.is_nil() && filter(id) {
+            result.push((offset, id));
+            offset += 16;
+        } else {
+            offset += 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::pack::{
+        tests::{payload, rfrm, K_TEST_CMDL, K_TEST_TXTR},
+        PackageBuilder,
+    };
+
+    /// Package with a texture, and a model whose metadata references the texture with the
+    /// given byte order.
+    fn referencing_package(e: Endian) -> (Vec<u8>, Uuid, Uuid) {
+        let mut builder = PackageBuilder::new();
+        let txtr = builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(0, 64))).id();
+        let mut meta = vec![0xFF; 5];
+        meta.extend_from_slice(&uuid_bytes(txtr, e));
+        let cmdl =
+            builder.add_asset(K_TEST_CMDL, rfrm(K_TEST_CMDL, &payload(1, 64))).with_meta(meta).id();
+        (builder.build().to_vec(e).unwrap(), txtr, cmdl)
+    }
+
+    /// Remaps the texture referenced by a model, checking the remapped package written and read
+    /// back with byte order `e`.
+    fn check_remap(e: Endian) {
+        let (data, txtr, cmdl) = referencing_package(e);
+        let mut package = Package::read(&data, e).unwrap();
+        assert_eq!(package.dependency_graph()[&cmdl], vec![txtr]);
+
+        let new_txtr = Uuid::from_u128(0x1234);
+        package.remap_ids(&HashMap::from([(txtr, new_txtr)])).unwrap();
+        let model = package.assets.iter().find(|a| a.id == cmdl).unwrap();
+        assert_eq!(model.meta.as_deref().unwrap()[5..], uuid_bytes(new_txtr, e));
+
+        let written = package.to_vec(e).unwrap();
+        let read = Package::read(&written, e).unwrap();
+        let texture = read.assets.iter().find(|a| a.kind == K_TEST_TXTR).unwrap();
+        assert_eq!((texture.id, texture.info.id), (new_txtr, new_txtr));
+        assert_eq!(read.dependency_graph()[&cmdl], vec![new_txtr]);
+    }
+
+    #[test]
+    fn remap_updates_directory_and_references() { check_remap(Endian::Little); }
+
+    #[test]
+    fn remap_big_endian() { check_remap(Endian::Big); }
+
+    #[test]
+    fn find_references_honours_byte_order() {
+        let id = Uuid::from_u128(0x0011_2233_4455_6677_8899_AABB_CCDD_EEFF);
+        for e in [Endian::Little, Endian::Big] {
+            let mut data = vec![0u8; 3];
+            data.extend_from_slice(&uuid_bytes(id, e));
+            assert_eq!(find_references(&data, e, |i| i == id), vec![(3, id)]);
+        }
+        assert!(find_references(&id.into_bytes(), Endian::Little, |i| i == id).is_empty());
+        assert!(find_references(&id.to_bytes_le(), Endian::Big, |i| i == id).is_empty());
+    }
+
+    #[test]
+    fn remap_rejects_duplicate_ids() {
+        let (data, txtr, cmdl) = referencing_package(Endian::Little);
+        let mut package = Package::read(&data, Endian::Little).unwrap();
+        assert!(package.remap_ids(&HashMap::from([(txtr, cmdl)])).is_err());
+        let swapped = HashMap::from([(txtr, cmdl), (cmdl, txtr)]);
+        assert!(package.assets.iter().any(|a| a.id == txtr && a.kind == K_TEST_TXTR));
+        package.remap_ids(&swapped).unwrap();
+        assert!(package.assets.iter().any(|a| a.id == cmdl && a.kind == K_TEST_TXTR));
+    }
+
+    #[test]
+    fn suggested_order_groups_area_references() {
+        let mut builder = PackageBuilder::new();
+        let ids: Vec<Uuid> = (0..4u8)
+            .map(|i| builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(i, 64))).id())
+            .collect();
+        let package = builder.clone().build();
+        let directory: Vec<Uuid> = package.assets.iter().map(|a| a.id).collect();
+        assert_eq!(package.suggested_order().unwrap(), directory);
+
+        let mut area = vec![];
+        for id in [ids[3], ids[1]] {
+            area.extend_from_slice(&id.to_bytes_le());
+        }
+        let area = builder.add_asset(GROUPING_TYPES[1], rfrm(GROUPING_TYPES[1], &area)).id();
+        let order = builder.build().suggested_order().unwrap();
+        assert_eq!(order[..3], [area, ids[3], ids[1]]);
+        let rest: Vec<Uuid> =
+            directory.into_iter().filter(|&id| id == ids[0] || id == ids[2]).collect();
+        assert_eq!(order[3..], rest);
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::format::pack::{Asset, Package};
+
+/// Differences between two packages, by asset ID. See [`Package::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageDiff {
+    /// Assets only in the first package
+    pub removed: Vec<Uuid>,
+    /// Assets only in the second package
+    pub added: Vec<Uuid>,
+    /// Assets in both packages that differ
+    pub changed: Vec<AssetChange>,
+}
+
+/// Asset present in both packages with differing fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetChange {
+    pub id: Uuid,
+    pub fields: Vec<AssetField>,
+}
+
+/// Asset field compared by [`Package::diff`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum AssetField {
+    Kind,
+    Name,
+    Version,
+    Meta,
+    /// Decompressed asset data
+    Data,
+}
+
+impl PackageDiff {
+    /// Whether the packages contain the same assets.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Package<'_> {
+    /// Compares the assets of this package with `other`. Compression is not compared.
+    pub fn diff(&self, other: &Package) -> Result<PackageDiff> {
+        let ids: HashSet<Uuid> = self.assets.iter().map(|a| a.id).collect();
+        let others: HashMap<Uuid, &Asset> = other.assets.iter().map(|a| (a.id, a)).collect();
+        let mut diff = PackageDiff::default();
+        for asset in &self.assets {
+            let Some(other) = others.get(&asset.id) else {
+                diff.removed.push(asset.id);
+                continue;
+            };
+            let fields = changed_fields(asset, other)?;
+            if !fields.is_empty() {
+                diff.changed.push(AssetChange { id: asset.id, fields });
+            }
+        }
+        diff.added = other.assets.iter().filter(|a| !ids.contains(&a.id)).map(|a| a.id).collect();
+        Ok(diff)
+    }
+}
+
+fn changed_fields(a: &Asset, b: &Asset) -> Result<Vec<AssetField>> {
+    let mut fields = vec![];
+    if a.kind != b.kind {
+        fields.push(AssetField::Kind);
+    }
+    if a.name != b.name {
+        fields.push(AssetField::Name);
+    }
+    if (a.version, a.other_version) != (b.version, b.other_version) {
+        fields.push(AssetField::Version);
+    }
+    if a.meta != b.meta {
+        fields.push(AssetField::Meta);
+    }
+    if a.data()? != b.data()? {
+        fields.push(AssetField::Data);
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::Endian;
+
+    use super::*;
+    use crate::format::pack::{
+        tests::{payload, rfrm, sample_package, K_TEST_TXTR},
+        PackageBuilder,
+    };
+
+    #[test]
+    fn diff_reports_each_change() {
+        let package = sample_package(2);
+        // Compression is not compared
+        let data = sample_package(0).to_vec(Endian::Little).unwrap();
+        let mut other = Package::read(&data, Endian::Little).unwrap();
+        assert!(package.diff(&other).unwrap().is_empty());
+
+        let ids: Vec<Uuid> = other.assets.iter().map(|a| a.id).collect();
+        other.assets.remove(0);
+        let kind = other.assets[0].kind;
+        other.assets[0].set_data(rfrm(kind, &payload(9, 256)));
+        other.assets[1].version += 1;
+        other.assets[2].name = Some("renamed".to_string());
+        let mut builder = PackageBuilder::new();
+        let added = builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(7, 64))).id();
+        other.assets.extend(builder.build().assets);
+
+        let diff = package.diff(&other).unwrap();
+        assert_eq!(diff, PackageDiff {
+            removed: vec![ids[0]],
+            added: vec![added],
+            changed: vec![
+                AssetChange { id: ids[1], fields: vec![AssetField::Data] },
+                AssetChange { id: ids[2], fields: vec![AssetField::Version] },
+                AssetChange { id: ids[3], fields: vec![AssetField::Name] },
+            ],
+        });
+        assert!(!diff.is_empty());
+        // The reverse diff swaps added and removed assets
+        let reverse = other.diff(&package).unwrap();
+        assert_eq!((reverse.removed, reverse.added), (vec![added], vec![ids[0]]));
+    }
+}
@@ -0,0 +1,35 @@
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::format::FourCC;
+
+/// Package read errors
+#[derive(Debug, Error)]
+pub enum PackError {
+    #[error("Asset {asset_id} nests forms deeper than the maximum depth {max_depth}")]
+    TooDeep { asset_id: Uuid, max_depth: usize },
+    #[error("Asset {asset_id} has type {expected}, but its RFRM form is {found}")]
+    FormIdMismatch { asset_id: Uuid, expected: FourCC, found: FourCC },
+    #[error("Asset {asset_id} has versions {expected:?}, but its RFRM form has {found:?}")]
+    VersionMismatch { asset_id: Uuid, expected: (u32, u32), found: (u32, u32) },
+    #[error("Asset {asset_id} size {expected:#X} does not match RFRM size {found:#X}")]
+    SizeMismatch { asset_id: Uuid, expected: u64, found: u64 },
+    #[error("Asset {asset_id} uses unsupported compression mode {mode}")]
+    UnsupportedCompression { asset_id: Uuid, mode: u32 },
+    #[error(
+        "Asset {asset_id} data at {offset:#X} size {size:#X} exceeds package data size {data_len:#X}"
+    )]
+    EntryOutOfBounds { asset_id: Uuid, offset: u64, size: u64, data_len: u64 },
+    #[error(
+        "Asset {asset_id} decompressed size {decompressed_size:#X} exceeds the maximum {max_size:#X} for its {size:#X} byte mode {mode} block"
+    )]
+    DecompressedSizeTooLarge {
+        asset_id: Uuid,
+        mode: u32,
+        size: u64,
+        decompressed_size: u64,
+        max_size: u64,
+    },
+    #[error("{id} size {size:#X} exceeds remaining data size {remaining:#X}")]
+    ChunkOutOfBounds { id: FourCC, size: u64, remaining: u64 },
+}
@@ -0,0 +1,158 @@
+use std::{
+    borrow::Cow,
+    fs,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use binrw::Endian;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::format::{
+    chunk::ChunkDescriptor,
+    pack::{
+        check_entry_bounds, entry_block, slice_tocc, write_toc_chunks, Package, ReadOptions,
+        StringTable, StringTableEntry, TableOfContents, K_CHUNK_DGST, K_FORM_PIDX,
+    },
+    rfrm::FormDescriptor,
+};
+
+impl<'a> Package<'a> {
+    /// Writes a sidecar index of the table of contents the package was read from.
+    ///
+    /// The index holds a digest of the package's TOCC, followed by the ADIR, META and STRG
+    /// chunks. It can be used with [`Package::read_with_index`] on subsequent reads.
+    pub fn write_index<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let Some(source) = &self.source else {
+            bail!("Package was not read from package data");
+        };
+        let directory = &source.toc.directory;
+        let meta: Vec<(Uuid, &[u8])> = directory
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                source.toc.meta.get(&entry.asset_id).map(|data| (entry.asset_id, data.as_ref()))
+            })
+            .collect();
+        let mut string_table = StringTable::default();
+        for entry in &directory.entries {
+            if let Some(name) = source.toc.names.get(&entry.asset_id) {
+                string_table.entries.push(StringTableEntry::new(
+                    entry.asset_type,
+                    entry.asset_id,
+                    source.toc.name_encoding(entry.asset_id).encode(name),
+                ));
+            }
+        }
+
+        let mut file = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("Failed to create file '{}'", path.display()))?,
+        );
+        FormDescriptor { size: 0, unk: 0, id: K_FORM_PIDX, version_a: 1, version_b: 1 }.write(
+            &mut file,
+            Endian::Little,
+            |w| {
+                ChunkDescriptor { id: K_CHUNK_DGST, size: 0, unk: 0, skip: 0 }.write(
+                    w,
+                    Endian::Little,
+                    |w| {
+                        w.write_all(&source.digest)?;
+                        Ok(())
+                    },
+                )?;
+                write_toc_chunks(
+                    w,
+                    Endian::Little,
+                    directory,
+                    Some(meta.as_slice()),
+                    Some(&string_table),
+                    &source.toc.unknown,
+                    Some(&source.toc.chunk_fields),
+                )?;
+                Ok(())
+            },
+        )?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Reads a package, using a sidecar index written by [`Package::write_index`] in place of
+    /// the package's own TOCC.
+    ///
+    /// Fails if the index digest doesn't match the package data.
+    pub fn read_with_index<P: AsRef<Path>>(
+        data: &'a [u8],
+        index_path: P,
+        e: Endian,
+    ) -> Result<Package<'a>> {
+        let path = index_path.as_ref();
+        let index = fs::read(path)
+            .with_context(|| format!("Failed to read index file '{}'", path.display()))?;
+        let (index_desc, index_data, _) = FormDescriptor::slice(&index, Endian::Little)?;
+        ensure!(index_desc.id == K_FORM_PIDX);
+        ensure!(index_desc.version_a == 1);
+        let (digest_desc, digest, toc_data) = ChunkDescriptor::slice(index_data, Endian::Little)?;
+        ensure!(digest_desc.id == K_CHUNK_DGST && digest.len() == 32, "Invalid index digest");
+
+        let (_, toc_len) = slice_tocc(data, e)?;
+        let expected: [u8; 32] = Sha256::digest(&data[..toc_len]).into();
+        ensure!(
+            digest == expected.as_slice(),
+            "Index file '{}' does not match package data",
+            path.display()
+        );
+        let toc = TableOfContents::read(toc_data, Endian::Little)?.into_owned();
+        check_entry_bounds(&toc.directory, data.len())?;
+        let header = Cow::Borrowed(&data[..toc_len]);
+        Self::read_assets(toc, expected, header, e, &ReadOptions::default(), |entry| {
+            entry_block(data, entry, e).map(Cow::Borrowed)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::pack::tests::{payload, rfrm, sample_package, temp_path, K_TEST_TXTR};
+
+    #[test]
+    fn read_with_index_matches_cold_read() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let cold = Package::read(&data, Endian::Little).unwrap();
+        let path = temp_path("index.pidx");
+        cold.write_index(&path).unwrap();
+
+        let indexed = Package::read_with_index(&data, &path, Endian::Little);
+        fs::remove_file(&path).unwrap();
+        let indexed = indexed.unwrap();
+        assert_eq!(indexed.assets.len(), cold.assets.len());
+        for (a, b) in indexed.assets.iter().zip(&cold.assets) {
+            assert_eq!((a.id, a.kind, &a.name, &a.meta), (b.id, b.kind, &b.name, &b.meta));
+            assert_eq!(a.data().unwrap(), b.data().unwrap());
+        }
+    }
+
+    #[test]
+    fn stale_index_is_rejected() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let path = temp_path("stale.pidx");
+        Package::read(&data, Endian::Little).unwrap().write_index(&path).unwrap();
+
+        let mut modified = sample_package(2);
+        modified.assets[0].set_data(rfrm(K_TEST_TXTR, &payload(5, 128)));
+        let modified = modified.to_vec(Endian::Little).unwrap();
+        let result = Package::read_with_index(&modified, &path, Endian::Little);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn write_index_requires_source() {
+        assert!(sample_package(0).write_index(temp_path("unread.pidx")).is_err());
+    }
+}
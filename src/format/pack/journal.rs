@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+
+use anyhow::{bail, Result};
+use uuid::Uuid;
+
+use crate::format::{
+    pack::{Asset, Package},
+    FourCC,
+};
+
+/// Package edit recorded by the journal, holding the state required to revert it
+#[derive(Debug, Clone)]
+pub enum PackageEdit<'a> {
+    /// Asset inserted at `index`
+    Insert { index: usize },
+    /// Asset removed from `index`
+    Remove { index: usize, asset: Asset<'a> },
+    /// Asset at `index` replaced; holds the previous asset
+    Replace { index: usize, asset: Asset<'a> },
+    /// Asset at `index` renamed; holds the previous name
+    Rename { index: usize, name: Option<String> },
+}
+
+/// Handling of assets present in both packages in [`Package::merge`]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum MergePolicy {
+    /// Replace the existing asset with the incoming one
+    #[default]
+    Overwrite,
+    /// Keep the existing asset
+    SkipExisting,
+    /// Fail without modifying the package
+    Error,
+}
+
+impl PackageEdit<'_> {
+    /// Copies any borrowed asset data held by the edit.
+    pub fn into_owned(self) -> PackageEdit<'static> {
+        match self {
+            PackageEdit::Insert { index } => PackageEdit::Insert { index },
+            PackageEdit::Remove { index, asset } => {
+                PackageEdit::Remove { index, asset: asset.into_owned() }
+            }
+            PackageEdit::Replace { index, asset } => {
+                PackageEdit::Replace { index, asset: asset.into_owned() }
+            }
+            PackageEdit::Rename { index, name } => PackageEdit::Rename { index, name },
+        }
+    }
+}
+
+impl<'a> Package<'a> {
+    /// Starts recording edits made through the editing methods, enabling [`Package::undo`].
+    ///
+    /// Edits are recorded by asset index, so `assets` must not be modified directly
+    /// while the journal is enabled.
+    pub fn enable_journal(&mut self) { self.journal.get_or_insert_with(Vec::new); }
+
+    /// Stops recording edits and discards the journal.
+    pub fn disable_journal(&mut self) { self.journal = None; }
+
+    /// Recorded edits, oldest first.
+    pub fn journal(&self) -> &[PackageEdit<'a>] { self.journal.as_deref().unwrap_or_default() }
+
+    /// Inserts a new asset, keeping assets ordered by ID. Returns the asset index.
+    pub fn insert_asset(&mut self, asset: Asset<'a>) -> Result<usize> {
+        if self.asset_index(asset.id).is_some() {
+            bail!("Asset {} already exists", asset.id);
+        }
+        let index = self.assets.partition_point(|a| a.id < asset.id);
+        self.assets.insert(index, asset);
+        self.record(|| PackageEdit::Insert { index });
+        Ok(index)
+    }
+
+    /// Removes an asset by ID, returning it if present.
+    pub fn remove_asset(&mut self, id: Uuid) -> Option<Asset<'a>> {
+        let index = self.asset_index(id)?;
+        let asset = self.assets.remove(index);
+        self.record(|| PackageEdit::Remove { index, asset: asset.clone() });
+        Some(asset)
+    }
+
+    /// Replaces the asset with the same ID, returning the previous asset.
+    pub fn replace_asset(&mut self, asset: Asset<'a>) -> Result<Asset<'a>> {
+        let Some(index) = self.asset_index(asset.id) else {
+            bail!("Asset {} not found", asset.id);
+        };
+        let previous = std::mem::replace(&mut self.assets[index], asset);
+        self.record(|| PackageEdit::Replace { index, asset: previous.clone() });
+        Ok(previous)
+    }
+
+    /// Sets the name of an asset by ID, returning the previous name.
+    pub fn rename_asset(&mut self, id: Uuid, name: Option<String>) -> Result<Option<String>> {
+        let Some(index) = self.asset_index(id) else {
+            bail!("Asset {} not found", id);
+        };
+        let previous = std::mem::replace(&mut self.assets[index].name, name);
+        self.record(|| PackageEdit::Rename { index, name: previous.clone() });
+        Ok(previous)
+    }
+
+    /// Adds the assets of `other`, resolving assets with the same ID according to `policy`.
+    /// Unknown TOCC chunks of `other` are not merged.
+    pub fn merge(&mut self, other: Package<'a>, policy: MergePolicy) -> Result<()> {
+        if policy == MergePolicy::Error {
+            if let Some(asset) = other.assets.iter().find(|a| self.asset_index(a.id).is_some()) {
+                bail!("Asset {} already exists", asset.id);
+            }
+        }
+        for asset in other.assets {
+            if self.asset_index(asset.id).is_none() {
+                self.insert_asset(asset)?;
+            } else if policy == MergePolicy::Overwrite {
+                self.replace_asset(asset)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes all assets of the given editor-only or debug asset types, returning the IDs
+    /// of the removed assets.
+    pub fn strip_debug(&mut self, types: &HashSet<FourCC>) -> Vec<Uuid> {
+        let mut ids = vec![];
+        let mut index = 0;
+        let journal = &mut self.journal;
+        self.assets.retain(|asset| {
+            if !types.contains(&asset.kind) {
+                index += 1;
+                return true;
+            }
+            // Journaled at the index it's removed from, after earlier removals
+            if let Some(journal) = journal {
+                journal.push(PackageEdit::Remove { index, asset: asset.clone() });
+            }
+            ids.push(asset.id);
+            false
+        });
+        ids
+    }
+
+    /// Reverts the most recent journaled edit. Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(edit) = self.journal.as_mut().and_then(Vec::pop) else {
+            return false;
+        };
+        match edit {
+            PackageEdit::Insert { index } => {
+                self.assets.remove(index);
+            }
+            PackageEdit::Remove { index, asset } => self.assets.insert(index, asset),
+            PackageEdit::Replace { index, asset } => self.assets[index] = asset,
+            PackageEdit::Rename { index, name } => self.assets[index].name = name,
+        }
+        true
+    }
+
+    fn asset_index(&self, id: Uuid) -> Option<usize> { self.assets.iter().position(|a| a.id == id) }
+
+    fn record<F>(&mut self, edit: F)
+    where F: FnOnce() -> PackageEdit<'a> {
+        if let Some(journal) = &mut self.journal {
+            journal.push(edit());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::pack::tests::{rfrm, sample_package, K_TEST_CMDL, K_TEST_TXTR};
+
+    fn summary(package: &Package) -> Vec<(Uuid, Option<String>, Vec<u8>)> {
+        package.assets.iter().map(|a| (a.id, a.name.clone(), a.data().unwrap().to_vec())).collect()
+    }
+
+    #[test]
+    fn undo_restores_original_state() {
+        let mut package = sample_package(0);
+        let original = summary(&package);
+        package.enable_journal();
+
+        let ids: Vec<Uuid> = package.assets.iter().map(|a| a.id).collect();
+        package
+            .insert_asset(Asset::new(Uuid::from_u128(1), K_TEST_TXTR, rfrm(K_TEST_TXTR, b"new")))
+            .unwrap();
+        package.remove_asset(ids[1]).unwrap();
+        package
+            .replace_asset(Asset::new(ids[2], K_TEST_TXTR, rfrm(K_TEST_TXTR, b"replaced")))
+            .unwrap();
+        package.rename_asset(ids[3], Some("renamed".to_string())).unwrap();
+        assert_eq!(package.journal().len(), 4);
+        assert_ne!(summary(&package), original);
+
+        while package.undo() {}
+        assert!(package.journal().is_empty());
+        assert_eq!(summary(&package), original);
+    }
+
+    #[test]
+    fn edits_are_not_recorded_without_journal() {
+        let mut package = sample_package(0);
+        let id = package.assets[0].id;
+        package.remove_asset(id).unwrap();
+        assert!(package.journal().is_empty());
+        assert!(!package.undo());
+        assert_eq!(package.assets.len(), 3);
+    }
+
+    #[test]
+    fn merge_policies() {
+        let mut package = sample_package(0);
+        let mut other = sample_package(0);
+        let replaced = Asset::new(package.assets[0].id, K_TEST_CMDL, rfrm(K_TEST_CMDL, b"x"));
+        other.assets = vec![replaced.clone()];
+
+        assert!(package.clone().merge(other.clone(), MergePolicy::Error).is_err());
+        let mut skipped = package.clone();
+        skipped.merge(other.clone(), MergePolicy::SkipExisting).unwrap();
+        assert_eq!(summary(&skipped), summary(&package));
+
+        package.merge(other, MergePolicy::Overwrite).unwrap();
+        assert_eq!(package.assets[0].data().unwrap(), replaced.data().unwrap());
+        assert_eq!(package.assets.len(), 4);
+    }
+
+    #[test]
+    fn strip_debug_removes_configured_types() {
+        let mut package = sample_package(0);
+        let original = summary(&package);
+        let cmdl: Vec<Uuid> =
+            package.assets.iter().filter(|a| a.kind == K_TEST_CMDL).map(|a| a.id).collect();
+        package.enable_journal();
+
+        let removed = package.strip_debug(&HashSet::from([K_TEST_CMDL]));
+        assert_eq!(removed, cmdl);
+        assert!(package.assets.iter().all(|a| a.kind == K_TEST_TXTR));
+        let kept: Vec<_> = original.iter().filter(|(id, ..)| !cmdl.contains(id)).cloned().collect();
+        assert_eq!(summary(&package), kept);
+        assert_eq!(package.journal().len(), 2);
+
+        while package.undo() {}
+        assert_eq!(summary(&package), original);
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    fmt::{Display, Formatter},
+    io::Cursor,
+};
+
+use anyhow::{ensure, Result};
+use binrw::{BinReaderExt, Endian};
+use uuid::Uuid;
+
+use crate::format::{
+    chunk::{is_padding, ChunkDescriptor},
+    pack::{
+        slice_range, unwrap_asset_block, AssetDirectory, AssetDirectoryEntry, Package,
+        DEFAULT_MAX_FORM_DEPTH, K_CHUNK_ADIR, K_FORM_PACK, K_FORM_TOCC,
+    },
+    peek_four_cc,
+    rfrm::{FormDescriptor, K_CHUNK_RFRM},
+    FourCC,
+};
+
+/// Forms and chunks of package data, located by their descriptors. See [`Package::describe`].
+///
+/// Displays as an indented tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageLayout {
+    /// PACK form, containing the TOCC form followed by asset data blocks in file order
+    pub root: LayoutNode,
+}
+
+/// Descriptor in a [`PackageLayout`]. Offsets are from the start of the package data, and
+/// sizes exclude the descriptor itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutNode {
+    Form {
+        offset: u64,
+        id: FourCC,
+        size: u64,
+        version_a: u32,
+        version_b: u32,
+        children: Vec<LayoutNode>,
+    },
+    Chunk {
+        offset: u64,
+        id: FourCC,
+        size: u64,
+        unk: u32,
+        skip: u64,
+    },
+    /// Asset data block from the asset directory. For blocks stored uncompressed, `children`
+    /// holds the asset's RFRM form.
+    Asset {
+        offset: u64,
+        size: u64,
+        id: Uuid,
+        kind: FourCC,
+        decompressed_size: u64,
+        children: Vec<LayoutNode>,
+    },
+    /// Data that couldn't be read as a descriptor
+    Invalid {
+        offset: u64,
+        error: String,
+    },
+}
+
+impl Package<'_> {
+    /// Describes the structure of package data from its form and chunk descriptors, without
+    /// decompressing any assets.
+    ///
+    /// Only the PACK and TOCC forms must be valid. Errors within asset data are reported as
+    /// [`LayoutNode::Invalid`], so damaged packages can still be inspected.
+    pub fn describe(data: &[u8], e: Endian) -> Result<PackageLayout> {
+        let (pack, pack_data, _) = FormDescriptor::slice(data, e)?;
+        ensure!(pack.id == K_FORM_PACK, "Expected PACK form, found {}", pack.id);
+        let tocc_offset = offset_in(data, pack_data);
+        let (tocc, mut tocc_data, _) = FormDescriptor::slice(pack_data, e)?;
+        ensure!(tocc.id == K_FORM_TOCC, "Expected TOCC form, found {}", tocc.id);
+        let mut chunks = vec![];
+        let mut directory = None;
+        while !is_padding(tocc_data) {
+            let offset = offset_in(data, tocc_data);
+            let (desc, chunk_data, remain) = ChunkDescriptor::slice(tocc_data, e)?;
+            if desc.id == K_CHUNK_ADIR {
+                directory = Some(Cursor::new(chunk_data).read_type::<AssetDirectory>(e)?);
+            }
+            chunks.push(chunk_node(offset, &desc));
+            tocc_data = remain;
+        }
+
+        let mut children = vec![form_node(tocc_offset, &tocc, chunks)];
+        let mut entries = directory.map(|directory| directory.entries).unwrap_or_default();
+        entries.sort_by_key(|entry| entry.offset);
+        children.extend(entries.iter().map(|entry| describe_asset(data, entry, e)));
+        Ok(PackageLayout { root: form_node(0, &pack, children) })
+    }
+}
+
+impl Display for PackageLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { self.root.fmt_tree(f, 0) }
+}
+
+impl LayoutNode {
+    fn fmt_tree(&self, f: &mut Formatter<'_>, indent: usize) -> std::fmt::Result {
+        let indstr = "  ".repeat(indent);
+        match self {
+            LayoutNode::Form { offset, id, size, version_a, version_b, children } => {
+                writeln!(f, "{indstr}{id} v{version_a}.{version_b} @ {offset:#X} size {size:#X}")?;
+                for child in children {
+                    child.fmt_tree(f, indent + 1)?;
+                }
+            }
+            LayoutNode::Chunk { offset, id, size, unk, skip } => {
+                writeln!(
+                    f,
+                    "{indstr}- {id} @ {offset:#X} size {size:#X} (unk {unk}, skip {skip:#X})"
+                )?;
+            }
+            LayoutNode::Asset { offset, size, id, kind, decompressed_size, children } => {
+                writeln!(
+                    f,
+                    "{indstr}{kind} {id} @ {offset:#X} size {size:#X} (decompressed {decompressed_size:#X})"
+                )?;
+                for child in children {
+                    child.fmt_tree(f, indent + 1)?;
+                }
+            }
+            LayoutNode::Invalid { offset, error } => {
+                writeln!(f, "{indstr}! @ {offset:#X}: {error}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn form_node(offset: u64, form: &FormDescriptor, children: Vec<LayoutNode>) -> LayoutNode {
+    LayoutNode::Form {
+        offset,
+        id: form.id,
+        size: form.size,
+        version_a: form.version_a,
+        version_b: form.version_b,
+        children,
+    }
+}
+
+fn chunk_node(offset: u64, desc: &ChunkDescriptor) -> LayoutNode {
+    LayoutNode::Chunk { offset, id: desc.id, size: desc.size, unk: desc.unk, skip: desc.skip }
+}
+
+fn describe_asset(data: &[u8], entry: &AssetDirectoryEntry, e: Endian) -> LayoutNode {
+    let mut children = vec![];
+    match slice_range(data, entry.offset, entry.size).map(|block| unwrap_asset_block(block, e)) {
+        Some(Ok(block)) if block.len() as u64 == entry.decompressed_size => {
+            children.push(describe_form(data, block, 1).0);
+        }
+        Some(Ok(_)) => {}
+        Some(Err(err)) => {
+            children.push(LayoutNode::Invalid { offset: entry.offset, error: err.to_string() })
+        }
+        None => children.push(LayoutNode::Invalid {
+            offset: entry.offset,
+            error: "Asset data out of bounds".to_string(),
+        }),
+    }
+    LayoutNode::Asset {
+        offset: entry.offset,
+        size: entry.size,
+        id: entry.asset_id,
+        kind: entry.asset_type,
+        decompressed_size: entry.decompressed_size,
+        children,
+    }
+}
+
+/// Describes the RFRM form at the start of `form_data`, returning the data following it.
+fn describe_form<'a>(data: &[u8], form_data: &'a [u8], depth: usize) -> (LayoutNode, &'a [u8]) {
+    let offset = offset_in(data, form_data);
+    if depth > DEFAULT_MAX_FORM_DEPTH {
+        let error = format!("Forms nested deeper than the maximum depth {DEFAULT_MAX_FORM_DEPTH}");
+        return (LayoutNode::Invalid { offset, error }, &[]);
+    }
+    let (form, mut contents, remain) = match FormDescriptor::slice(form_data, Endian::Little) {
+        Ok(result) => result,
+        Err(err) => return (LayoutNode::Invalid { offset, error: err.to_string() }, &[]),
+    };
+    let mut children = vec![];
+    while !is_padding(contents) {
+        if contents.len() >= 4 && peek_four_cc(contents) == K_CHUNK_RFRM {
+            let (node, remain) = describe_form(data, contents, depth + 1);
+            children.push(node);
+            contents = remain;
+            continue;
+        }
+        let chunk_offset = offset_in(data, contents);
+        match ChunkDescriptor::slice(contents, Endian::Little) {
+            Ok((desc, _, remain)) => {
+                children.push(chunk_node(chunk_offset, &desc));
+                contents = remain;
+            }
+            Err(err) => {
+                children.push(LayoutNode::Invalid { offset: chunk_offset, error: err.to_string() });
+                break;
+            }
+        }
+    }
+    (form_node(offset, &form, children), remain)
+}
+
+/// Offset of `slice` within `data`, which must contain it.
+fn offset_in(data: &[u8], slice: &[u8]) -> u64 {
+    (slice.as_ptr() as usize - data.as_ptr() as usize) as u64
+}
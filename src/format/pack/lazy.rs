@@ -0,0 +1,386 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    path::Path,
+    sync::OnceLock,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use binrw::Endian;
+use uuid::Uuid;
+
+use crate::format::{
+    pack::{
+        block_mode, check_entry_bounds, compress_asset, entry_block, read_asset_data, slice_range,
+        slice_tocc, AssetDirectoryEntry, CompressionChoice, FormFields, ManifestEntry,
+        PackageManifest, ReadOptions, StringTableEntry, TableOfContents, WriteOptions, WritePlan,
+    },
+    rfrm::FormDescriptor,
+    FourCC,
+};
+
+/// Asset information from the package table of contents, without the asset data
+#[derive(Debug, Clone)]
+pub struct AssetHeader<'a> {
+    pub id: Uuid,
+    pub kind: FourCC,
+    pub name: Option<String>,
+    pub meta: Option<Cow<'a, [u8]>>,
+    pub version: u32,
+    pub other_version: u32,
+    /// Offset of the asset data block in the package
+    pub offset: u64,
+    /// Size of the asset data block in the package
+    pub size: u64,
+    pub decompressed_size: u64,
+}
+
+/// Package reader that only parses the table of contents, decompressing asset data on demand
+#[derive(Debug, Clone)]
+pub struct LazyPackage<'a> {
+    data: &'a [u8],
+    e: Endian,
+    options: ReadOptions,
+    toc: TableOfContents<'a>,
+    /// Asset data decompressed by [`LazyPackage::data`], in directory order
+    cache: Vec<OnceLock<Cow<'a, [u8]>>>,
+}
+
+impl<'a> LazyPackage<'a> {
+    pub fn read(data: &'a [u8], e: Endian) -> Result<Self> {
+        Self::read_with_options(data, e, ReadOptions::default())
+    }
+
+    pub fn read_with_options(data: &'a [u8], e: Endian, options: ReadOptions) -> Result<Self> {
+        let (tocc_data, _) = slice_tocc(data, e)?;
+        let toc = TableOfContents::read(tocc_data, e)?;
+        check_entry_bounds(&toc.directory, data.len())?;
+        if options.validate_asset_types {
+            for entry in &toc.directory.entries {
+                entry.validate();
+            }
+        }
+        let cache = toc.directory.entries.iter().map(|_| OnceLock::new()).collect();
+        Ok(Self { data, e, options, toc, cache })
+    }
+
+    /// Number of assets in the package.
+    pub fn len(&self) -> usize { self.toc.directory.entries.len() }
+
+    pub fn is_empty(&self) -> bool { self.toc.directory.entries.is_empty() }
+
+    /// Asset headers, in directory order.
+    pub fn headers(&self) -> impl Iterator<Item = AssetHeader<'a>> + '_ {
+        self.toc.directory.entries.iter().map(|entry| self.header(entry))
+    }
+
+    /// Builds a manifest of the package's assets like [`Package::manifest`], in directory
+    /// order. Only the compression mode header of each data block is read, and nothing is
+    /// decompressed.
+    ///
+    /// [`Package::manifest`]: crate::format::pack::Package::manifest
+    pub fn manifest(&self) -> Result<PackageManifest> {
+        let assets = self
+            .toc
+            .directory
+            .entries
+            .iter()
+            .map(|entry| {
+                let block = entry_block(self.data, entry, self.e)?;
+                let (compression_mode, _) = block_mode(block, entry, &self.options)?;
+                let header = self.header(entry);
+                Ok(ManifestEntry {
+                    id: header.id,
+                    kind: header.kind,
+                    version: header.version,
+                    other_version: header.other_version,
+                    name: header.name,
+                    compression_mode,
+                    decompressed_size: header.decompressed_size,
+                    has_meta: header.meta.is_some(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(PackageManifest { assets })
+    }
+
+    /// Data of the asset at `index`, decompressed on first access and cached.
+    pub fn data(&self, index: usize) -> Result<&[u8]> {
+        let cell = self.cache.get(index).with_context(|| self.index_error(index))?;
+        if let Some(data) = cell.get() {
+            return Ok(data);
+        }
+        let data = self.decompress(index)?;
+        Ok(cell.get_or_init(|| data))
+    }
+
+    /// Reads and decompresses the data of the asset at `index`, without caching it.
+    pub fn decompress(&self, index: usize) -> Result<Cow<'a, [u8]>> {
+        let entry =
+            self.toc.directory.entries.get(index).with_context(|| self.index_error(index))?;
+        Ok(read_asset_data(self.data, entry, self.e, &self.options)?.1)
+    }
+
+    fn index_error(&self, index: usize) -> String {
+        format!("Asset index {} out of range for {} assets", index, self.len())
+    }
+
+    /// Iterates assets in directory order, decompressing each asset's data as it's reached.
+    ///
+    /// An error reading one asset is yielded in its place, and iteration continues with the
+    /// next asset.
+    pub fn iter_decompressed(
+        &self,
+    ) -> impl Iterator<Item = Result<(AssetHeader<'a>, Cow<'a, [u8]>)>> + '_ {
+        self.toc.directory.entries.iter().map(|entry| {
+            let (_, data) = read_asset_data(self.data, entry, self.e, &self.options)?;
+            Ok((self.header(entry), data))
+        })
+    }
+
+    /// Decompresses assets accepted by `filter` across threads, calling `f` with each.
+    ///
+    /// Returns the errors for any assets that failed to decompress.
+    #[cfg(feature = "rayon")]
+    pub fn par_process<P, F>(&self, filter: P, f: F) -> Vec<(Uuid, anyhow::Error)>
+    where
+        P: Fn(&AssetHeader) -> bool + Sync,
+        F: Fn(&AssetHeader, &[u8]) + Sync,
+    {
+        use rayon::prelude::*;
+
+        self.toc
+            .directory
+            .entries
+            .par_iter()
+            .filter_map(|entry| {
+                let header = self.header(entry);
+                if !filter(&header) {
+                    return None;
+                }
+                match read_asset_data(self.data, entry, self.e, &self.options) {
+                    Ok((_, data)) => {
+                        f(&header, &data);
+                        None
+                    }
+                    Err(e) => Some((entry.asset_id, e)),
+                }
+            })
+            .collect()
+    }
+
+    fn header(&self, entry: &AssetDirectoryEntry) -> AssetHeader<'a> {
+        AssetHeader {
+            id: entry.asset_id,
+            kind: entry.asset_type,
+            name: self
+                .options
+                .name_overrides
+                .get(&entry.asset_id)
+                .or_else(|| self.toc.names.get(&entry.asset_id))
+                .cloned(),
+            meta: self.toc.meta.get(&entry.asset_id).cloned(),
+            version: entry.version,
+            other_version: entry.other_version,
+            offset: entry.offset,
+            size: entry.size,
+            decompressed_size: entry.decompressed_size,
+        }
+    }
+}
+
+/// Package editor over a [`LazyPackage`].
+///
+/// Unchanged assets are written by copying their data blocks from the source package as-is,
+/// so only replaced assets are compressed when saving.
+#[derive(Debug, Clone)]
+pub struct EditablePackage<'a> {
+    source: LazyPackage<'a>,
+    replaced: HashMap<Uuid, (Vec<u8>, CompressionChoice)>,
+}
+
+impl<'a> EditablePackage<'a> {
+    pub fn new(source: LazyPackage<'a>) -> Self { Self { source, replaced: HashMap::new() } }
+
+    /// Source package.
+    pub fn source(&self) -> &LazyPackage<'a> { &self.source }
+
+    /// Replaces the data of an existing asset. `data` must be an RFRM form of the same type.
+    pub fn replace(&mut self, id: Uuid, data: Vec<u8>, choice: CompressionChoice) -> Result<()> {
+        let Some(entry) = self.source.toc.directory.entries.iter().find(|e| e.asset_id == id)
+        else {
+            bail!("Asset {} not found", id);
+        };
+        let (form, _, _) = FormDescriptor::slice(&data, Endian::Little)?;
+        ensure!(
+            form.id == entry.asset_type,
+            "Asset {} replacement type {:?} does not match {:?}",
+            id,
+            form.id,
+            entry.asset_type
+        );
+        self.replaced.insert(id, (data, choice));
+        Ok(())
+    }
+
+    /// Discards the replacement for an asset. Returns `false` if it wasn't replaced.
+    pub fn revert(&mut self, id: Uuid) -> bool { self.replaced.remove(&id).is_some() }
+
+    /// Writes the edited package to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut file = BufWriter::new(
+            File::create(path)
+                .with_context(|| format!("Failed to create file '{}'", path.display()))?,
+        );
+        self.write(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Writes the edited package, keeping the source package's asset order and data layout.
+    pub fn write<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
+        let source = &self.source;
+        let entries = &source.toc.directory.entries;
+        let mut plan = WritePlan::new(&WriteOptions::default());
+        plan.meta = entries
+            .iter()
+            .filter_map(|entry| {
+                source.toc.meta.get(&entry.asset_id).map(|m| (entry.asset_id, m.as_ref()))
+            })
+            .collect();
+        plan.data = Vec::with_capacity(entries.len());
+        plan.unknown = &source.toc.unknown;
+        plan.chunk_fields = Some(&source.toc.chunk_fields);
+        plan.forms = FormFields::read(source.data, source.e)?;
+        for entry in entries {
+            let (data, decompressed_size, version, other_version) = match self
+                .replaced
+                .get(&entry.asset_id)
+            {
+                Some((data, choice)) => {
+                    let (form, _, _) = FormDescriptor::slice(data, Endian::Little)?;
+                    let (_, block) = compress_asset(data, *choice)?;
+                    (block, data.len() as u64, form.version_a, form.version_b)
+                }
+                None => {
+                    let block = slice_range(source.data, entry.offset, entry.size)
+                        .with_context(|| format!("Asset {} data out of bounds", entry.asset_id))?;
+                    (
+                        Cow::Borrowed(block),
+                        entry.decompressed_size,
+                        entry.version,
+                        entry.other_version,
+                    )
+                }
+            };
+            plan.directory.entries.push(AssetDirectoryEntry {
+                offset: 0,
+                decompressed_size,
+                size: data.len() as u64,
+                version,
+                other_version,
+                ..entry.clone()
+            });
+            plan.data.push(data);
+            if let Some(name) = source.toc.names.get(&entry.asset_id) {
+                plan.string_table.entries.push(StringTableEntry::new(
+                    entry.asset_type,
+                    entry.asset_id,
+                    source.toc.name_encoding(entry.asset_id).encode(name),
+                ));
+            }
+        }
+        let mut order: Vec<usize> = (0..entries.len()).collect();
+        order.sort_by_key(|&i| entries[i].offset);
+        plan.write(w, source.e, &order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::pack::{
+        tests::{payload, rfrm, sample_package, temp_path, K_TEST_TXTR},
+        Package,
+    };
+
+    #[test]
+    fn manifest_matches_package_manifest() {
+        for mode in 0..=3 {
+            let data = sample_package(mode).to_vec(Endian::Little).unwrap();
+            let lazy = LazyPackage::read(&data, Endian::Little).unwrap();
+            let package = Package::read(&data, Endian::Little).unwrap();
+            assert_eq!(lazy.manifest().unwrap(), package.manifest());
+        }
+    }
+
+    #[test]
+    fn iter_decompressed_yields_each_asset() {
+        let package = sample_package(2);
+        let mut data = package.to_vec(Endian::Little).unwrap();
+        let lazy = LazyPackage::read(&data, Endian::Little).unwrap();
+        let items: Vec<_> = lazy.iter_decompressed().map(Result::unwrap).collect();
+        assert_eq!(items.len(), package.assets.len());
+        for ((header, data), asset) in items.iter().zip(&package.assets) {
+            assert_eq!(header.id, asset.id);
+            assert_eq!(data.as_ref(), asset.data().unwrap());
+        }
+
+        // A block that fails to decompress is yielded as an error, and iteration continues
+        let (offset, size) = lazy.headers().nth(1).map(|h| (h.offset, h.size)).unwrap();
+        data[offset as usize + 4..(offset + size) as usize].fill(0xFF);
+        let lazy = LazyPackage::read(&data, Endian::Little).unwrap();
+        let results: Vec<_> = lazy.iter_decompressed().collect();
+        assert_eq!(results.len(), package.assets.len());
+        for (index, result) in results.iter().enumerate() {
+            assert_eq!(result.is_err(), index == 1, "asset {}", index);
+        }
+    }
+
+    #[test]
+    fn save_copies_unchanged_blocks() {
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let source = LazyPackage::read(&data, Endian::Little).unwrap();
+        let headers: Vec<_> = source.headers().collect();
+        let edited = headers.iter().find(|h| h.kind == K_TEST_TXTR).unwrap().id;
+        let replacement = rfrm(K_TEST_TXTR, &payload(9, 512));
+
+        let mut editor = EditablePackage::new(source);
+        editor.replace(edited, replacement.clone(), CompressionChoice::Mode(3)).unwrap();
+        let path = temp_path("editable.pak");
+        editor.save(&path).unwrap();
+        let saved = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let read = LazyPackage::read(&saved, Endian::Little).unwrap();
+        assert_eq!(read.len(), headers.len());
+        for (index, (header, original)) in read.headers().zip(&headers).enumerate() {
+            assert_eq!(header.id, original.id);
+            assert_eq!(header.name, original.name);
+            assert_eq!(header.meta, original.meta);
+            if header.id == edited {
+                assert_eq!(read.data(index).unwrap(), replacement);
+            } else {
+                let block = slice_range(&saved, header.offset, header.size).unwrap();
+                let source_block = slice_range(&data, original.offset, original.size).unwrap();
+                assert_eq!(block, source_block);
+            }
+        }
+        assert!(editor.revert(edited));
+        assert!(!editor.revert(edited));
+    }
+
+    #[test]
+    fn replace_checks_id_and_type() {
+        let data = sample_package(0).to_vec(Endian::Little).unwrap();
+        let source = LazyPackage::read(&data, Endian::Little).unwrap();
+        let cmdl = source.headers().find(|h| h.kind != K_TEST_TXTR).unwrap().id;
+        let mut editor = EditablePackage::new(source);
+        let txtr = rfrm(K_TEST_TXTR, &payload(1, 32));
+        assert!(editor.replace(cmdl, txtr.clone(), CompressionChoice::Store).is_err());
+        assert!(editor.replace(Uuid::from_u128(1), txtr, CompressionChoice::Store).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Seek, Write},
+};
+
+use anyhow::{ensure, Result};
+use binrw::{binrw, BinReaderExt, BinWriterExt, Endian};
+use uuid::Uuid;
+
+use crate::format::{
+    chunk::ChunkDescriptor, pack::Package, parse_uuid, rfrm::FormDescriptor, write_uuid, FourCC,
+};
+
+// Binary package manifest
+pub const K_FORM_MANI: FourCC = FourCC(*b"MANI");
+// Binary package manifest entries
+pub const K_CHUNK_MENT: FourCC = FourCC(*b"MENT");
+
+/// Listing of the assets a package contains, without asset data
+///
+/// With the `serde` feature, the manifest can be serialized, e.g. with
+/// [`PackageManifest::to_json`]. Assets are listed in ID order, so manifests of two package
+/// versions can be compared with a text diff.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageManifest {
+    pub assets: Vec<ManifestEntry>,
+}
+
+/// Package manifest entry
+///
+/// Binary manifests only store the ID, type and versions. The remaining fields read as their
+/// defaults.
+#[binrw]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManifestEntry {
+    #[br(parse_with = parse_uuid)]
+    #[bw(write_with = write_uuid)]
+    pub id: Uuid,
+    pub kind: FourCC,
+    pub version: u32,
+    pub other_version: u32,
+    #[brw(ignore)]
+    pub name: Option<String>,
+    #[brw(ignore)]
+    pub compression_mode: u32,
+    #[brw(ignore)]
+    pub decompressed_size: u64,
+    #[brw(ignore)]
+    pub has_meta: bool,
+}
+
+/// MANI::MENT chunk
+#[binrw]
+#[derive(Clone, Debug, Default)]
+struct ManifestTable {
+    #[bw(try_calc = entries.len().try_into())]
+    entry_count: u32,
+    #[br(count = entry_count)]
+    entries: Vec<ManifestEntry>,
+}
+
+impl ManifestEntry {
+    /// Whether the type and versions match, which are the fields stored in binary manifests.
+    fn same_type_and_versions(&self, other: &ManifestEntry) -> bool {
+        (self.kind, self.version, self.other_version)
+            == (other.kind, other.version, other.other_version)
+    }
+}
+
+impl PackageManifest {
+    /// Reads a binary manifest written by [`PackageManifest::write_binary`].
+    pub fn read_binary(data: &[u8]) -> Result<Self> {
+        let (form, form_data, _) = FormDescriptor::slice(data, Endian::Little)?;
+        ensure!(form.id == K_FORM_MANI);
+        ensure!(form.version_a == 1);
+        let (desc, chunk_data, _) = ChunkDescriptor::slice(form_data, Endian::Little)?;
+        ensure!(desc.id == K_CHUNK_MENT);
+        let table: ManifestTable = Cursor::new(chunk_data).read_type(Endian::Little)?;
+        Ok(Self { assets: table.entries })
+    }
+
+    /// Writes the manifest as a compact binary MANI form.
+    pub fn write_binary<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
+        let table = ManifestTable { entries: self.assets.clone() };
+        FormDescriptor { size: 0, unk: 0, id: K_FORM_MANI, version_a: 1, version_b: 1 }.write(
+            w,
+            Endian::Little,
+            |w| {
+                ChunkDescriptor { id: K_CHUNK_MENT, size: 0, unk: 0, skip: 0 }.write(
+                    w,
+                    Endian::Little,
+                    |w| {
+                        w.write_le(&table)?;
+                        Ok(())
+                    },
+                )
+            },
+        )
+    }
+
+    /// Serializes the manifest as pretty-printed JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String> { Ok(serde_json::to_string_pretty(self)?) }
+}
+
+/// Difference between a package and a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestDiff {
+    /// Asset in the manifest, but not in the package
+    Missing(ManifestEntry),
+    /// Asset in the package, but not in the manifest
+    Extra(ManifestEntry),
+    /// Asset with a different type or versions than expected
+    Changed { expected: ManifestEntry, found: ManifestEntry },
+}
+
+impl Package<'_> {
+    /// Builds a manifest of the package's assets.
+    pub fn manifest(&self) -> PackageManifest {
+        PackageManifest {
+            assets: self
+                .assets
+                .iter()
+                .map(|asset| ManifestEntry {
+                    id: asset.id,
+                    kind: asset.kind,
+                    version: asset.version,
+                    other_version: asset.other_version,
+                    name: asset.name.clone(),
+                    compression_mode: asset.info.compression_mode,
+                    decompressed_size: asset.decompressed_size(),
+                    has_meta: asset.meta.is_some(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes a binary manifest of the package's assets. See [`PackageManifest::write_binary`].
+    pub fn write_binary_manifest<W: Write + Seek>(&self, w: &mut W) -> Result<()> {
+        self.manifest().write_binary(w)
+    }
+
+    /// Reads a binary manifest written by [`Package::write_binary_manifest`].
+    pub fn read_binary_manifest(data: &[u8]) -> Result<PackageManifest> {
+        PackageManifest::read_binary(data)
+    }
+
+    /// Checks that the package contains exactly the assets in `manifest`, with the same types
+    /// and versions. Returns an empty list if the package matches.
+    pub fn validate_against(&self, manifest: &PackageManifest) -> Vec<ManifestDiff> {
+        let found = self.manifest();
+        let found_map: HashMap<Uuid, &ManifestEntry> =
+            found.assets.iter().map(|entry| (entry.id, entry)).collect();
+        let expected_map: HashMap<Uuid, &ManifestEntry> =
+            manifest.assets.iter().map(|entry| (entry.id, entry)).collect();
+        let mut diffs = vec![];
+        for expected in &manifest.assets {
+            match found_map.get(&expected.id) {
+                Some(&found) if !found.same_type_and_versions(expected) => {
+                    diffs.push(ManifestDiff::Changed {
+                        expected: expected.clone(),
+                        found: found.clone(),
+                    })
+                }
+                Some(_) => {}
+                None => diffs.push(ManifestDiff::Missing(expected.clone())),
+            }
+        }
+        for entry in &found.assets {
+            if !expected_map.contains_key(&entry.id) {
+                diffs.push(ManifestDiff::Extra(entry.clone()));
+            }
+        }
+        diffs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::pack::{
+        tests::{rfrm, rfrm_with_version, sample_package, K_TEST_CMDL, K_TEST_TXTR},
+        Asset, PackageBuilder,
+    };
+
+    #[test]
+    fn binary_manifest_round_trip() {
+        let mut builder = PackageBuilder::new();
+        for i in 0..1000u32 {
+            let kind = if i % 3 == 0 { K_TEST_CMDL } else { K_TEST_TXTR };
+            builder
+                .add_asset(kind, rfrm_with_version(kind, (i % 5, i % 7), &i.to_le_bytes()))
+                .with_name(format!("asset{}", i));
+        }
+        let package = builder.build();
+        let mut cursor = Cursor::new(Vec::new());
+        package.write_binary_manifest(&mut cursor).unwrap();
+        let data = cursor.into_inner();
+        let read = Package::read_binary_manifest(&data).unwrap();
+
+        let manifest = package.manifest();
+        assert_eq!(read.assets.len(), 1000);
+        for (read, entry) in read.assets.iter().zip(&manifest.assets) {
+            assert_eq!(
+                (read.id, read.kind, read.version, read.other_version),
+                (entry.id, entry.kind, entry.version, entry.other_version)
+            );
+            assert!(read.same_type_and_versions(entry));
+            assert_eq!((&read.name, read.decompressed_size, read.has_meta), (&None, 0, false));
+        }
+        assert!(package.validate_against(&read).is_empty());
+        #[cfg(feature = "serde")]
+        assert!(data.len() * 8 < manifest.to_json().unwrap().len());
+
+        let mut corrupt = data.clone();
+        corrupt[20] = b'X';
+        assert!(Package::read_binary_manifest(&corrupt).is_err());
+    }
+
+    #[test]
+    fn validate_against_reports_differences() {
+        let package = sample_package(2);
+        let manifest = package.manifest();
+        assert!(package.validate_against(&manifest).is_empty());
+
+        let mut modified = package.clone();
+        let extra = Asset::new(Uuid::from_u128(1), K_TEST_TXTR, rfrm(K_TEST_TXTR, &[]));
+        modified.insert_asset(extra).unwrap();
+        let diffs = modified.validate_against(&manifest);
+        assert!(matches!(diffs.as_slice(), [ManifestDiff::Extra(e)] if e.id == Uuid::from_u128(1)));
+
+        let removed = modified.assets.pop().unwrap().id;
+        modified.assets[1].version = 5;
+        let diffs = modified.validate_against(&manifest);
+        assert_eq!(diffs.len(), 3);
+        assert!(diffs.iter().any(|d| matches!(d,
+            ManifestDiff::Changed { expected, found } if expected.version == 1 && found.version == 5)));
+        assert!(diffs.iter().any(|d| matches!(d, ManifestDiff::Missing(e) if e.id == removed)));
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use anyhow::Result;
+use binrw::Endian;
+use memmap2::Mmap;
+
+use crate::{
+    format::pack::{LazyPackage, Package, ReadOptions},
+    util::file::map_file,
+};
+
+/// Memory-mapped package file
+///
+/// Packages read from the map borrow uncompressed asset data from it instead of copying the
+/// file, so the map must outlive them; the borrow checker ensures it does. To keep a package
+/// after dropping the map, detach it with [`Package::into_owned`].
+#[derive(Debug)]
+pub struct MappedPackage {
+    map: Mmap,
+    e: Endian,
+}
+
+impl MappedPackage {
+    /// Maps a package file, checking that its table of contents can be read.
+    pub fn open<P: AsRef<Path>>(path: P, e: Endian) -> Result<Self> {
+        let map = map_file(path)?;
+        LazyPackage::read(&map, e)?;
+        Ok(Self { map, e })
+    }
+
+    /// Mapped file data.
+    pub fn data(&self) -> &[u8] { &self.map }
+
+    /// Reads the package from the map. See [`Package::read`].
+    pub fn package(&self) -> Result<Package<'_>> { Package::read(&self.map, self.e) }
+
+    pub fn package_with_options(&self, options: &ReadOptions) -> Result<Package<'_>> {
+        Package::read_with_options(&self.map, self.e, options)
+    }
+
+    /// Reads the package's table of contents from the map, decompressing assets on demand.
+    pub fn lazy(&self) -> Result<LazyPackage<'_>> { LazyPackage::read(&self.map, self.e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::format::pack::tests::{sample_package, temp_path};
+
+    #[test]
+    fn mapped_package_borrows_file_data() {
+        let package = sample_package(0);
+        let path = temp_path("mapped.pak");
+        fs::write(&path, package.to_vec(Endian::Little).unwrap()).unwrap();
+        let mapped = MappedPackage::open(&path, Endian::Little).unwrap();
+
+        let read = mapped.package().unwrap();
+        let range = mapped.data().as_ptr_range();
+        for (asset, original) in read.assets.iter().zip(&package.assets) {
+            let data = asset.data().unwrap();
+            assert_eq!(data, original.data().unwrap());
+            assert!(range.contains(&data.as_ptr()));
+        }
+        assert_eq!(mapped.lazy().unwrap().len(), package.assets.len());
+        let owned = mapped.package().unwrap().into_owned();
+        drop(mapped);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(owned.assets.len(), package.assets.len());
+
+        let path = temp_path("mapped-invalid.pak");
+        fs::write(&path, [0u8; 64]).unwrap();
+        assert!(MappedPackage::open(&path, Endian::Little).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
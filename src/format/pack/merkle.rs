@@ -0,0 +1,149 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::format::pack::{Asset, Package};
+
+/// Sibling hash along a [`MerkleProof`] path
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MerkleSibling {
+    Left([u8; 32]),
+    Right([u8; 32]),
+}
+
+/// Proof that an asset belongs to a package's [`Package::merkle_root`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MerkleProof {
+    /// Hash of the asset
+    pub leaf: [u8; 32],
+    /// Sibling hashes from the leaf up to the root
+    pub siblings: Vec<MerkleSibling>,
+}
+
+impl MerkleProof {
+    /// Checks that the proof leads to `root`.
+    pub fn verify(&self, root: &[u8; 32]) -> bool {
+        let hash = self.siblings.iter().fold(self.leaf, |hash, sibling| match sibling {
+            MerkleSibling::Left(left) => node_hash(left, &hash),
+            MerkleSibling::Right(right) => node_hash(&hash, right),
+        });
+        &hash == root
+    }
+}
+
+impl Package<'_> {
+    /// Root of a Merkle tree of asset hashes, ordered by asset ID.
+    ///
+    /// Each asset hash covers the asset's ID, type, versions and data. Packages with identical
+    /// assets have identical roots, regardless of asset order or compression.
+    pub fn merkle_root(&self) -> Result<[u8; 32]> {
+        let mut level = self.merkle_leaves()?.into_iter().map(|(_, hash)| hash).collect::<Vec<_>>();
+        if level.is_empty() {
+            return Ok([0u8; 32]);
+        }
+        while level.len() > 1 {
+            level = parent_level(&level);
+        }
+        Ok(level[0])
+    }
+
+    /// Builds a proof that the asset with the given ID belongs to [`Package::merkle_root`].
+    /// Returns `None` if the package has no asset with the given ID.
+    pub fn merkle_proof(&self, id: Uuid) -> Result<Option<MerkleProof>> {
+        let leaves = self.merkle_leaves()?;
+        let Some(mut index) = leaves.iter().position(|&(leaf_id, _)| leaf_id == id) else {
+            return Ok(None);
+        };
+        let mut level: Vec<[u8; 32]> = leaves.into_iter().map(|(_, hash)| hash).collect();
+        let leaf = level[index];
+        let mut siblings = vec![];
+        while level.len() > 1 {
+            if index % 2 == 1 {
+                siblings.push(MerkleSibling::Left(level[index - 1]));
+            } else if index + 1 < level.len() {
+                siblings.push(MerkleSibling::Right(level[index + 1]));
+            }
+            level = parent_level(&level);
+            index /= 2;
+        }
+        Ok(Some(MerkleProof { leaf, siblings }))
+    }
+
+    fn merkle_leaves(&self) -> Result<Vec<(Uuid, [u8; 32])>> {
+        let mut leaves = self
+            .assets
+            .iter()
+            .map(|asset| Ok((asset.id, leaf_hash(asset)?)))
+            .collect::<Result<Vec<_>>>()?;
+        leaves.sort_by_key(|&(id, _)| id);
+        Ok(leaves)
+    }
+}
+
+fn leaf_hash(asset: &Asset) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(asset.id.as_bytes());
+    hasher.update(asset.kind.0);
+    hasher.update(asset.version.to_le_bytes());
+    hasher.update(asset.other_version.to_le_bytes());
+    hasher.update(asset.data()?);
+    Ok(hasher.finalize().into())
+}
+
+/// Hashes pairs of nodes, carrying an odd node up unchanged.
+fn parent_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| if let [left, right] = pair { node_hash(left, right) } else { pair[0] })
+        .collect()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use binrw::Endian;
+
+    use super::*;
+    use crate::format::pack::tests::{payload, rfrm, sample_package, K_TEST_TXTR};
+
+    #[test]
+    fn identical_assets_have_identical_roots() {
+        let stored = sample_package(0);
+        let root = stored.merkle_root().unwrap();
+        let mut reversed = sample_package(0);
+        reversed.assets.reverse();
+        assert_eq!(reversed.merkle_root().unwrap(), root);
+        let data = sample_package(2).to_vec(Endian::Little).unwrap();
+        let read = Package::read(&data, Endian::Little).unwrap();
+        assert_eq!(read.merkle_root().unwrap(), root);
+
+        let mut changed = sample_package(0);
+        changed.assets[1].set_data(rfrm(K_TEST_TXTR, &payload(9, 256)));
+        assert_ne!(changed.merkle_root().unwrap(), root);
+        assert_eq!(Package::default().merkle_root().unwrap(), [0u8; 32]);
+    }
+
+    #[test]
+    fn proofs_verify_against_root() {
+        let mut package = sample_package(0);
+        // Odd asset count, so one node is carried up unchanged
+        package.assets.pop();
+        let root = package.merkle_root().unwrap();
+        for asset in &package.assets {
+            let proof = package.merkle_proof(asset.id).unwrap().unwrap();
+            assert!(proof.verify(&root));
+            let mut tampered = proof.clone();
+            tampered.leaf[0] ^= 1;
+            assert!(!tampered.verify(&root));
+        }
+        assert!(package.merkle_proof(Uuid::from_u128(1)).unwrap().is_none());
+    }
+}
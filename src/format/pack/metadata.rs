@@ -0,0 +1,169 @@
+use std::{collections::HashMap, io::Cursor};
+
+use anyhow::Result;
+use binrw::{BinReaderExt, BinWriterExt, Endian};
+use uuid::Uuid;
+
+use crate::format::{
+    pack::{deps::find_references, Asset, Package},
+    txtr::{STextureMetaData, K_FORM_TXTR},
+    FourCC,
+};
+
+/// Decoded PACK::TOCC::META entry.
+///
+/// The layout of the metadata depends on the asset type. Unrecognized types, and metadata that
+/// doesn't re-encode to the original bytes, are kept as raw bytes. References to other assets
+/// are found with [`Package::metadata_references`].
+#[derive(Clone, Debug)]
+pub enum Metadata {
+    Texture(STextureMetaData),
+    Raw(Vec<u8>),
+}
+
+impl Metadata {
+    /// Decodes the metadata of an asset of type `kind`.
+    pub fn parse(kind: FourCC, data: &[u8], e: Endian) -> Result<Self> {
+        let decoded = match kind {
+            K_FORM_TXTR => Cursor::new(data).read_type(e).ok().map(Metadata::Texture),
+            _ => None,
+        };
+        match decoded {
+            Some(metadata) if metadata.to_bytes(e)? == data => Ok(metadata),
+            _ => Ok(Metadata::Raw(data.to_vec())),
+        }
+    }
+
+    /// Encodes the metadata. Parsed metadata reproduces the original bytes.
+    pub fn to_bytes(&self, e: Endian) -> Result<Vec<u8>> {
+        match self {
+            Metadata::Texture(meta) => {
+                let mut cursor = Cursor::new(Vec::new());
+                cursor.write_type(meta, e)?;
+                Ok(cursor.into_inner())
+            }
+            Metadata::Raw(data) => Ok(data.clone()),
+        }
+    }
+}
+
+/// Reference to another asset in a PACK::TOCC::META entry
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MetadataReference {
+    /// Offset of the referenced ID within the metadata
+    pub offset: usize,
+    pub asset_id: Uuid,
+    /// Type of the referenced asset
+    pub kind: FourCC,
+}
+
+impl Asset<'_> {
+    /// Decodes the asset's metadata, if present. See [`Metadata::parse`].
+    pub fn metadata(&self, e: Endian) -> Result<Option<Metadata>> {
+        self.meta_bytes().map(|data| Metadata::parse(self.kind, data, e)).transpose()
+    }
+}
+
+impl Package<'_> {
+    /// References to other assets in the package from the metadata of asset `id`, in the
+    /// order they appear. Returns an empty list for unknown assets and assets without metadata.
+    ///
+    /// No common layout of dependency lists is known, so as with
+    /// [`Package::dependency_graph`], any ID of another asset in the package counts.
+    pub fn metadata_references(&self, id: Uuid) -> Vec<MetadataReference> {
+        let Some(meta) = self.asset_by_id(id).and_then(|asset| asset.meta_bytes()) else {
+            return vec![];
+        };
+        let kinds: HashMap<Uuid, FourCC> = self.assets.iter().map(|a| (a.id, a.kind)).collect();
+        find_references(meta, self.endian(), |other| other != id && kinds.contains_key(&other))
+            .into_iter()
+            .map(|(offset, asset_id)| MetadataReference {
+                offset,
+                asset_id,
+                kind: kinds[&asset_id],
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{
+        pack::{
+            tests::{payload, rfrm, K_TEST_CMDL, K_TEST_TXTR},
+            PackageBuilder,
+        },
+        txtr::{STextureCompressedBufferInfo, STextureReadInfo},
+    };
+
+    fn texture_meta(e: Endian) -> Vec<u8> {
+        let meta = STextureMetaData {
+            unk1: 1,
+            unk2: 2,
+            alloc_category: 3,
+            gpu_offset: 0x40,
+            align: 0x200,
+            decompressed_size: 0x1000,
+            info: vec![STextureReadInfo { index: 0, offset: 0, size: 0x800 }],
+            buffers: vec![STextureCompressedBufferInfo {
+                index: 0,
+                offset: 0,
+                size: 0x800,
+                dest_offset: 0,
+                dest_size: 0x1000,
+            }],
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        cursor.write_type(&meta, e).unwrap();
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn parse_round_trips_bytes() {
+        for e in [Endian::Little, Endian::Big] {
+            let data = texture_meta(e);
+            let metadata = Metadata::parse(K_FORM_TXTR, &data, e).unwrap();
+            assert!(
+                matches!(&metadata, Metadata::Texture(meta) if meta.decompressed_size == 0x1000)
+            );
+            assert_eq!(metadata.to_bytes(e).unwrap(), data);
+
+            // Trailing bytes, unknown types and truncated data are kept raw
+            let mut extended = data.clone();
+            extended.push(0xFF);
+            for (kind, data) in [
+                (K_FORM_TXTR, extended.as_slice()),
+                (K_TEST_CMDL, data.as_slice()),
+                (K_FORM_TXTR, &data[..data.len() - 1]),
+            ] {
+                let metadata = Metadata::parse(kind, data, e).unwrap();
+                assert!(matches!(metadata, Metadata::Raw(_)));
+                assert_eq!(metadata.to_bytes(e).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn references_to_package_assets() {
+        let mut builder = PackageBuilder::new();
+        let txtr = builder.add_asset(K_TEST_TXTR, rfrm(K_TEST_TXTR, &payload(0, 64))).id();
+        let other = builder.add_asset(K_TEST_CMDL, rfrm(K_TEST_CMDL, &payload(1, 64))).id();
+        let model = builder.add_asset(K_TEST_CMDL, rfrm(K_TEST_CMDL, &payload(2, 64)));
+        let cmdl = model.id();
+        // A self reference and an ID not in the package are ignored
+        let mut meta = vec![0xFF; 4];
+        for id in [other, cmdl, Uuid::from_u128(0x1234), txtr] {
+            meta.extend_from_slice(&id.to_bytes_le());
+        }
+        model.with_meta(meta);
+        let package = builder.build();
+
+        assert_eq!(package.metadata_references(cmdl), [
+            MetadataReference { offset: 4, asset_id: other, kind: K_TEST_CMDL },
+            MetadataReference { offset: 52, asset_id: txtr, kind: K_TEST_TXTR },
+        ]);
+        assert!(package.metadata_references(txtr).is_empty());
+        assert!(package.metadata_references(Uuid::nil()).is_empty());
+    }
+}
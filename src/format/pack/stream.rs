@@ -0,0 +1,141 @@
+use std::{
+    borrow::Cow,
+    io::{Cursor, Read, Seek, SeekFrom},
+    sync::Arc,
+};
+
+use anyhow::{ensure, Context, Result};
+use binrw::Endian;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::format::{
+    pack::{
+        unwrap_asset_block, Asset, AssetDirectoryEntry, Package, ReadOptions, TableOfContents,
+        FORM_DESCRIPTOR_SIZE, K_FORM_PACK, K_FORM_TOCC,
+    },
+    rfrm::FormDescriptor,
+};
+
+impl Package<'static> {
+    /// Reads a package from a seekable reader, without buffering the whole package.
+    ///
+    /// The TOCC is read first, then each asset's data block is read from its offset. The reader
+    /// must be positioned at the start of the package.
+    ///
+    /// Only the input is streamed: the returned package owns the data block of every asset,
+    /// along with the decompressed data once accessed, so all of it is held in memory at once.
+    pub fn read_from<R: Read + Seek>(reader: &mut R, e: Endian) -> Result<Self> {
+        Self::read_from_with_options(reader, e, &ReadOptions::default())
+    }
+
+    pub fn read_from_with_options<R: Read + Seek>(
+        reader: &mut R,
+        e: Endian,
+        options: &ReadOptions,
+    ) -> Result<Self> {
+        let start = reader.stream_position()?;
+        let toc_data = read_toc_data(reader, e)?;
+        let digest = Sha256::digest(&toc_data).into();
+        let toc = TableOfContents::read(&toc_data[FORM_DESCRIPTOR_SIZE * 2..], e)?.into_owned();
+        let package = Self::read_assets(toc, digest, Cow::Owned(toc_data), e, options, |entry| {
+            read_block(reader, start, entry, e).map(Cow::Owned)
+        })?;
+        if options.eager {
+            package.decompress_all()?;
+        }
+        Ok(package)
+    }
+
+    /// Reads a single asset from a seekable reader, reading only the TOCC and the asset's own
+    /// data block. Returns `None` if the package has no asset with the given ID.
+    ///
+    /// The reader must be positioned at the start of the package.
+    pub fn extract_one<R: Read + Seek>(
+        reader: &mut R,
+        e: Endian,
+        id: Uuid,
+    ) -> Result<Option<Asset<'static>>> {
+        let start = reader.stream_position()?;
+        let toc_data = read_toc_data(reader, e)?;
+        let toc = TableOfContents::read(&toc_data[FORM_DESCRIPTOR_SIZE * 2..], e)?.into_owned();
+        let Some(entry) = toc.directory.entries.iter().find(|entry| entry.asset_id == id) else {
+            return Ok(None);
+        };
+        let block = read_block(reader, start, entry, e)?;
+        let asset = toc.asset(entry, Cow::Owned(block), &Arc::new(ReadOptions::default()))?;
+        asset.data()?;
+        Ok(Some(asset))
+    }
+}
+
+/// Reads the PACK and TOCC form headers and the TOCC contents.
+fn read_toc_data<R: Read + Seek>(reader: &mut R, e: Endian) -> Result<Vec<u8>> {
+    let mut toc_data = vec![0u8; FORM_DESCRIPTOR_SIZE * 2];
+    reader.read_exact(&mut toc_data).context("Failed to read package header")?;
+    let mut header = Cursor::new(&toc_data);
+    let pack = FormDescriptor::read(&mut header, e)?;
+    ensure!(pack.id == K_FORM_PACK);
+    ensure!(pack.version_a == 1);
+    log::debug!("PACK: {:?}", pack);
+    let tocc = FormDescriptor::read(&mut header, e)?;
+    ensure!(tocc.id == K_FORM_TOCC);
+    ensure!(tocc.version_a == 3);
+    log::debug!("TOCC: {:?}", tocc);
+    reader.by_ref().take(tocc.size).read_to_end(&mut toc_data).context("Failed to read TOCC")?;
+    ensure!(
+        toc_data.len() as u64 == FORM_DESCRIPTOR_SIZE as u64 * 2 + tocc.size,
+        "TOCC out of bounds"
+    );
+    Ok(toc_data)
+}
+
+/// Reads an asset's data block without any chunk header wrapping it, for a package starting
+/// at `start`.
+fn read_block<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    entry: &AssetDirectoryEntry,
+    e: Endian,
+) -> Result<Vec<u8>> {
+    let offset = start
+        .checked_add(entry.offset)
+        .with_context(|| format!("Asset {} data out of bounds", entry.asset_id))?;
+    reader.seek(SeekFrom::Start(offset))?;
+    // Read through `take`, so a corrupt size can't allocate more than the reader holds
+    let mut block = Vec::new();
+    reader.by_ref().take(entry.size).read_to_end(&mut block)?;
+    ensure!(block.len() as u64 == entry.size, "Asset {} data out of bounds", entry.asset_id);
+    let header_len = block.len() - unwrap_asset_block(&block, e)?.len();
+    block.drain(..header_len);
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::pack::tests::sample_package;
+
+    #[test]
+    fn extract_one_matches_read() {
+        for e in [Endian::Little, Endian::Big] {
+            let data = sample_package(2).to_vec(e).unwrap();
+            let package = Package::read(&data, e).unwrap();
+            // The package doesn't have to start at the beginning of the reader
+            let mut prefixed = vec![0xFF; 7];
+            prefixed.extend_from_slice(&data);
+            let mut reader = Cursor::new(prefixed);
+            for expected in &package.assets {
+                reader.set_position(7);
+                let asset = Package::extract_one(&mut reader, e, expected.id).unwrap().unwrap();
+                assert_eq!((asset.id, asset.kind), (expected.id, expected.kind));
+                assert_eq!(asset.name, expected.name);
+                assert_eq!(asset.meta, expected.meta);
+                assert_eq!(asset.info.compression_mode, expected.info.compression_mode);
+                assert_eq!(asset.data().unwrap(), expected.data().unwrap());
+            }
+            reader.set_position(7);
+            assert!(Package::extract_one(&mut reader, e, Uuid::nil()).unwrap().is_none());
+        }
+    }
+}
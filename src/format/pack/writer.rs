@@ -0,0 +1,240 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+use anyhow::{ensure, Result};
+use binrw::{BinWriterExt, Endian};
+use uuid::Uuid;
+
+use crate::format::{
+    pack::{
+        align, asset_block, write_toc_chunks, Asset, AssetDirectory, AssetDirectoryEntry,
+        FormFields, NameEncoding, Package, StringTable, StringTableEntry, WriteOptions, WritePlan,
+        FORM_DESCRIPTOR_SIZE,
+    },
+    FourCC,
+};
+
+// Size of the buffer used to move the data region when finishing a package
+const SHIFT_BUFFER_SIZE: usize = 1 << 20;
+
+/// Writes a package one asset at a time, holding only the table of contents in memory.
+///
+/// Asset data is written as each asset is added. [`PackageWriter::finish`] then moves the data
+/// region forward to make room for the TOCC, so the writer must also support reading back
+/// what was written (e.g. a [`File`](std::fs::File) or a [`Cursor`](std::io::Cursor)).
+///
+/// Assets must be added in ascending ID order. The result is identical to
+/// [`Package::write_with_options`] with [`AssetOrdering::Directory`](super::AssetOrdering)
+/// for the same assets, using the compression and final alignment settings of the
+/// [`WriteOptions`]. For packages read from package data, start with
+/// [`PackageWriter::begin_from`] to also keep their form header fields, TOCC chunk fields,
+/// unknown chunks and name encodings. Asset alignment and deduplication are not supported.
+pub struct PackageWriter<W: Read + Write + Seek> {
+    w: W,
+    e: Endian,
+    options: WriteOptions,
+    /// Position of the start of the package
+    start: u64,
+    directory: AssetDirectory,
+    meta: Vec<(Uuid, Vec<u8>)>,
+    string_table: StringTable,
+    /// Size of the asset data written so far
+    data_size: u64,
+    /// PACK and TOCC form header fields
+    forms: FormFields,
+    /// `unk` and `skip` of each chunk, if read from package data
+    chunk_fields: Option<HashMap<FourCC, (u32, u64)>>,
+    /// Unrecognized TOCC chunks, written after STRG
+    unknown: Vec<(FourCC, Vec<u8>)>,
+    /// Encoding of each asset name, if not UTF-8
+    name_encodings: HashMap<Uuid, NameEncoding>,
+}
+
+impl<W: Read + Write + Seek> PackageWriter<W> {
+    /// Starts writing a package at the current position of `w`.
+    pub fn begin(mut w: W, e: Endian, options: WriteOptions) -> Result<Self> {
+        ensure!(
+            options.asset_alignment <= 1 && !options.deduplicate,
+            "Asset alignment and deduplication are not supported when streaming"
+        );
+        let start = w.stream_position()?;
+        Ok(Self {
+            w,
+            e,
+            options,
+            start,
+            directory: AssetDirectory::default(),
+            meta: vec![],
+            string_table: StringTable::default(),
+            data_size: 0,
+            forms: FormFields::default(),
+            chunk_fields: None,
+            unknown: vec![],
+            name_encodings: HashMap::new(),
+        })
+    }
+
+    /// Starts writing a package like [`PackageWriter::begin`], keeping the form header fields,
+    /// TOCC chunk fields, unknown chunks and name encodings of `package`, as
+    /// [`Package::write_with_options`] does.
+    pub fn begin_from(w: W, e: Endian, options: WriteOptions, package: &Package) -> Result<Self> {
+        let mut writer = Self::begin(w, e, options)?;
+        writer.unknown = package.unknown_chunks.clone();
+        if let Some(source) = &package.source {
+            writer.forms = source.forms;
+            writer.chunk_fields = Some(source.toc.chunk_fields.clone());
+            writer.name_encodings = source.toc.name_encodings.clone();
+        }
+        Ok(writer)
+    }
+
+    /// Compresses and writes an asset's data, recording its table of contents entries.
+    pub fn add_asset(&mut self, asset: &Asset) -> Result<()> {
+        if let Some(last) = self.directory.entries.last() {
+            ensure!(asset.id >= last.asset_id, "Assets must be ordered by ID ascending");
+        }
+        let (_, block) = asset_block(asset, self.options.choice(asset))?;
+        self.w.write_all(&block)?;
+        self.directory.entries.push(AssetDirectoryEntry {
+            asset_type: asset.kind,
+            asset_id: asset.id,
+            version: asset.version,
+            other_version: asset.other_version,
+            // Relative to the data region until finish
+            offset: self.data_size,
+            decompressed_size: asset.decompressed_size(),
+            size: block.len() as u64,
+        });
+        self.data_size += block.len() as u64;
+        if let Some(meta) = &asset.meta {
+            self.meta.push((asset.id, meta.to_vec()));
+        }
+        if let Some(name) = &asset.name {
+            let encoding = self.name_encodings.get(&asset.id).copied().unwrap_or_default();
+            self.string_table.entries.push(StringTableEntry::new(
+                asset.kind,
+                asset.id,
+                encoding.encode(name),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes the PACK header and TOCC before the asset data, returning the writer positioned
+    /// at the end of the package.
+    pub fn finish(mut self) -> Result<W> {
+        let e = self.e;
+        let mut plan = WritePlan::new(&self.options);
+        plan.directory = self.directory;
+        plan.string_table = self.string_table;
+        plan.meta = self.meta.iter().map(|(id, data)| (*id, data.as_slice())).collect();
+        plan.unknown = &self.unknown;
+        plan.chunk_fields = self.chunk_fields.as_ref();
+        plan.forms = self.forms;
+        let toc_size = plan.toc_size();
+        shift_forward(&mut self.w, self.start, self.data_size, toc_size)?;
+        for entry in &mut plan.directory.entries {
+            entry.offset += self.start + toc_size;
+        }
+
+        let w = &mut self.w;
+        w.seek(SeekFrom::Start(self.start))?;
+        let pack = plan.forms.pack_form(toc_size + self.data_size - FORM_DESCRIPTOR_SIZE as u64);
+        w.write_type(&pack, e)?;
+        plan.forms.tocc_form().write(w, e, |w| {
+            let (meta, string_table) = plan.tables();
+            write_toc_chunks(
+                w,
+                e,
+                &plan.directory,
+                meta,
+                string_table,
+                plan.unknown,
+                plan.chunk_fields,
+            )?;
+            Ok(())
+        })?;
+        ensure!(w.stream_position()? == self.start + toc_size, "TOCC size mismatch");
+
+        let end = self.start + toc_size + self.data_size;
+        w.seek(SeekFrom::Start(end))?;
+        w.write_all(&vec![plan.pad_byte; (align(end, plan.final_alignment) - end) as usize])?;
+        Ok(self.w)
+    }
+}
+
+/// Moves `len` bytes at `start` forward by `by` bytes, copying from the end so the source
+/// isn't overwritten before it's read.
+fn shift_forward<W: Read + Write + Seek>(w: &mut W, start: u64, len: u64, by: u64) -> Result<()> {
+    let mut buf = vec![0u8; SHIFT_BUFFER_SIZE.min(len as usize)];
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(buf.len() as u64);
+        remaining -= n;
+        let buf = &mut buf[..n as usize];
+        w.seek(SeekFrom::Start(start + remaining))?;
+        w.read_exact(buf)?;
+        w.seek(SeekFrom::Start(start + remaining + by))?;
+        w.write_all(buf)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::format::pack::tests::{package_with_form_fields, sample_package};
+
+    /// Streams the package's assets through a [`PackageWriter`].
+    fn stream(package: &Package, e: Endian, options: &WriteOptions) -> Vec<u8> {
+        let w = Cursor::new(Vec::new());
+        let mut writer = PackageWriter::begin_from(w, e, options.clone(), package).unwrap();
+        for asset in &package.assets {
+            writer.add_asset(asset).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn streamed_matches_write_with_options() {
+        let options = WriteOptions { final_alignment: 64, pad_byte: 0xFF, ..Default::default() };
+        for e in [Endian::Little, Endian::Big] {
+            for mode in [0, 2] {
+                let package = sample_package(mode);
+                let mut writer =
+                    PackageWriter::begin(Cursor::new(Vec::new()), e, options.clone()).unwrap();
+                for asset in &package.assets {
+                    writer.add_asset(asset).unwrap();
+                }
+                let written = writer.finish().unwrap().into_inner();
+                assert_eq!(written, package.to_vec_with_options(e, &options).unwrap());
+                assert_eq!(stream(&package, e, &options), written);
+            }
+        }
+    }
+
+    #[test]
+    fn streamed_read_package_keeps_source_fields() {
+        let fields = FormFields { pack: (0x1234, 2), tocc: (0x5678, 3) };
+        for e in [Endian::Little, Endian::Big] {
+            let data = package_with_form_fields(e, fields);
+            let mut package = Package::read(&data, e).unwrap();
+            package.unknown_chunks.push((FourCC(*b"UNKN"), vec![1, 2, 3, 4]));
+            package.assets[0].name = Some("名前".to_string());
+            let id = package.assets[0].id;
+            package.source.as_mut().unwrap().toc.name_encodings.insert(id, NameEncoding::Utf16Le);
+
+            let written = stream(&package, e, &WriteOptions::default());
+            assert_eq!(written, package.to_vec(e).unwrap());
+            assert_eq!(FormFields::read(&written, e).unwrap(), fields);
+            let read = Package::read(&written, e).unwrap();
+            assert_eq!(read.unknown_chunks, package.unknown_chunks);
+            assert_eq!(read.name_encoding(id), NameEncoding::Utf16Le);
+            assert_eq!(read.assets[0].name.as_deref(), Some("名前"));
+        }
+    }
+}
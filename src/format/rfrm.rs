@@ -1,9 +1,24 @@
-use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "std")]
+use std::io::Write;
 
+#[cfg(feature = "std")]
 use anyhow::Result;
-use binrw::{binrw, BinReaderExt, BinResult, BinWriterExt, Endian};
+#[cfg(feature = "std")]
+use binrw::io::SeekFrom;
+#[cfg(feature = "std")]
+use binrw::BinWriterExt;
+use binrw::{
+    binrw,
+    io::{Cursor, Read, Seek},
+    BinReaderExt, BinResult, Endian,
+};
 
-use crate::format::{chunk::ChunkDescriptor, peek_four_cc, FourCC};
+use crate::format::{chunk::check_bounds, FourCC};
+#[cfg(feature = "std")]
+use crate::format::{
+    chunk::{is_padding, ChunkDescriptor},
+    peek_four_cc,
+};
 
 // Resource format
 pub const K_CHUNK_RFRM: FourCC = FourCC(*b"RFRM");
@@ -25,16 +40,23 @@ impl FormDescriptor {
         reader.read_type(e)
     }
 
+    /// Slices the form contents, returning the header, contents and remaining data.
+    ///
+    /// Form contents directly follow the header and are `size` bytes long. Padding between
+    /// chunks inside a form is declared by each chunk's `skip`; zero padding may also follow
+    /// the last chunk (see [`is_padding`]).
     #[inline]
     pub fn slice(data: &[u8], e: Endian) -> BinResult<(Self, &[u8], &[u8])> {
         let mut reader = Cursor::new(data);
         let header = Self::read(&mut reader, e)?;
         let start = reader.position();
-        let slice = &data[start as usize..(start + header.size) as usize];
-        let remain = &data[(start + header.size) as usize..];
+        let end = check_bounds(data, start, header.size, header.id)?;
+        let slice = &data[start as usize..end];
+        let remain = &data[end..];
         Ok((header, slice, remain))
     }
 
+    #[cfg(feature = "std")]
     pub fn write<W: Write + Seek, CB>(&mut self, w: &mut W, e: Endian, mut cb: CB) -> Result<()>
     where CB: FnMut(&mut W) -> Result<()> {
         let form_pos = w.stream_position()?;
@@ -51,13 +73,14 @@ impl FormDescriptor {
 }
 
 /// Recursively dump an RFRM + contained chunks
+#[cfg(feature = "std")]
 #[allow(unused)]
 pub fn dump_rfrm<'a, W: Write>(w: &mut W, data: &'a [u8], indent: usize) -> Result<&'a [u8]> {
     let (rfrm, mut rfrm_data, remain) = FormDescriptor::slice(data, Endian::Little)?;
     let indstr = "  ".repeat(indent);
     writeln!(w, "{indstr}{rfrm:?}")?;
-    while !rfrm_data.is_empty() {
-        if peek_four_cc(rfrm_data) == K_CHUNK_RFRM {
+    while !is_padding(rfrm_data) {
+        if rfrm_data.len() >= 4 && peek_four_cc(rfrm_data) == K_CHUNK_RFRM {
             rfrm_data = dump_rfrm(w, rfrm_data, indent + 1)?;
         } else {
             let (desc, _, remain) = ChunkDescriptor::slice(rfrm_data, Endian::Little)?;
@@ -0,0 +1,143 @@
+//! PACK::TOCC table chunks, shared by the package reader and `no_std` users
+
+use alloc::{boxed::Box, vec::Vec};
+
+use binrw::binrw;
+use uuid::Uuid;
+
+use crate::format::{parse_uuid, write_uuid, FourCC};
+
+/// PACK::TOCC::ADIR chunk
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct AssetDirectory {
+    #[bw(try_calc = entries.len().try_into())]
+    pub entry_count: u32,
+    #[br(count = entry_count)]
+    pub entries: Vec<AssetDirectoryEntry>,
+}
+
+/// PACK::TOCC::ADIR chunk entry
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct AssetDirectoryEntry {
+    pub asset_type: FourCC,
+    #[br(parse_with = parse_uuid)]
+    #[bw(write_with = write_uuid)]
+    pub asset_id: Uuid,
+    pub version: u32,
+    pub other_version: u32,
+    pub offset: u64,
+    pub decompressed_size: u64,
+    pub size: u64,
+}
+
+impl AssetDirectoryEntry {
+    /// Logs a warning if the asset type contains null or non-ASCII bytes.
+    /// Returns whether the entry passed validation.
+    pub fn validate(&self) -> bool {
+        if self.asset_type.is_printable() {
+            return true;
+        }
+        log::warn!(
+            "Asset {} has invalid type {:?} ({:02X?}), possible misaligned read",
+            self.asset_id,
+            self.asset_type,
+            self.asset_type.0
+        );
+        false
+    }
+}
+
+/// PACK::TOCC::META chunk
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct MetadataTable {
+    #[bw(try_calc = entries.len().try_into())]
+    pub entry_count: u32,
+    #[br(count = entry_count)]
+    pub entries: Vec<MetadataTableEntry>,
+}
+
+/// PACK::TOCC::META chunk entry
+#[binrw]
+#[derive(Clone, Debug)]
+pub struct MetadataTableEntry {
+    #[br(parse_with = parse_uuid)]
+    #[bw(write_with = write_uuid)]
+    pub asset_id: Uuid,
+    pub offset: u32,
+}
+
+/// PACK::TOCC::STRG chunk
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct StringTable {
+    #[bw(try_calc = entries.len().try_into())]
+    pub entry_count: u32,
+    #[br(count = entry_count)]
+    pub entries: Vec<StringTableEntry>,
+}
+
+/// PACK::TOCC::STRG chunk entry
+#[binrw]
+#[derive(Clone, Debug, Default)]
+pub struct StringTableEntry {
+    // Stored as a u32 in file byte order, so the bytes are reversed in little-endian packages
+    // and in natural order in big-endian packages
+    #[br(map = FourCC::from_u32)]
+    #[bw(map = FourCC::as_u32)]
+    pub kind: FourCC,
+    #[br(parse_with = parse_uuid)]
+    #[bw(write_with = write_uuid)]
+    pub asset_id: Uuid,
+    #[bw(try_calc = name.len().try_into())]
+    pub name_length: u32,
+    #[br(count = name_length)]
+    pub name: Vec<u8>,
+}
+
+/// Encoding of a STRG name
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum NameEncoding {
+    /// UTF-8, without a byte order mark
+    #[default]
+    Utf8,
+    /// UTF-16LE, starting with a byte order mark
+    Utf16Le,
+    /// UTF-16BE, starting with a byte order mark
+    Utf16Be,
+}
+
+impl NameEncoding {
+    /// Encodes a name, including the byte order mark for UTF-16.
+    pub fn encode(self, name: &str) -> Vec<u8> {
+        let utf16 = |bom: [u8; 2], to_bytes: fn(u16) -> [u8; 2]| {
+            bom.into_iter().chain(name.encode_utf16().flat_map(to_bytes)).collect()
+        };
+        match self {
+            NameEncoding::Utf8 => name.as_bytes().to_vec(),
+            NameEncoding::Utf16Le => utf16([0xFF, 0xFE], u16::to_le_bytes),
+            NameEncoding::Utf16Be => utf16([0xFE, 0xFF], u16::to_be_bytes),
+        }
+    }
+}
+
+impl StringTableEntry {
+    /// Entry for an asset's encoded name.
+    pub fn new(kind: FourCC, asset_id: Uuid, name: Vec<u8>) -> Self {
+        // Default::default makes the IDE happy,
+        // just need to suppress clippy
+        #[allow(clippy::needless_update)]
+        Self { kind, asset_id, name, ..Default::default() }
+    }
+
+    /// Encoding of the name, detected by its byte order mark.
+    pub fn name_encoding(&self) -> NameEncoding {
+        match self.name.as_slice() {
+            [0xFF, 0xFE, ..] => NameEncoding::Utf16Le,
+            [0xFE, 0xFF, ..] => NameEncoding::Utf16Be,
+            _ => NameEncoding::Utf8,
+        }
+    }
+}
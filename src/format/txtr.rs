@@ -1,9 +1,14 @@
 use std::num::NonZeroUsize;
 
-use anyhow::{ensure, Result};
+use anyhow::{anyhow, ensure, Error, Result};
 use binrw::binrw;
 use tegra_swizzle::surface::BlockDim;
 
+use crate::format::FourCC;
+
+// Texture
+pub const K_FORM_TXTR: FourCC = FourCC(*b"TXTR");
+
 #[binrw]
 #[repr(u32)]
 #[brw(repr(u32))]
@@ -81,6 +86,22 @@ pub struct STextureHeader {
     pub sampler_data: STextureSamplerData,
 }
 
+impl STextureHeader {
+    /// Dimensions of a mip level.
+    pub fn mip_dims(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// Size of a mip level of a single layer in deswizzled texture data.
+    pub fn mip_size(&self, level: u32) -> usize {
+        let (width, height) = self.mip_dims(level);
+        let (bw, bh, _) = self.format.block_size();
+        let blocks_x = (width as usize).div_ceil(bw as usize);
+        let blocks_y = (height as usize).div_ceil(bh as usize);
+        blocks_x * blocks_y * self.format.bytes_per_pixel() as usize
+    }
+}
+
 #[binrw]
 #[derive(Clone, Debug)]
 pub struct STextureSamplerData {
@@ -370,6 +391,120 @@ impl ETextureFormat {
     }
 }
 
+/// GX texture format, as stored in original (GameCube/Wii) TXTR headers.
+///
+/// The BCn variants use the raw values of the equivalent [`ETextureFormat`].
+#[binrw]
+#[repr(u32)]
+#[brw(repr(u32))]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GxFormat {
+    I4 = 0x0,
+    I8 = 0x1,
+    Ia4 = 0x2,
+    Ia8 = 0x3,
+    C4 = 0x4,
+    C8 = 0x5,
+    C14x2 = 0x6,
+    Rgb565 = 0x7,
+    Rgb5a3 = 0x8,
+    Rgba8 = 0x9,
+    Cmpr = 0xA,
+    Bc1 = 20,      // DXT1
+    Bc1Srgb = 21,  // DXT1
+    Bc2 = 22,      // DXT3
+    Bc2Srgb = 23,  // DXT3
+    Bc3 = 24,      // DXT5
+    Bc3Srgb = 25,  // DXT5
+    Bc4 = 26,      // RGTC1
+    Bc4Snorm = 27, // RGTC1
+    Bc5 = 28,      // RGTC2
+    Bc5Snorm = 29, // RGTC2
+}
+
+impl GxFormat {
+    pub const ALL: [GxFormat; 21] = [
+        GxFormat::I4,
+        GxFormat::I8,
+        GxFormat::Ia4,
+        GxFormat::Ia8,
+        GxFormat::C4,
+        GxFormat::C8,
+        GxFormat::C14x2,
+        GxFormat::Rgb565,
+        GxFormat::Rgb5a3,
+        GxFormat::Rgba8,
+        GxFormat::Cmpr,
+        GxFormat::Bc1,
+        GxFormat::Bc1Srgb,
+        GxFormat::Bc2,
+        GxFormat::Bc2Srgb,
+        GxFormat::Bc3,
+        GxFormat::Bc3Srgb,
+        GxFormat::Bc4,
+        GxFormat::Bc4Snorm,
+        GxFormat::Bc5,
+        GxFormat::Bc5Snorm,
+    ];
+
+    /// Block dimensions in pixels (width, height).
+    ///
+    /// CMPR is stored as 8x8 blocks of four 4x4 DXT1 sub-blocks.
+    pub fn block_dims(self) -> (u32, u32) {
+        match self {
+            GxFormat::I4 | GxFormat::C4 | GxFormat::Cmpr => (8, 8),
+            GxFormat::I8 | GxFormat::Ia4 | GxFormat::C8 => (8, 4),
+            GxFormat::Ia8
+            | GxFormat::C14x2
+            | GxFormat::Rgb565
+            | GxFormat::Rgb5a3
+            | GxFormat::Rgba8
+            | GxFormat::Bc1
+            | GxFormat::Bc1Srgb
+            | GxFormat::Bc2
+            | GxFormat::Bc2Srgb
+            | GxFormat::Bc3
+            | GxFormat::Bc3Srgb
+            | GxFormat::Bc4
+            | GxFormat::Bc4Snorm
+            | GxFormat::Bc5
+            | GxFormat::Bc5Snorm => (4, 4),
+        }
+    }
+
+    /// Size of a single block in bytes.
+    ///
+    /// RGBA8 blocks are stored as two 32-byte halves (AR, then GB).
+    pub fn bytes_per_block(self) -> u32 {
+        match self {
+            GxFormat::Rgba8 => 64,
+            GxFormat::Bc1 | GxFormat::Bc1Srgb | GxFormat::Bc4 | GxFormat::Bc4Snorm => 8,
+            GxFormat::Bc2
+            | GxFormat::Bc2Srgb
+            | GxFormat::Bc3
+            | GxFormat::Bc3Srgb
+            | GxFormat::Bc5
+            | GxFormat::Bc5Snorm => 16,
+            _ => 32,
+        }
+    }
+}
+
+impl TryFrom<u32> for GxFormat {
+    type Error = Error;
+
+    fn try_from(value: u32) -> Result<Self> {
+        GxFormat::ALL
+            .into_iter()
+            .find(|&f| f as u32 == value)
+            .ok_or_else(|| anyhow!("Unknown GX texture format {:#X}", value))
+    }
+}
+
+impl From<GxFormat> for u32 {
+    fn from(value: GxFormat) -> Self { value as u32 }
+}
+
 pub fn deswizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
     let (bw, bh, bd) = header.format.block_size();
     let block_dim = BlockDim {
@@ -401,3 +536,40 @@ pub fn deswizzle(header: &STextureHeader, data: &[u8]) -> Result<Vec<u8>> {
         header.layers as usize,
     )?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gx_format_raw_round_trip() {
+        for format in GxFormat::ALL {
+            assert_eq!(GxFormat::try_from(u32::from(format)).unwrap(), format);
+        }
+        assert_eq!(GxFormat::try_from(21).unwrap(), GxFormat::Bc1Srgb);
+        assert_eq!(GxFormat::try_from(29).unwrap(), GxFormat::Bc5Snorm);
+        assert!(GxFormat::try_from(0xB).is_err());
+        assert!(GxFormat::try_from(30).is_err());
+    }
+
+    #[test]
+    fn gx_format_blocks() {
+        assert_eq!(GxFormat::Cmpr.block_dims(), (8, 8));
+        assert_eq!(GxFormat::Cmpr.bytes_per_block(), 32);
+        assert_eq!(GxFormat::Rgba8.block_dims(), (4, 4));
+        assert_eq!(GxFormat::Rgba8.bytes_per_block(), 64);
+        // BCn variants match the block sizes of the equivalent ETextureFormat
+        for (gx, format) in [
+            (GxFormat::Bc1Srgb, ETextureFormat::RgbaBc1Srgb),
+            (GxFormat::Bc2Srgb, ETextureFormat::RgbaBc2Srgb),
+            (GxFormat::Bc3Srgb, ETextureFormat::RgbaBc3Srgb),
+            (GxFormat::Bc4Snorm, ETextureFormat::RgbaBc4Snorm),
+            (GxFormat::Bc5Snorm, ETextureFormat::RgbaBc5Snorm),
+        ] {
+            assert_eq!(u32::from(gx), format as u32);
+            assert_eq!(gx.bytes_per_block(), format.bytes_per_pixel());
+            let (bw, bh, _) = format.block_size();
+            assert_eq!(gx.block_dims(), (bw as u32, bh as u32));
+        }
+    }
+}
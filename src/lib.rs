@@ -0,0 +1,13 @@
+//! Tools for working with Retro game formats.
+//!
+//! Without the default `std` feature, only the descriptor and table types, [`format::FourCC`]
+//! and LZSS decompression are available, for `no_std` + `alloc` targets.
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+pub mod util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
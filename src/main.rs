@@ -1,9 +1,8 @@
 mod argh_version;
 mod cmd;
-mod format;
-mod util;
 
 use argh::FromArgs;
+use retrotool::{format, util};
 
 #[derive(FromArgs, PartialEq, Debug)]
 /// GameCube/Wii decompilation project tools.
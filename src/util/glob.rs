@@ -0,0 +1,65 @@
+/// Matches `text` against a pattern. Patterns containing `*` or `?` are matched as globs
+/// against the whole text, and other patterns match any substring.
+pub fn matches(pattern: &str, text: &str, case_sensitive: bool) -> bool {
+    let (pattern, text) = if case_sensitive {
+        (pattern.to_string(), text.to_string())
+    } else {
+        (pattern.to_lowercase(), text.to_lowercase())
+    };
+    if pattern.contains(['*', '?']) {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        glob(&pattern, &text)
+    } else {
+        text.contains(&pattern)
+    }
+}
+
+/// Glob matching with `*` (any characters) and `?` (one character).
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` in the pattern, and the text position it's matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_and_substring_patterns() {
+        assert!(matches("tex_*", "tex_rock", true));
+        assert!(matches("tex_*", "tex_", true));
+        assert!(!matches("tex_*", "my_tex_rock", true));
+        assert!(matches("*_rock", "tex_rock", true));
+        assert!(matches("t?x_*k", "tax_rock", true));
+        assert!(!matches("t?x", "tx", true));
+        assert!(matches("*a*b*", "xxaxxbxx", true));
+        assert!(!matches("*a*b", "xxaxxbxx", true));
+        assert!(matches("rock", "tex_rock_01", true));
+        assert!(!matches("ROCK", "tex_rock_01", true));
+        assert!(matches("ROCK", "tex_rock_01", false));
+        assert!(matches("TEX_*", "tex_rock", false));
+    }
+}
@@ -1,11 +1,101 @@
-use std::borrow::Cow;
+use alloc::{borrow::Cow, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::io::Write;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 
 /// https://wiki.axiodl.com/w/LZSS_Compression
-pub fn decompress<const M: u8>(mut input: &[u8], output: &mut [u8]) -> bool {
+///
+/// [`compress`] produces data in the same format, with the same group, match length and
+/// window sizes for each mode.
+///
+/// Returns the number of bytes written to `output`. Fails if the input ends partway through
+/// a literal or match, a match has a distance of zero or references data before the start of
+/// the output, or the output is too small.
+pub fn decompress<const M: u8>(input: &[u8], output: &mut [u8]) -> Result<usize> {
+    let mut sink = SliceSink { output, len: 0 };
+    decode::<M, _>(input, &mut sink)?;
+    Ok(sink.len)
+}
+
+/// Decompresses data like [`decompress`], growing the output as it decodes, for blocks
+/// without a known decompressed size.
+pub fn decompress_to_vec<const M: u8>(input: &[u8]) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 2);
+    decode::<M, _>(input, &mut output)?;
+    Ok(output)
+}
+
+/// Decoder output, shared by [`decompress`] and [`decompress_to_vec`]
+trait Sink {
+    /// Number of bytes written so far
+    fn len(&self) -> usize;
+
+    /// Appends a literal group.
+    fn literal(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Appends `len` bytes copied from offset `seek`, which may overlap the bytes produced.
+    fn copy(&mut self, seek: usize, len: usize) -> Result<()>;
+}
+
+/// Fixed-size output buffer
+struct SliceSink<'a> {
+    output: &'a mut [u8],
+    len: usize,
+}
+
+impl SliceSink<'_> {
+    fn reserve(&self, len: usize) -> Result<()> {
+        ensure!(
+            self.len + len <= self.output.len(),
+            "Decompressed data exceeds output size {:#X}",
+            self.output.len()
+        );
+        Ok(())
+    }
+}
+
+impl Sink for SliceSink<'_> {
+    fn len(&self) -> usize { self.len }
+
+    fn literal(&mut self, data: &[u8]) -> Result<()> {
+        self.reserve(data.len())?;
+        self.output[self.len..self.len + data.len()].copy_from_slice(data);
+        self.len += data.len();
+        Ok(())
+    }
+
+    fn copy(&mut self, seek: usize, len: usize) -> Result<()> {
+        self.reserve(len)?;
+        for n in 0..len {
+            self.output[self.len + n] = self.output[seek + n];
+        }
+        self.len += len;
+        Ok(())
+    }
+}
+
+impl Sink for Vec<u8> {
+    fn len(&self) -> usize { Vec::len(self) }
+
+    fn literal(&mut self, data: &[u8]) -> Result<()> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn copy(&mut self, seek: usize, len: usize) -> Result<()> {
+        // Matches may overlap the bytes they produce, so copy one byte at a time
+        for n in 0..len {
+            let byte = self[seek + n];
+            self.push(byte);
+        }
+        Ok(())
+    }
+}
+
+/// Decodes an LZSS stream into `output`. See [`decompress`].
+fn decode<const M: u8, S: Sink>(mut input: &[u8], output: &mut S) -> Result<()> {
     let group_len = 2usize.pow(M as u32 - 1);
-    let mut out_cur = 0usize;
 
     let mut header_byte = 0u8;
     let mut group = 0u8;
@@ -14,42 +104,260 @@ pub fn decompress<const M: u8>(mut input: &[u8], output: &mut [u8]) -> bool {
             header_byte = input[0];
             input = &input[1..];
             group = 8;
+            if input.is_empty() {
+                break;
+            }
         }
 
         if header_byte & 0x80 == 0 {
-            output[out_cur..group_len + out_cur].copy_from_slice(&input[..group_len]);
+            let Some(literal) = input.get(..group_len) else {
+                bail!("Compressed data ends within a literal at output offset {:#X}", output.len());
+            };
+            output.literal(literal)?;
             input = &input[group_len..];
-            out_cur += group_len;
         } else {
-            let count = (input[0] as usize >> 4) + (4 - M as usize);
-            let length = (((input[0] as usize & 0xF) << 0x8) | input[1] as usize) << (M - 1);
+            let &[b0, b1, ..] = input else {
+                bail!("Compressed data ends within a match at output offset {:#X}", output.len());
+            };
+            let count = (b0 as usize >> 4) + (4 - M as usize);
+            let length = (((b0 as usize & 0xF) << 0x8) | b1 as usize) << (M - 1);
             input = &input[2..];
 
-            let seek = out_cur - length;
-            for n in 0..count * group_len {
-                output[out_cur + n] = output[seek + n];
+            if length == 0 {
+                bail!("Match at output offset {:#X} has a distance of zero", output.len());
             }
-            out_cur += count * group_len;
+            let Some(seek) = output.len().checked_sub(length) else {
+                bail!(
+                    "Match at output offset {:#X} references {:#X} bytes back, before the start of the output",
+                    output.len(),
+                    length
+                );
+            };
+            output.copy(seek, count * group_len)?;
         }
 
         header_byte <<= 1;
         group -= 1;
     }
 
-    out_cur == output.len()
+    Ok(())
+}
+
+// Maximum match distance, in groups
+#[cfg(feature = "std")]
+const WINDOW_SIZE: usize = 0xFFF;
+// Hash chain table size
+#[cfg(feature = "std")]
+const HASH_BITS: u32 = 15;
+// Maximum number of hash chain entries searched per position
+#[cfg(feature = "std")]
+const MAX_CHAIN: usize = 256;
+
+/// Compresses data such that `decompress::<M>` reproduces it exactly.
+///
+/// Data is processed in groups of `2^(M-1)` bytes, so the input size must be a multiple of
+/// the group size. Matches are `4 - M` to `19 - M` groups long, and reference up to 4095 groups
+/// back. Match finding is greedy.
+#[cfg(feature = "std")]
+pub fn compress<const M: u8>(input: &[u8]) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() / 2 + 1);
+    compress_into::<M, _>(input, &mut output)?;
+    Ok(output)
+}
+
+/// Compresses data like [`compress`], writing the output to `w` one header group at a time.
+#[cfg(feature = "std")]
+pub fn compress_into<const M: u8, W: Write>(input: &[u8], w: &mut W) -> Result<()> {
+    let group_len = 2usize.pow(M as u32 - 1);
+    ensure!(
+        input.len().is_multiple_of(group_len),
+        "Data size {:#X} is not a multiple of {} for compression mode {}",
+        input.len(),
+        group_len,
+        M
+    );
+    let min_count = 4 - M as usize;
+    let max_count = min_count + 0xF;
+    // Matches are located by hashing their first (at least 3) bytes
+    let key_len = (min_count * group_len).max(3);
+
+    // Header byte followed by up to 8 literals or matches
+    let mut block = Vec::with_capacity(1 + 8 * group_len.max(2));
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; input.len() / group_len];
+    let insert = |pos: usize, head: &mut [usize], prev: &mut [usize]| {
+        if pos + key_len <= input.len() {
+            let hash = hash_key(&input[pos..pos + key_len]);
+            prev[pos / group_len] = head[hash];
+            head[hash] = pos;
+        }
+    };
+
+    let mut group = 0u8;
+    let mut in_cur = 0usize;
+    while in_cur < input.len() {
+        if group == 0 {
+            w.write_all(&block)?;
+            block.clear();
+            block.push(0);
+            group = 8;
+        }
+        group -= 1;
+
+        let mut best_count = 0usize;
+        let mut best_dist = 0usize;
+        if in_cur + key_len <= input.len() {
+            let mut cand = head[hash_key(&input[in_cur..in_cur + key_len])];
+            let mut chain = 0;
+            while cand != usize::MAX && chain < MAX_CHAIN {
+                let dist = (in_cur - cand) / group_len;
+                if dist > WINDOW_SIZE {
+                    break;
+                }
+                let mut count = 0;
+                while count < max_count
+                    && in_cur + (count + 1) * group_len <= input.len()
+                    && input[cand + count * group_len..cand + (count + 1) * group_len]
+                        == input[in_cur + count * group_len..in_cur + (count + 1) * group_len]
+                {
+                    count += 1;
+                }
+                if count > best_count {
+                    best_count = count;
+                    best_dist = dist;
+                    if count == max_count {
+                        break;
+                    }
+                }
+                cand = prev[cand / group_len];
+                chain += 1;
+            }
+        }
+
+        if best_count >= min_count {
+            block[0] |= 1 << group;
+            let count = best_count - min_count;
+            block.push(((count << 4) | (best_dist >> 8)) as u8);
+            block.push(best_dist as u8);
+            for n in 0..best_count {
+                insert(in_cur + n * group_len, &mut head, &mut prev);
+            }
+            in_cur += best_count * group_len;
+        } else {
+            block.extend_from_slice(&input[in_cur..in_cur + group_len]);
+            insert(in_cur, &mut head, &mut prev);
+            in_cur += group_len;
+        }
+    }
+    w.write_all(&block)?;
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+#[inline]
+fn hash_key(key: &[u8]) -> usize {
+    let value = key.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+    (value.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compression mode for zlib streams, with the `experimental-deflate` feature.
+///
+/// Experimental: no known packages use zlib streams, so this mode number was chosen here
+/// rather than taken from game data. Packages written with it can't be read by the games, or
+/// by builds without the feature.
+#[cfg(feature = "experimental-deflate")]
+pub const DEFLATE_MODE: u32 = 4;
+
+/// Whether the given compression mode can be compressed and decompressed.
+pub fn is_supported_mode(mode: u32) -> bool {
+    #[cfg(feature = "experimental-deflate")]
+    if mode == DEFLATE_MODE {
+        return true;
+    }
+    matches!(mode, 0..=3)
+}
+
+/// Size of a compression group for the given mode. Data sizes must be a multiple of this.
+pub fn group_len(mode: u32) -> Option<usize> { matches!(mode, 1..=3).then(|| 2usize.pow(mode - 1)) }
+
+// Maximum zlib expansion ratio
+#[cfg(feature = "experimental-deflate")]
+const DEFLATE_MAX_RATIO: u64 = 1032;
+
+/// Upper bound of the decompressed size of `len` bytes of data compressed with the given mode
+/// (without the mode header), or `None` for unsupported modes. Used to reject sizes read from
+/// untrusted data before allocating the output.
+///
+/// Each 2-byte LZSS match produces at most `19 - M` groups, and literals don't expand.
+pub fn max_decompressed_size(mode: u32, len: usize) -> Option<u64> {
+    match mode {
+        0 => Some(len as u64),
+        1..=3 => {
+            let max_match = (19 - mode as u64) * group_len(mode)? as u64;
+            Some((len as u64 / 2 + 1) * max_match)
+        }
+        #[cfg(feature = "experimental-deflate")]
+        DEFLATE_MODE => Some(len as u64 * DEFLATE_MAX_RATIO),
+        _ => None,
+    }
+}
+
+/// Compresses data with the given mode, prepending the 4-byte mode header.
+#[cfg(feature = "std")]
+pub fn compress_buffer(mode: u32, input: &[u8]) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() / 2 + 5);
+    compress_streaming(mode, input, &mut output)?;
+    Ok(output)
+}
+
+/// Compresses data with the given mode, writing the 4-byte mode header and compressed data
+/// directly to `w`. The output matches [`compress_buffer`].
+#[cfg(feature = "std")]
+pub fn compress_streaming<W: Write>(mode: u32, input: &[u8], w: &mut W) -> Result<()> {
+    if !is_supported_mode(mode) {
+        bail!("Unsupported compression mode {}", mode);
+    }
+    w.write_all(&mode.to_le_bytes())?;
+    match mode {
+        1 => compress_into::<1, _>(input, w),
+        2 => compress_into::<2, _>(input, w),
+        3 => compress_into::<3, _>(input, w),
+        #[cfg(feature = "experimental-deflate")]
+        DEFLATE_MODE => deflate(input, w),
+        _ => Ok(w.write_all(input)?),
+    }
 }
 
 pub fn decompress_buffer(
     compressed_data: &[u8],
     decompressed_size: u64,
-) -> Result<(u32, Cow<[u8]>)> {
+) -> Result<(u32, Cow<'_, [u8]>)> {
     if compressed_data.len() < 4 {
         bail!("Invalid compressed data size: {}", compressed_data.len());
     }
     if compressed_data[0..4] == [0u8; 4] {
-        // Shortcut for uncompressed data
-        return Ok((0, Cow::Borrowed(&compressed_data[4..])));
+        // Shortcut for uncompressed data: the rest of the block is stored verbatim
+        let data = &compressed_data[4..];
+        ensure!(
+            data.len() as u64 == decompressed_size,
+            "Uncompressed data size {:#X} does not match expected size {:#X}",
+            data.len(),
+            decompressed_size
+        );
+        return Ok((0, Cow::Borrowed(data)));
     }
+    let mode = u32::from_le_bytes(compressed_data[0..4].try_into().unwrap());
+    let Some(max_size) = max_decompressed_size(mode, compressed_data.len() - 4) else {
+        bail!("Unsupported compression mode {}", mode);
+    };
+    ensure!(
+        decompressed_size <= max_size,
+        "Decompressed size {:#X} exceeds the maximum {:#X} for a {:#X} byte mode {} block",
+        decompressed_size,
+        max_size,
+        compressed_data.len(),
+        mode
+    );
     let mut out = vec![0u8; decompressed_size as usize];
     let mode = decompress_into(compressed_data, &mut out)?;
     Ok((mode, Cow::Owned(out)))
@@ -60,7 +368,21 @@ pub fn decompress_into(compressed_data: &[u8], out: &mut [u8]) -> Result<u32> {
         bail!("Invalid compressed data size: {}", compressed_data.len());
     }
     let mode = u32::from_le_bytes(compressed_data[0..4].try_into().unwrap());
-    let data = &compressed_data[4..];
+    decompress_mode(mode, &compressed_data[4..], out)?;
+    Ok(mode)
+}
+
+/// Guesses the LZSS mode of a block without a mode header, trying modes 1 to 3 in order and
+/// returning the first that decodes the whole block to exactly `expected_len` bytes.
+///
+/// Short blocks may decode validly under more than one mode, so the result is only a hint.
+pub fn detect_mode(input: &[u8], expected_len: usize) -> Option<u32> {
+    let mut out = vec![0u8; expected_len];
+    (1..=3).find(|&mode| decompress_mode(mode, input, &mut out).is_ok())
+}
+
+/// Decompresses a block without a mode header, using the given compression mode.
+pub fn decompress_mode(mode: u32, data: &[u8], out: &mut [u8]) -> Result<()> {
     if !match mode {
         0 => {
             if data.len() == out.len() {
@@ -70,12 +392,208 @@ pub fn decompress_into(compressed_data: &[u8], out: &mut [u8]) -> Result<u32> {
                 false
             }
         }
-        1 => decompress::<1>(data, out),
-        2 => decompress::<2>(data, out),
-        3 => decompress::<3>(data, out),
+        1 => decompress::<1>(data, out)? == out.len(),
+        2 => decompress::<2>(data, out)? == out.len(),
+        3 => decompress::<3>(data, out)? == out.len(),
+        #[cfg(feature = "experimental-deflate")]
+        DEFLATE_MODE => inflate(data, out)?,
         _ => bail!("Unsupported compression mode {}", mode),
     } {
-        bail!("Decompression failed");
+        bail!("Decompressed data does not match the expected size {:#X}", out.len());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "experimental-deflate")]
+fn deflate<W: Write>(input: &[u8], w: &mut W) -> Result<()> {
+    let mut encoder = flate2::write::ZlibEncoder::new(w, flate2::Compression::best());
+    encoder.write_all(input)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Inflates a zlib stream, returning whether it filled `out` exactly.
+#[cfg(feature = "experimental-deflate")]
+fn inflate(data: &[u8], out: &mut [u8]) -> Result<bool> {
+    use std::io::Read;
+
+    let mut decoder = flate2::read::ZlibDecoder::new(data);
+    decoder.read_exact(out)?;
+    Ok(decoder.read(&mut [0u8])? == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compressible data of `len` bytes, with runs and repeated patterns.
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| ((i / 7) ^ ((i % 13) * 3)) as u8).collect()
+    }
+
+    /// Pseudo-random, incompressible data of `len` bytes.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x12345678u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn check_round_trip<const M: u8>() {
+        let group_len = 2usize.pow(M as u32 - 1);
+        let mut inputs = vec![vec![], vec![0; 0x10000], sample(0x4000), noise(0x1000)];
+        // Sizes around a header group (8 groups) and the shortest and longest matches
+        for groups in [1, 2, 3, 7, 8, 9, 16, 17, 18, 19, 20] {
+            inputs.push(sample(groups * group_len));
+            inputs.push(vec![0xAA; groups * group_len]);
+        }
+        // Repeats at the edge of the match window
+        for distance in [WINDOW_SIZE - 1, WINDOW_SIZE, WINDOW_SIZE + 1] {
+            let block = noise(32 * group_len);
+            let mut input = block.clone();
+            input.extend(noise((distance - 32) * group_len).iter().map(|b| b ^ 0x5A));
+            input.extend_from_slice(&block);
+            inputs.push(input);
+        }
+        for input in inputs {
+            let compressed = compress::<M>(&input).unwrap();
+            let mut output = vec![0u8; input.len()];
+            assert_eq!(decompress::<M>(&compressed, &mut output).unwrap(), input.len());
+            assert_eq!(output, input, "mode {} size {:#X}", M, input.len());
+            assert_eq!(decompress_to_vec::<M>(&compressed).unwrap(), input);
+            assert!(
+                input.len() as u64 <= max_decompressed_size(M as u32, compressed.len()).unwrap()
+            );
+        }
+        if group_len > 1 {
+            assert!(compress::<M>(&[0; 3]).is_err());
+        }
+    }
+
+    #[test]
+    fn compress_round_trip() {
+        check_round_trip::<1>();
+        check_round_trip::<2>();
+        check_round_trip::<3>();
+    }
+
+    fn check_truncated<const M: u8>() {
+        let group_len = 2usize.pow(M as u32 - 1);
+        let input = sample(0x400);
+        let compressed = compress::<M>(&input).unwrap();
+        let mut output = vec![0u8; input.len()];
+        for len in 0..compressed.len() {
+            let truncated = &compressed[..len];
+            // A stream cut at a token boundary is valid, but shorter
+            if let Ok(written) = decompress::<M>(truncated, &mut output) {
+                assert!(written < input.len(), "mode {} length {}", M, len);
+            }
+            if let Ok(data) = decompress_to_vec::<M>(truncated) {
+                assert!(data.len() < input.len(), "mode {} length {}", M, len);
+            }
+            assert!(decompress_mode(M as u32, truncated, &mut output).is_err());
+        }
+
+        let error = |input: &[u8], output: &mut [u8]| decompress::<M>(input, output).unwrap_err();
+        let literal = [vec![0x00], vec![0xAB; group_len - 1]].concat();
+        if group_len > 1 {
+            assert!(error(&literal, &mut output).to_string().contains("within a literal"));
+        }
+        assert!(error(&[0x80, 0x10], &mut output).to_string().contains("within a match"));
+        assert!(error(&[0x80, 0x00, 0x01], &mut output).to_string().contains("before the start"));
+        // A zero-distance match after a literal would copy bytes not yet written
+        let zero_distance = [vec![0x40], vec![0xAB; group_len], vec![0x00, 0x00]].concat();
+        assert!(error(&zero_distance, &mut output).to_string().contains("distance of zero"));
+        assert!(decompress_to_vec::<M>(&zero_distance).is_err());
+        let literal = [vec![0x00], vec![0xAB; group_len]].concat();
+        assert!(error(&literal, &mut []).to_string().contains("exceeds output size"));
+        assert!(decompress_to_vec::<M>(&[0x80, 0x00, 0x01]).is_err());
+    }
+
+    fn check_zero_distance<const M: u8>() {
+        let input = [0x80, 0x00, 0x00];
+        let mut output = vec![0u8; 0x100];
+        let error = decompress::<M>(&input, &mut output).unwrap_err();
+        assert!(error.to_string().contains("distance of zero"), "mode {}: {}", M, error);
+        let error = decompress_to_vec::<M>(&input).unwrap_err();
+        assert!(error.to_string().contains("distance of zero"), "mode {}: {}", M, error);
+    }
+
+    #[test]
+    fn zero_distance_matches_are_rejected() {
+        check_zero_distance::<1>();
+        check_zero_distance::<2>();
+        check_zero_distance::<3>();
+    }
+
+    #[test]
+    fn truncated_streams_are_rejected() {
+        check_truncated::<1>();
+        check_truncated::<2>();
+        check_truncated::<3>();
+    }
+
+    #[test]
+    fn detect_mode_of_compressed_data() {
+        let input = sample(0x400);
+        for (mode, compressed) in [
+            (1, compress::<1>(&input).unwrap()),
+            (2, compress::<2>(&input).unwrap()),
+            (3, compress::<3>(&input).unwrap()),
+        ] {
+            assert_eq!(detect_mode(&compressed, input.len()), Some(mode));
+            assert_eq!(detect_mode(&compressed, input.len() + 1), None);
+        }
+        // A match before the start of the output is invalid in every mode
+        assert_eq!(detect_mode(&[0x80, 0xFF, 0xFF], 0x10), None);
+    }
+
+    #[cfg(feature = "experimental-deflate")]
+    #[test]
+    fn deflate_round_trip() {
+        for input in [vec![], sample(0x4000), noise(0x1000), vec![0; 0x10000]] {
+            let compressed = compress_buffer(DEFLATE_MODE, &input).unwrap();
+            assert_eq!(compressed[..4], DEFLATE_MODE.to_le_bytes());
+            let (mode, data) = decompress_buffer(&compressed, input.len() as u64).unwrap();
+            assert_eq!((mode, data.as_ref()), (DEFLATE_MODE, input.as_slice()));
+            assert!(decompress_buffer(&compressed, input.len() as u64 + 1).is_err());
+        }
+    }
+
+    #[cfg(not(feature = "experimental-deflate"))]
+    #[test]
+    fn deflate_mode_unsupported_without_feature() {
+        assert!(!is_supported_mode(4));
+        assert!(compress_buffer(4, &sample(64)).is_err());
+        let mut block = 4u32.to_le_bytes().to_vec();
+        block.extend_from_slice(&sample(16));
+        assert!(decompress_buffer(&block, 64).is_err());
+    }
+
+    #[test]
+    fn streaming_matches_buffered() {
+        let input = sample(1 << 20);
+        for mode in 1..=3u8 {
+            let mut streamed = vec![];
+            compress_streaming(mode as u32, &input, &mut streamed).unwrap();
+            let buffered = match mode {
+                1 => compress::<1>(&input),
+                2 => compress::<2>(&input),
+                _ => compress::<3>(&input),
+            }
+            .unwrap();
+            assert_eq!(streamed[..4], (mode as u32).to_le_bytes());
+            assert_eq!(streamed[4..], buffered);
+            assert_eq!(decompress_buffer(&streamed, input.len() as u64).unwrap().1, input);
+        }
+        let mut stored = vec![];
+        compress_streaming(0, &input, &mut stored).unwrap();
+        assert_eq!(stored[4..], input);
+        assert!(compress_streaming(9, &input, &mut vec![]).is_err());
     }
-    Ok(mode)
 }
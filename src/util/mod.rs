@@ -1,8 +1,16 @@
+#[cfg(feature = "std")]
 pub mod astc;
+#[cfg(feature = "std")]
 pub mod dds;
+#[cfg(feature = "mmap")]
 pub mod file;
+#[cfg(feature = "std")]
+pub mod glob;
 pub mod lzss;
+#[cfg(feature = "std")]
 pub mod math_classes;
+#[cfg(feature = "std")]
+pub mod png;
 
 /// Creates a fixed-size array reference from a slice.
 #[macro_export]
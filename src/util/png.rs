@@ -0,0 +1,100 @@
+use std::{borrow::Cow, io::Write};
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::format::txtr::{ETextureFormat, STextureHeader};
+
+/// Whether textures of the given format can be written as PNG.
+///
+/// RGBA8 is written directly. BCn (other than BC6H and the SNORM variants of BC4 and BC5)
+/// and ASTC textures are decoded to RGBA8 first.
+pub fn supports_format(format: ETextureFormat) -> bool {
+    matches!(
+        format,
+        ETextureFormat::Rgba8Unorm
+            | ETextureFormat::Rgba8Srgb
+            | ETextureFormat::RgbaBc1Unorm
+            | ETextureFormat::RgbaBc1Srgb
+            | ETextureFormat::RgbaBc2Unorm
+            | ETextureFormat::RgbaBc2Srgb
+            | ETextureFormat::RgbaBc3Unorm
+            | ETextureFormat::RgbaBc3Srgb
+            | ETextureFormat::RgbaBc4Unorm
+            | ETextureFormat::RgbaBc5Unorm
+            | ETextureFormat::BptcUnorm
+            | ETextureFormat::BptcUnormSrgb
+    ) || format.is_astc()
+}
+
+/// Writes a mip level of the first layer of deswizzled texture data as a PNG.
+/// See [`supports_format`] for the supported texture formats.
+pub fn write_png<W: Write>(
+    w: &mut W,
+    head: &STextureHeader,
+    data: &[u8],
+    level: u32,
+) -> Result<()> {
+    let rgba = decode_rgba(head, data, level)?;
+    let (width, height) = head.mip_dims(level);
+    let mut encoder = png::Encoder::new(w, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgba)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Decodes a mip level of the first layer of deswizzled texture data to RGBA8.
+pub fn decode_rgba<'a>(head: &STextureHeader, data: &'a [u8], level: u32) -> Result<Cow<'a, [u8]>> {
+    if !supports_format(head.format) {
+        bail!("Unsupported PNG texture format {:?}", head.format);
+    }
+    ensure!((level as usize) < head.mip_sizes.len(), "Invalid mip level {}", level);
+    let offset: usize = (0..level).map(|l| head.mip_size(l)).sum();
+    let size = head.mip_size(level);
+    ensure!(offset + size <= data.len(), "Texture data too small for mip level {}", level);
+    let data = &data[offset..offset + size];
+
+    let (width, height) = head.mip_dims(level);
+    let (width, height) = (width as usize, height as usize);
+    let mut image = vec![0u32; width * height];
+    let result = match head.format {
+        ETextureFormat::Rgba8Unorm | ETextureFormat::Rgba8Srgb => {
+            return Ok(Cow::Borrowed(data));
+        }
+        ETextureFormat::RgbaBc1Unorm | ETextureFormat::RgbaBc1Srgb => {
+            texture2ddecoder::decode_bc1a(data, width, height, &mut image)
+        }
+        ETextureFormat::RgbaBc2Unorm | ETextureFormat::RgbaBc2Srgb => {
+            texture2ddecoder::decode_bc2(data, width, height, &mut image)
+        }
+        ETextureFormat::RgbaBc3Unorm | ETextureFormat::RgbaBc3Srgb => {
+            texture2ddecoder::decode_bc3(data, width, height, &mut image)
+        }
+        ETextureFormat::RgbaBc4Unorm => {
+            texture2ddecoder::decode_bc4(data, width, height, &mut image)
+        }
+        ETextureFormat::RgbaBc5Unorm => {
+            texture2ddecoder::decode_bc5(data, width, height, &mut image)
+        }
+        ETextureFormat::BptcUnorm | ETextureFormat::BptcUnormSrgb => {
+            texture2ddecoder::decode_bc7(data, width, height, &mut image)
+        }
+        format => {
+            let (bw, bh, _) = format.block_size();
+            texture2ddecoder::decode_astc(data, width, height, bw as usize, bh as usize, &mut image)
+        }
+    };
+    result.map_err(|e| anyhow!("Failed to decode {:?} texture: {}", head.format, e))?;
+    // Decoded pixels are BGRA in little-endian byte order
+    Ok(Cow::Owned(
+        image
+            .into_iter()
+            .flat_map(|pixel| {
+                let [b, g, r, a] = pixel.to_le_bytes();
+                [r, g, b, a]
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,82 @@
+//! WebAssembly API for reading packages.
+//!
+//! Packages are passed in as byte slices and read as little-endian, without touching the
+//! filesystem or spawning threads.
+//!
+//! Build the library from the `retrotool-ffi` crate with `-p retrotool-ffi --target
+//! wasm32-unknown-unknown --no-default-features --features wasm`. Tests run in this crate with
+//! `--target wasm32-unknown-unknown --no-default-features --features wasm`, under
+//! `wasm-bindgen-test-runner` set as the target runner.
+use binrw::Endian;
+use uuid::Uuid;
+use wasm_bindgen::prelude::*;
+
+use crate::format::pack::LazyPackage;
+
+/// Lists the assets in a package, returning its
+/// [`PackageManifest`](crate::format::pack::PackageManifest) as a JavaScript object. Only the
+/// table of contents is read; no asset data is decompressed.
+#[wasm_bindgen]
+pub fn list_assets(data: &[u8]) -> Result<JsValue, JsValue> {
+    let package = LazyPackage::read(data, Endian::Little).map_err(to_js_error)?;
+    let manifest = package.manifest().map_err(to_js_error)?;
+    serde_wasm_bindgen::to_value(&manifest).map_err(JsValue::from)
+}
+
+/// Decompresses a single asset by its UUID string.
+#[wasm_bindgen]
+pub fn extract_asset(data: &[u8], id: &str) -> Result<Vec<u8>, JsValue> {
+    let id = Uuid::parse_str(id).map_err(|e| JsError::new(&e.to_string()))?;
+    let package = LazyPackage::read(data, Endian::Little).map_err(to_js_error)?;
+    let Some(index) = package.headers().position(|header| header.id == id) else {
+        return Err(JsError::new(&format!("Asset {id} not found")).into());
+    };
+    Ok(package.decompress(index).map_err(to_js_error)?.into_owned())
+}
+
+fn to_js_error(e: anyhow::Error) -> JsValue { JsError::new(&format!("{e:?}")).into() }
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+    use crate::format::{
+        pack::{Package, PackageBuilder, PackageManifest},
+        rfrm::FormDescriptor,
+        FourCC,
+    };
+
+    #[wasm_bindgen_test]
+    fn list_and_extract_assets() {
+        let mut builder = PackageBuilder::new();
+        for i in 0..4u8 {
+            let mut cursor = Cursor::new(Vec::new());
+            FormDescriptor { size: 0, unk: 0, id: FourCC(*b"TXTR"), version_a: 1, version_b: 0 }
+                .write(&mut cursor, Endian::Little, |w| {
+                    w.write_all(&[i; 256])?;
+                    Ok(())
+                })
+                .unwrap();
+            builder
+                .add_asset(FourCC(*b"TXTR"), cursor.into_inner())
+                .with_name(format!("asset{i}"))
+                .with_compression(2);
+        }
+        let data = builder.build().to_vec(Endian::Little).unwrap();
+        let package = Package::read(&data, Endian::Little).unwrap();
+
+        let manifest: PackageManifest =
+            serde_wasm_bindgen::from_value(list_assets(&data).unwrap()).unwrap();
+        assert_eq!(manifest, package.manifest());
+        for asset in &package.assets {
+            let extracted = extract_asset(&data, &asset.id.to_string()).unwrap();
+            assert_eq!(extracted, asset.data().unwrap());
+        }
+        assert!(extract_asset(&data, &Uuid::nil().to_string()).is_err());
+        assert!(extract_asset(&data, "not a uuid").is_err());
+        assert!(list_assets(&data[..16]).is_err());
+    }
+}